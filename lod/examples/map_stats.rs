@@ -0,0 +1,10 @@
+use lod::{get_lod_path, map_stats::MapStatsReport, LodManager};
+
+/// Parses every map in games.lod and prints what we could read out of it.
+/// The lod folder is taken from OPENMM_6_PATH or from the first argument.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let lod_path = std::env::args().nth(1).unwrap_or_else(get_lod_path);
+    let lod_manager = LodManager::new(lod_path)?;
+    print!("{}", MapStatsReport::new(&lod_manager)?);
+    Ok(())
+}
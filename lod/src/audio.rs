@@ -0,0 +1,330 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{utils::try_read_string_block, zlib};
+
+const NAME_SIZE: usize = 40;
+/// MM6 entries have a name, an offset and a size
+const MM6_ENTRY_SIZE: usize = NAME_SIZE + 8;
+/// MM7 and MM8 entries add the decompressed size
+const MM7_ENTRY_SIZE: usize = MM6_ENTRY_SIZE + 4;
+const PCM_FORMAT: u16 = 1;
+
+struct SndEntry {
+    offset: usize,
+    size: usize,
+    /// Same as `size` when the sound is stored uncompressed
+    decompressed_size: usize,
+}
+
+/// Where the stored sounds are read from
+enum SndData {
+    Memory(Vec<u8>),
+    /// Archive file, read a sound at a time
+    File(PathBuf),
+}
+
+/// Sound archive, audio.snd in the data directory. Every entry is a wav file.
+/// An opened archive only keeps its index in memory.
+pub struct SndArchive {
+    data: SndData,
+    entries: HashMap<String, SndEntry>,
+}
+
+impl SndArchive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = BufReader::new(File::open(&path)?);
+        let size = file.get_ref().metadata()?.len();
+        let entries = read_entries(&mut file, size)?;
+        Ok(Self {
+            data: SndData::File(path),
+            entries,
+        })
+    }
+
+    /// Sound names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.entries.keys().map(|k| k.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(&name.to_lowercase())
+    }
+
+    /// Wav file of a sound, the lookup is case-insensitive
+    pub fn wav(&self, name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let entry = self
+            .entries
+            .get(&name.to_lowercase())
+            .ok_or_else(|| format!("sound {} not found", name))?;
+        let data = self
+            .read(entry)
+            .map_err(|e| format!("unable to read sound {}: {}", name, e))?;
+        if entry.decompressed_size == entry.size {
+            Ok(data)
+        } else {
+            zlib::decompress(&data, entry.size, entry.decompressed_size)
+        }
+    }
+
+    pub fn sound(&self, name: &str) -> Result<Sound, Box<dyn Error>> {
+        Sound::try_from(self.wav(name)?.as_slice())
+    }
+
+    fn read(&self, entry: &SndEntry) -> Result<Vec<u8>, Box<dyn Error>> {
+        match &self.data {
+            SndData::Memory(data) => Ok(data
+                .get(entry.offset..entry.offset + entry.size)
+                .ok_or("entry past the end of the archive")?
+                .to_vec()),
+            SndData::File(path) => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(entry.offset as u64))?;
+                let mut data = vec![0; entry.size];
+                file.read_exact(&mut data)?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+impl TryFrom<Vec<u8>> for SndArchive {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: Vec<u8>) -> Result<Self, Self::Error> {
+        let entries = read_entries(&mut Cursor::new(data.as_slice()), data.len() as u64)?;
+        Ok(Self {
+            data: SndData::Memory(data),
+            entries,
+        })
+    }
+}
+
+/// Index of an archive of `size` bytes
+fn read_entries<R: Read + Seek>(
+    reader: &mut R,
+    size: u64,
+) -> Result<HashMap<String, SndEntry>, Box<dyn Error>> {
+    let count = reader.read_u32::<LittleEndian>()? as usize;
+    // the entries follow the count, so the first offset tells their size
+    let entry_size = if count == 0 {
+        MM7_ENTRY_SIZE
+    } else {
+        reader.seek(SeekFrom::Start((4 + NAME_SIZE) as u64))?;
+        let first_offset = reader.read_u32::<LittleEndian>()? as usize;
+        match first_offset.checked_sub(4).map(|size| size / count) {
+            Some(MM6_ENTRY_SIZE) => MM6_ENTRY_SIZE,
+            Some(MM7_ENTRY_SIZE) => MM7_ENTRY_SIZE,
+            _ => return Err("Unknown sound archive layout".into()),
+        }
+    };
+    if 4 + (count * entry_size) as u64 > size {
+        return Err("Sound archive index is truncated".into());
+    }
+
+    let mut index = vec![0; count * entry_size];
+    reader.seek(SeekFrom::Start(4))?;
+    reader.read_exact(&mut index)?;
+    let mut cursor = Cursor::new(index.as_slice());
+    let mut entries = HashMap::with_capacity(count);
+    for i in 0..count {
+        cursor.seek(SeekFrom::Start((i * entry_size) as u64))?;
+        let name = try_read_string_block(&mut cursor, NAME_SIZE)?.to_lowercase();
+        let offset = cursor.read_u32::<LittleEndian>()? as usize;
+        let size = cursor.read_u32::<LittleEndian>()? as usize;
+        let decompressed_size = if entry_size == MM7_ENTRY_SIZE {
+            cursor.read_u32::<LittleEndian>()? as usize
+        } else {
+            size
+        };
+        entries.insert(
+            name,
+            SndEntry {
+                offset,
+                size,
+                decompressed_size,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+/// Decoded PCM sound
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sound {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    /// Interleaved little endian samples
+    pub samples: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for Sound {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let mut cursor = Cursor::new(data);
+        let mut tag = [0; 4];
+        cursor.read_exact(&mut tag)?;
+        let _riff_size = cursor.read_u32::<LittleEndian>()?;
+        let mut wave = [0; 4];
+        cursor.read_exact(&mut wave)?;
+        if &tag != b"RIFF" || &wave != b"WAVE" {
+            return Err("Not a wav file".into());
+        }
+
+        let mut format = None;
+        while (cursor.position() as usize) < data.len() {
+            cursor.read_exact(&mut tag)?;
+            let size = cursor.read_u32::<LittleEndian>()? as usize;
+            let start = cursor.position() as usize;
+            match &tag {
+                b"fmt " => {
+                    let format_tag = cursor.read_u16::<LittleEndian>()?;
+                    if format_tag != PCM_FORMAT {
+                        return Err(format!("Unsupported wav format {}", format_tag).into());
+                    }
+                    let channels = cursor.read_u16::<LittleEndian>()?;
+                    let sample_rate = cursor.read_u32::<LittleEndian>()?;
+                    cursor.seek(SeekFrom::Current(6))?;
+                    let bits_per_sample = cursor.read_u16::<LittleEndian>()?;
+                    format = Some((channels, sample_rate, bits_per_sample));
+                }
+                b"data" => {
+                    let (channels, sample_rate, bits_per_sample) =
+                        format.ok_or("Wav data before its format")?;
+                    // some files have a data size past the end
+                    let end = (start + size).min(data.len());
+                    return Ok(Self {
+                        channels,
+                        sample_rate,
+                        bits_per_sample,
+                        samples: data[start..end].to_vec(),
+                    });
+                }
+                _ => {}
+            }
+            // chunks are padded to an even size
+            cursor.seek(SeekFrom::Start((start + size + size % 2) as u64))?;
+        }
+        Err("Wav file without data".into())
+    }
+}
+
+impl Sound {
+    /// Canonical PCM wav file
+    pub fn to_wav(&self) -> Vec<u8> {
+        let block_align = self.channels * self.bits_per_sample / 8;
+        let mut wav = Vec::with_capacity(44 + self.samples.len());
+        wav.extend_from_slice(b"RIFF");
+        let _ = wav.write_u32::<LittleEndian>(36 + self.samples.len() as u32);
+        wav.extend_from_slice(b"WAVEfmt ");
+        let _ = wav.write_u32::<LittleEndian>(16);
+        let _ = wav.write_u16::<LittleEndian>(PCM_FORMAT);
+        let _ = wav.write_u16::<LittleEndian>(self.channels);
+        let _ = wav.write_u32::<LittleEndian>(self.sample_rate);
+        let _ = wav.write_u32::<LittleEndian>(self.sample_rate * block_align as u32);
+        let _ = wav.write_u16::<LittleEndian>(block_align);
+        let _ = wav.write_u16::<LittleEndian>(self.bits_per_sample);
+        wav.extend_from_slice(b"data");
+        let _ = wav.write_u32::<LittleEndian>(self.samples.len() as u32);
+        wav.extend_from_slice(&self.samples);
+        wav
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_wav())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::{SndArchive, Sound, MM6_ENTRY_SIZE, MM7_ENTRY_SIZE, NAME_SIZE};
+
+    fn sound() -> Sound {
+        Sound {
+            channels: 1,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            samples: vec![1, 2, 3, 4],
+        }
+    }
+
+    fn archive(entries: &[(&str, Vec<u8>, usize)], entry_size: usize) -> Vec<u8> {
+        let mut data = (entries.len() as u32).to_le_bytes().to_vec();
+        let mut offset = 4 + entries.len() * entry_size;
+        for (name, stored, size) in entries {
+            let mut entry = name.as_bytes().to_vec();
+            entry.resize(NAME_SIZE, 0);
+            entry.extend((offset as u32).to_le_bytes());
+            entry.extend((stored.len() as u32).to_le_bytes());
+            if entry_size == MM7_ENTRY_SIZE {
+                entry.extend((*size as u32).to_le_bytes());
+            }
+            data.extend(entry);
+            offset += stored.len();
+        }
+        for (_, stored, _) in entries {
+            data.extend(stored);
+        }
+        data
+    }
+
+    #[test]
+    fn wav_round_trip_works() {
+        let wav = sound().to_wav();
+        assert_eq!(wav.len(), 44 + 4);
+        assert_eq!(Sound::try_from(wav.as_slice()).unwrap(), sound());
+        assert!(Sound::try_from(&wav[..20]).is_err());
+    }
+
+    #[test]
+    fn snd_archive_works() {
+        let wav = sound().to_wav();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&wav).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mm7 = archive(
+            &[
+                ("Swing", wav.clone(), wav.len()),
+                ("door", compressed, wav.len()),
+            ],
+            MM7_ENTRY_SIZE,
+        );
+        let snd = SndArchive::try_from(mm7).unwrap();
+        assert_eq!(snd.names(), vec!["door", "swing"]);
+        assert_eq!(snd.wav("SWING").unwrap(), wav);
+        assert_eq!(snd.sound("door").unwrap(), sound());
+        assert!(snd.wav("missing").is_err());
+        assert!(snd.contains("Door") && !snd.contains("missing"));
+
+        let mm6 = archive(&[("swing", wav.clone(), wav.len())], MM6_ENTRY_SIZE);
+        let dir = crate::TempDir::new("audio");
+        let path = dir.join("audio.snd");
+        std::fs::write(&path, &mm6).unwrap();
+        let opened = SndArchive::open(&path).unwrap();
+        assert_eq!(opened.wav("swing").unwrap(), wav);
+        std::fs::write(&path, &mm6[..30]).unwrap();
+        assert!(SndArchive::open(&path).is_err());
+        assert_eq!(
+            SndArchive::try_from(mm6).unwrap().wav("swing").unwrap(),
+            wav
+        );
+    }
+}
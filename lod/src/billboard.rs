@@ -1,6 +1,6 @@
 use std::{
     error::Error,
-    io::{Cursor, Read},
+    io::{Cursor, Read, Write},
 };
 
 use image::{DynamicImage, GenericImageView};
@@ -8,7 +8,7 @@ use image::{DynamicImage, GenericImageView};
 use crate::{
     ddeclist::{DDecList, DDecListItem},
     dsft::{DSFTFrame, DSFT},
-    utils::try_read_string_block,
+    utils::{read_string_block, string_block},
     LodManager,
 };
 
@@ -58,9 +58,13 @@ impl BillboardData {
 #[derive(Default, Debug)]
 pub struct Billboard {
     pub declist_name: String,
+    /// `declist_name` as stored, with the bytes after the terminator
+    pub declist_name_block: [u8; DECLIST_NAME_SIZE],
     pub data: BillboardData,
 }
 
+const DECLIST_NAME_SIZE: usize = 32;
+
 pub(super) fn read_billboards(
     cursor: &mut Cursor<&[u8]>,
     count: usize,
@@ -78,15 +82,17 @@ pub(super) fn read_billboards(
 
     let mut billboard_names = Vec::new();
     for _i in 0..count {
-        let name = try_read_string_block(cursor, 32);
-        billboard_names.push(name?.to_lowercase());
+        let mut block = [0; DECLIST_NAME_SIZE];
+        cursor.read_exact(&mut block)?;
+        billboard_names.push((read_string_block(&block)?.to_lowercase(), block));
     }
 
     let billboards = billboards_data
         .into_iter()
         .zip(billboard_names)
-        .map(|(data, name)| Billboard {
+        .map(|(data, (name, block))| Billboard {
             declist_name: name,
+            declist_name_block: block,
             data,
         })
         .collect();
@@ -94,6 +100,28 @@ pub(super) fn read_billboards(
     Ok(billboards)
 }
 
+/// Writes the billboards back the way `read_billboards` reads them
+pub(super) fn write_billboards<W: Write>(
+    writer: &mut W,
+    billboards: &[Billboard],
+) -> Result<(), Box<dyn Error>> {
+    for billboard in billboards {
+        writer.write_all(unsafe {
+            std::slice::from_raw_parts(
+                &billboard.data as *const _ as *const u8,
+                std::mem::size_of::<BillboardData>(),
+            )
+        })?;
+    }
+    for billboard in billboards {
+        writer.write_all(&string_block(
+            &billboard.declist_name,
+            &billboard.declist_name_block,
+        )?)?;
+    }
+    Ok(())
+}
+
 pub struct BillboardManager {
     d_declist: DDecList,
     d_sft: DSFT,
@@ -128,22 +156,30 @@ impl BillboardManager {
         Ok(Self { d_declist, d_sft })
     }
 
-    pub fn get(
+    /// Name of the sprite used for a billboard, falls back to "pending" when there is none.
+    pub fn sprite_name(
         &self,
         lod_manager: &LodManager,
         name: &str,
         declist_id: u16,
-    ) -> Option<BillboardSprite> {
+    ) -> Option<String> {
         let declist_item = self.d_declist.items.get(declist_id as usize)?;
         let sft_frame = self.d_sft.frames.get(declist_item.sft_index() as usize)?;
 
-        let image = if let Some(image) = lod_manager.sprite(&declist_item.name()?) {
-            image
-        } else if let Some(image) = lod_manager.sprite(&sft_frame.sprite_name()?) {
-            image
-        } else if let Some(image) = lod_manager.sprite(name) {
-            image
-        } else {
+        let exists = |name: &String| {
+            lod_manager
+                .try_get_bytes(format!("sprites/{}", name))
+                .is_ok()
+        };
+        let sprite_name = [
+            declist_item.name(),
+            sft_frame.sprite_name(),
+            Some(name.to_string()),
+        ]
+        .into_iter()
+        .flatten()
+        .find(exists);
+        if sprite_name.is_none() {
             dbg!(format!(
                 "failed to read entity: id:{}|name:{:?}|game_name:{:?}, sft group_name:{:?}|sprite_name:{:?}",
                 declist_item.sft_index(),
@@ -152,8 +188,19 @@ impl BillboardManager {
                 sft_frame.group_name(),
                 sft_frame.sprite_name()
             ));
-            lod_manager.sprite("pending").unwrap()
-        };
+        }
+        Some(sprite_name.unwrap_or_else(|| "pending".to_string()))
+    }
+
+    pub fn get(
+        &self,
+        lod_manager: &LodManager,
+        name: &str,
+        declist_id: u16,
+    ) -> Option<BillboardSprite> {
+        let declist_item = self.d_declist.items.get(declist_id as usize)?;
+        let sft_frame = self.d_sft.frames.get(declist_item.sft_index() as usize)?;
+        let image = lod_manager.sprite(&self.sprite_name(lod_manager, name, declist_id)?)?;
 
         Some(BillboardSprite {
             image,
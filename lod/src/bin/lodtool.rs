@@ -0,0 +1,13 @@
+use std::{env, error::Error, process::Command};
+
+/// Alias kept for the old `lodtool list | inspect [--annotate] | layouts` commands, which are
+/// now openmm-extract commands. Runs the openmm-extract built next to it with the same arguments.
+fn main() -> Result<(), Box<dyn Error>> {
+    let extract =
+        env::current_exe()?.with_file_name(format!("openmm-extract{}", env::consts::EXE_SUFFIX));
+    let status = Command::new(&extract)
+        .args(env::args_os().skip(1))
+        .status()
+        .map_err(|e| format!("unable to run {}: {}", extract.display(), e))?;
+    std::process::exit(status.code().unwrap_or(1));
+}
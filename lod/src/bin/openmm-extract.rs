@@ -0,0 +1,177 @@
+use std::{error::Error, fs, path::Path};
+
+use lod::{
+    checksums::{verify_install, ChecksumManifest},
+    get_lod_path, inspect, LodManager,
+};
+
+const USAGE: &str = "usage: openmm-extract [--data <dir>] <command>
+commands:
+  archives                        list the archives found
+  list <archive>                  list the entries of an archive
+  find <pattern>                  list the entries matching a pattern like sprites/gm*
+  source <[archive/]entry>        show which lod file supplies an entry
+  inspect <archive/entry> [--annotate]
+                                  hexdump an entry, --annotate shows the header fields
+  layouts                         print the header fields --annotate knows
+  extract <archive/entry> [file]  write the raw bytes of an entry
+  png <archive/entry> [file]      convert a bitmap, sprite, icon or pcx picture to png
+  dump <archive> <dir>            extract a whole archive, images as png
+  checksums                       print the checksum manifest of the install
+  verify <manifest>               compare the install with a checksum manifest
+  gltf <map> <file>               export an .odm or .blv map to .gltf or .glb (gltf feature)
+  impostor <sprite> <file>        export the eight views of a sprite as an impostor mesh (gltf feature)";
+
+/// Image of an entry, decoded the way its archive stores images
+fn image(lod_manager: &LodManager, path: &str) -> Option<image::DynamicImage> {
+    let (archive, name) = path.split_once('/')?;
+    match archive.to_lowercase().as_str() {
+        "sprites" => lod_manager.sprite(name),
+        "bitmaps" => lod_manager.bitmap(name),
+        "icons" => lod_manager.icon(name),
+        _ => None,
+    }
+    .or_else(|| lod_manager.screen(name))
+}
+
+/// Output file of an entry, the entry name in the current directory when not given
+fn output(path: &str, file: Option<&&str>, extension: Option<&str>) -> String {
+    file.map(|f| f.to_string()).unwrap_or_else(|| {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        match extension {
+            Some(extension) => format!("{}.{}", name, extension),
+            None => name.to_string(),
+        }
+    })
+}
+
+fn print_fields(fields: &[inspect::Field]) {
+    for field in fields {
+        println!(
+            "{:>4x}..{:<4x} {:<18} {}",
+            field.range.start, field.range.end, field.name, field.value
+        );
+    }
+}
+
+/// Extracts resources from the game archives. The lod folder is taken from `--data`, or from
+/// OPENMM_6_PATH when not given.
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let data_path = match args.iter().position(|a| a == "--data") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.remove(i + 1);
+            args.remove(i);
+            path
+        }
+        Some(_) => return Err(USAGE.into()),
+        None => get_lod_path(),
+    };
+    if args.first().is_some_and(|a| a == "layouts") {
+        print!("{}", inspect::layouts());
+        return Ok(());
+    }
+    let lod_manager = LodManager::new(data_path)?;
+
+    match args
+        .iter()
+        .map(|a| a.as_str())
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["archives"] => {
+            for archive in lod_manager.archives() {
+                println!("{}", archive);
+            }
+        }
+        ["list", archive] => {
+            let mut files = lod_manager
+                .files(&archive.to_lowercase())
+                .ok_or_else(|| format!("{} not found", archive))?;
+            files.sort();
+            for file in files {
+                println!("{}", file);
+            }
+        }
+        ["find", pattern] => {
+            for (archive, entry) in lod_manager.glob(pattern) {
+                println!("{}/{}", archive, entry);
+            }
+        }
+        ["source", path] => {
+            let source = lod_manager
+                .source(path)
+                .ok_or_else(|| format!("{} not found", path))?;
+            println!("{}", source);
+        }
+        ["inspect", path, flags @ ..] if flags.iter().all(|f| *f == "--annotate") => {
+            let data = lod_manager.try_get_bytes(path)?;
+            let fields = if flags.is_empty() {
+                Vec::new()
+            } else {
+                let archive = path.split('/').next().unwrap_or_default();
+                inspect::annotate(archive, data)
+            };
+            println!("{}: {} bytes", path, data.len());
+            print_fields(&fields);
+            print!("{}", inspect::hexdump(data, &fields));
+            // maps are compressed, their own header is in the decompressed bytes
+            if let Some((contents, fields)) = (!flags.is_empty())
+                .then(|| inspect::annotate_contents(path, data))
+                .flatten()
+            {
+                println!("decompressed: {} bytes", contents.len());
+                print_fields(&fields);
+                let end = fields.iter().map(|f| f.range.end).max().unwrap_or(0);
+                print!("{}", inspect::hexdump(&contents[..end], &fields));
+            }
+        }
+        ["extract", path, file @ ..] if file.len() <= 1 => {
+            let data = lod_manager.try_get_bytes(path)?;
+            let file = output(path, file.first(), None);
+            fs::write(&file, data)?;
+            println!("{} -> {}", path, file);
+        }
+        ["png", path, file @ ..] if file.len() <= 1 => {
+            let image =
+                image(&lod_manager, path).ok_or_else(|| format!("{} is not an image", path))?;
+            let file = output(path, file.first(), Some("png"));
+            image.save(&file)?;
+            println!("{} -> {}", path, file);
+        }
+        ["dump", archive, dir] => {
+            lod_manager.save_archive(archive, Path::new(dir))?;
+            println!("{} -> {}", archive, dir);
+        }
+        ["checksums"] => print!("{}", ChecksumManifest::from_lod_manager(&lod_manager)),
+        ["verify", manifest] => {
+            let manifest: ChecksumManifest = fs::read_to_string(manifest)?.parse()?;
+            let report = verify_install(&lod_manager, &manifest);
+            print!("{}", report);
+            if !report.is_ok() {
+                return Err("the install differs from the manifest".into());
+            }
+        }
+        #[cfg(feature = "gltf")]
+        ["gltf", map, file] => {
+            let scene = if map.to_lowercase().ends_with(".blv") {
+                lod::gltf::export_indoor(
+                    &lod_manager,
+                    &lod::blv::IndoorMap::new(&lod_manager, map)?,
+                )?
+            } else {
+                lod::gltf::export_odm(&lod_manager, &lod::odm::Odm::new(&lod_manager, map)?)?
+            };
+            scene.save(file)?;
+            println!("{} -> {}", map, file);
+        }
+        #[cfg(feature = "gltf")]
+        ["impostor", sprite, file] => {
+            let impostor = lod::impostor::Impostor::new(&lod_manager, sprite)?;
+            lod::gltf::export_impostor(sprite, &impostor).save(file)?;
+            println!("{} -> {}", sprite, file);
+        }
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
+}
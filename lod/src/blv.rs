@@ -0,0 +1,669 @@
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{layout::layout, lod_data::LodData, utils::try_read_string_block, LodManager, Version};
+
+/// The section sizes follow the unused part of the header
+const HEADER_SIZES_OFFSET: usize = 104;
+const TEXTURE_NAME_SIZE: usize = 10;
+/// Per face arrays in `faces_data`: vertex ids, x, y and z displacements, u and v
+const FACE_DATA_ARRAYS: usize = 6;
+
+layout! {
+    /// Fixed part of an indoor map before the vertices
+    pub(crate) struct BlvHeaderRecord {
+        unknown: [u8; HEADER_SIZES_OFFSET],
+        faces_data_size: u32,
+        rooms_data_size: u32,
+        rooms_lights_data_size: u32,
+        doors_data_size: u32,
+        reserved: [u8; 16],
+    }
+}
+
+/// Byte sizes of the variable length sections
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BlvHeader {
+    /// Header bytes before the sizes, not decoded
+    pub unknown: Vec<u8>,
+    /// Vertex ids, displacements and texture coordinates of the faces
+    pub faces_data_size: u32,
+    /// Face, portal and decoration lists of the rooms
+    pub rooms_data_size: u32,
+    /// Light lists of the rooms
+    pub rooms_lights_data_size: u32,
+    /// Vertex, face and room lists of the doors, stored in the map delta
+    pub doors_data_size: u32,
+    /// Header bytes after the sizes, not decoded
+    pub reserved: Vec<u8>,
+}
+
+impl BlvHeader {
+    fn read(cursor: &mut Cursor<&[u8]>) -> Result<Self, Box<dyn Error>> {
+        let header = BlvHeaderRecord::read(cursor)?;
+        Ok(Self {
+            unknown: header.unknown.to_vec(),
+            faces_data_size: header.faces_data_size,
+            rooms_data_size: header.rooms_data_size,
+            rooms_lights_data_size: header.rooms_lights_data_size,
+            doors_data_size: header.doors_data_size,
+            reserved: header.reserved.to_vec(),
+        })
+    }
+}
+
+layout! {
+    /// Face record of MM6, MM7 and MM8 put a float plane in front of it
+    pub struct BlvFace {
+        /// Normal scaled by 65536 and distance to the origin
+        plane: [i32; 4],
+        /// Coefficients giving the z of a point of the face from its x and y
+        z_calc: [i32; 3],
+        attributes: u32,
+        /// Pointers to the face data, fixed up by the game at load time
+        pointers: [u8; 24],
+        face_extra_id: u16,
+        bitmap_id: u16,
+        room_id: u16,
+        /// Room behind a portal
+        back_room_id: i16,
+        bounding_box: [i16; 6],
+        polygon_type: u8,
+        vertex_count: u8,
+        unknown: [u8; 2],
+    }
+}
+
+impl BlvFace {
+    pub fn is_portal(&self) -> bool {
+        (self.attributes & 0x00000001) != 0
+    }
+
+    pub fn is_invisible(&self) -> bool {
+        (self.attributes & 0x00002000) != 0
+    }
+}
+
+layout! {
+    /// Texture offsets and events of a face
+    pub struct BlvFaceExtra {
+        unknown: [u8; 14],
+        additional_bitmap_id: u16,
+        unknown_2: [u8; 4],
+        texture_delta_u: i16,
+        texture_delta_v: i16,
+        cog_number: i16,
+        event_id: u16,
+        unknown_3: [u8; 8],
+    }
+}
+
+layout! {
+    /// Room record, each count is followed by a pointer fixed up at load time. The lists are
+    /// stored in the rooms data in field order.
+    pub(crate) struct RoomRecord {
+        flags: u32,
+        floors: u16,
+        floors_pointer: [u8; 6],
+        walls: u16,
+        walls_pointer: [u8; 6],
+        ceilings: u16,
+        ceilings_pointer: [u8; 6],
+        fluids: u16,
+        fluids_pointer: [u8; 6],
+        portals: u16,
+        portals_pointer: [u8; 6],
+        faces: u16,
+        non_bsp_faces: u16,
+        faces_pointer: [u8; 4],
+        cylinder_faces: u16,
+        cylinder_faces_pointer: [u8; 6],
+        cogs: u16,
+        cogs_pointer: [u8; 6],
+        decorations: u16,
+        decorations_pointer: [u8; 6],
+        markers: u16,
+        markers_pointer: [u8; 6],
+        lights: u16,
+        lights_pointer: [u8; 6],
+        water_level: i16,
+        mist_level: i16,
+        light_distance_multiplier: i16,
+        min_ambient_light: i16,
+        first_bsp_node: i16,
+        exit_tag: i16,
+        bounding_box: [i16; 6],
+    }
+}
+
+/// Room ("sector") of an indoor map, the lists hold face ids unless noted
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Room {
+    pub flags: u32,
+    pub floors: Vec<u16>,
+    pub walls: Vec<u16>,
+    pub ceilings: Vec<u16>,
+    pub fluids: Vec<u16>,
+    /// Portal faces leading to the neighbouring rooms
+    pub portals: Vec<u16>,
+    pub faces: Vec<u16>,
+    /// Leading faces of `faces` left out of the BSP tree
+    pub non_bsp_faces: u16,
+    pub cylinder_faces: Vec<u16>,
+    pub cogs: Vec<u16>,
+    /// Decoration ids
+    pub decorations: Vec<u16>,
+    pub markers: Vec<u16>,
+    /// Light ids
+    pub lights: Vec<u16>,
+    pub water_level: i16,
+    pub mist_level: i16,
+    pub light_distance_multiplier: i16,
+    pub min_ambient_light: i16,
+    pub first_bsp_node: i16,
+    pub exit_tag: i16,
+    pub bounding_box: [i16; 6],
+}
+
+/// Takes the first `count` values of a list section
+fn take_list(list: &mut &[u16], count: u16, section: &str) -> Result<Vec<u16>, Box<dyn Error>> {
+    if list.len() < count as usize {
+        return Err(format!("Indoor map {} data is truncated", section).into());
+    }
+    let (taken, rest) = list.split_at(count as usize);
+    *list = rest;
+    Ok(taken.to_vec())
+}
+
+impl Room {
+    fn new(
+        record: RoomRecord,
+        data: &mut &[u16],
+        lights: &mut &[u16],
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            flags: record.flags,
+            floors: take_list(data, record.floors, "rooms")?,
+            walls: take_list(data, record.walls, "rooms")?,
+            ceilings: take_list(data, record.ceilings, "rooms")?,
+            fluids: take_list(data, record.fluids, "rooms")?,
+            portals: take_list(data, record.portals, "rooms")?,
+            faces: take_list(data, record.faces, "rooms")?,
+            non_bsp_faces: record.non_bsp_faces,
+            cylinder_faces: take_list(data, record.cylinder_faces, "rooms")?,
+            cogs: take_list(data, record.cogs, "rooms")?,
+            decorations: take_list(data, record.decorations, "rooms")?,
+            markers: take_list(data, record.markers, "rooms")?,
+            lights: take_list(lights, record.lights, "rooms lights")?,
+            water_level: record.water_level,
+            mist_level: record.mist_level,
+            light_distance_multiplier: record.light_distance_multiplier,
+            min_ambient_light: record.min_ambient_light,
+            first_bsp_node: record.first_bsp_node,
+            exit_tag: record.exit_tag,
+            bounding_box: record.bounding_box,
+        })
+    }
+}
+
+/// Portal face between two rooms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Portal {
+    pub face_id: usize,
+    pub room_id: u16,
+    pub back_room_id: i16,
+}
+
+layout! {
+    /// Door record of the map delta, the pointers are fixed up at load time
+    pub(crate) struct DoorRecord {
+        attributes: u32,
+        id: u32,
+        time_since_triggered: u32,
+        direction: [i32; 3],
+        move_length: i32,
+        open_speed: i32,
+        close_speed: i32,
+        pointers: [u8; 32],
+        vertices: u16,
+        faces: u16,
+        rooms: u16,
+        offsets: u16,
+        state: u16,
+        unknown: [u8; 2],
+    }
+}
+
+/// Moving part of an indoor map
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Door {
+    pub id: u32,
+    pub attributes: u32,
+    /// Unit vector scaled by 65536
+    pub direction: [i32; 3],
+    pub move_length: i32,
+    pub open_speed: i32,
+    pub close_speed: i32,
+    /// 0 open, 1 closing, 2 closed, 3 opening
+    pub state: u16,
+    pub vertex_ids: Vec<u16>,
+    pub face_ids: Vec<u16>,
+    pub room_ids: Vec<u16>,
+    /// Texture offsets of each face when the door is closed
+    pub texture_deltas: Vec<[i16; 2]>,
+    /// Positions of the vertices when the door is closed
+    pub offsets: Vec<[i16; 3]>,
+}
+
+impl Door {
+    fn new(record: DoorRecord, data: &mut &[u16]) -> Result<Self, Box<dyn Error>> {
+        let vertex_ids = take_list(data, record.vertices, "doors")?;
+        let face_ids = take_list(data, record.faces, "doors")?;
+        let room_ids = take_list(data, record.rooms, "doors")?;
+        let u = take_list(data, record.faces, "doors")?;
+        let v = take_list(data, record.faces, "doors")?;
+        let x = take_list(data, record.offsets, "doors")?;
+        let y = take_list(data, record.offsets, "doors")?;
+        let z = take_list(data, record.offsets, "doors")?;
+        Ok(Self {
+            id: record.id,
+            attributes: record.attributes,
+            direction: record.direction,
+            move_length: record.move_length,
+            open_speed: record.open_speed,
+            close_speed: record.close_speed,
+            state: record.state,
+            vertex_ids,
+            face_ids,
+            room_ids,
+            texture_deltas: u
+                .iter()
+                .zip(&v)
+                .map(|(&u, &v)| [u as i16, v as i16])
+                .collect(),
+            offsets: x
+                .iter()
+                .zip(&y)
+                .zip(&z)
+                .map(|((&x, &y), &z)| [x as i16, y as i16, z as i16])
+                .collect(),
+        })
+    }
+}
+
+/// Indoor map from a .blv file, in the MM6 layout. Decorations, lights, the BSP tree and the
+/// map outlines follow the door count in `unparsed`.
+#[derive(Debug, Default)]
+pub struct IndoorMap {
+    pub name: String,
+    pub header: BlvHeader,
+    pub vertices: Vec<[i16; 3]>,
+    pub faces: Vec<BlvFace>,
+    /// Per face vertex ids, displacements and texture coordinates as stored in the file
+    pub faces_data: Vec<i16>,
+    pub face_textures: Vec<String>,
+    pub face_extras: Vec<BlvFaceExtra>,
+    pub face_extra_textures: Vec<String>,
+    pub rooms: Vec<Room>,
+    /// The doors themselves are stored in the map delta, see `doors`
+    pub door_count: u32,
+    pub unparsed: Vec<u8>,
+}
+
+/// Polygon of an indoor map, decoded from a face record and its part of `faces_data`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IndoorFace {
+    pub attributes: u32,
+    /// Ids in `IndoorMap::vertices`, in order
+    pub vertex_ids: Vec<u16>,
+    /// Texture coordinates of each vertex in texels
+    pub texels: Vec<[f32; 2]>,
+    pub texture: String,
+}
+
+impl IndoorFace {
+    pub fn is_portal(&self) -> bool {
+        (self.attributes & 0x00000001) != 0
+    }
+
+    pub fn is_invisible(&self) -> bool {
+        (self.attributes & 0x00002000) != 0
+    }
+}
+
+impl IndoorMap {
+    /// Polygons of the map in face order. Each array of `faces_data` has one more value than
+    /// the face has vertices, the first vertex repeated. Stops at the first face whose data is
+    /// truncated.
+    pub fn polygons(&self) -> Vec<IndoorFace> {
+        let mut polygons = Vec::with_capacity(self.faces.len());
+        let mut offset = 0;
+        for (face, texture) in self.faces.iter().zip(&self.face_textures) {
+            let count = face.vertex_count as usize;
+            let arrays = match self
+                .faces_data
+                .get(offset..offset + FACE_DATA_ARRAYS * (count + 1))
+            {
+                Some(arrays) => arrays,
+                None => break,
+            };
+            offset += arrays.len();
+            let array = |i: usize| &arrays[i * (count + 1)..i * (count + 1) + count];
+            polygons.push(IndoorFace {
+                attributes: face.attributes,
+                vertex_ids: array(0).iter().map(|&id| id as u16).collect(),
+                texels: array(4)
+                    .iter()
+                    .zip(array(5))
+                    .map(|(&u, &v)| [u as f32, v as f32])
+                    .collect(),
+                texture: texture.clone(),
+            });
+        }
+        polygons
+    }
+
+    /// Portal faces with the rooms they join
+    pub fn portals(&self) -> Vec<Portal> {
+        self.faces
+            .iter()
+            .enumerate()
+            .filter(|(_, face)| face.is_portal())
+            .map(|(face_id, face)| Portal {
+                face_id,
+                room_id: face.room_id,
+                back_room_id: face.back_room_id,
+            })
+            .collect()
+    }
+
+    /// Doors from the door section of the map delta (.dlv): `door_count` records followed by
+    /// `doors_data_size` bytes of lists.
+    pub fn doors(&self, section: &[u8]) -> Result<Vec<Door>, Box<dyn Error>> {
+        let records_size = self.door_count as usize * DoorRecord::SIZE;
+        let data_size = self.header.doors_data_size as usize;
+        if section.len() < records_size + data_size {
+            return Err("Door section is truncated".into());
+        }
+        let mut records = &section[..records_size];
+        let data: Vec<u16> = section[records_size..records_size + data_size]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        let mut data = data.as_slice();
+        (0..self.door_count)
+            .map(|_| Door::new(DoorRecord::read(&mut records)?, &mut data))
+            .collect()
+    }
+
+    /// Only MM6 maps are supported, the later games use bigger records
+    pub fn new(lod_manager: &LodManager, name: &str) -> Result<Self, Box<dyn Error>> {
+        if let Some(version) = lod_manager.version("games").filter(|v| *v != Version::MM6) {
+            return Err(format!("Indoor maps of {:?} are not supported", version).into());
+        }
+        let data = LodData::try_from(lod_manager.try_get_bytes(format!("games/{}", name))?)?;
+        let mut map = Self::try_from(data.data.as_slice())?;
+        map.name = name.to_string();
+        Ok(map)
+    }
+}
+
+/// Reads `count` records, checking the data holds them before allocating
+fn read_records<'a, T>(
+    cursor: &mut Cursor<&'a [u8]>,
+    size: usize,
+    read: impl Fn(&mut Cursor<&'a [u8]>) -> std::io::Result<T>,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let count = cursor.read_u32::<LittleEndian>()? as usize;
+    let left = cursor.get_ref().len() - cursor.position() as usize;
+    if count.saturating_mul(size) > left {
+        return Err("Indoor map records are truncated".into());
+    }
+    (0..count).map(|_| Ok(read(cursor)?)).collect()
+}
+
+fn read_names(cursor: &mut Cursor<&[u8]>, count: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut names = Vec::with_capacity(count);
+    for _ in 0..count {
+        if cursor.position() as usize + TEXTURE_NAME_SIZE > cursor.get_ref().len() {
+            return Err("Texture names are truncated".into());
+        }
+        names.push(try_read_string_block(cursor, TEXTURE_NAME_SIZE)?);
+    }
+    Ok(names)
+}
+
+/// Reads a list section of `size` bytes
+fn read_list(cursor: &mut Cursor<&[u8]>, size: u32) -> Result<Vec<u16>, Box<dyn Error>> {
+    let mut list = vec![0; size as usize / 2];
+    cursor.read_u16_into::<LittleEndian>(&mut list)?;
+    Ok(list)
+}
+
+impl TryFrom<&[u8]> for IndoorMap {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let mut cursor = Cursor::new(data);
+        let header = BlvHeader::read(&mut cursor)?;
+
+        let vertices = read_records(&mut cursor, 6, |cursor| {
+            Ok([
+                cursor.read_i16::<LittleEndian>()?,
+                cursor.read_i16::<LittleEndian>()?,
+                cursor.read_i16::<LittleEndian>()?,
+            ])
+        })?;
+        let faces = read_records(&mut cursor, BlvFace::SIZE, BlvFace::read)?;
+        let mut faces_data = vec![0; header.faces_data_size as usize / 2];
+        cursor.read_i16_into::<LittleEndian>(&mut faces_data)?;
+        let face_textures = read_names(&mut cursor, faces.len())?;
+
+        let face_extras = read_records(&mut cursor, BlvFaceExtra::SIZE, BlvFaceExtra::read)?;
+        let face_extra_textures = read_names(&mut cursor, face_extras.len())?;
+
+        let room_records = read_records(&mut cursor, RoomRecord::SIZE, RoomRecord::read)?;
+        let rooms_data = read_list(&mut cursor, header.rooms_data_size)?;
+        let rooms_lights = read_list(&mut cursor, header.rooms_lights_data_size)?;
+        let (mut rooms_data, mut rooms_lights) = (rooms_data.as_slice(), rooms_lights.as_slice());
+        let rooms = room_records
+            .into_iter()
+            .map(|record| Room::new(record, &mut rooms_data, &mut rooms_lights))
+            .collect::<Result<_, _>>()?;
+
+        let door_count = cursor.read_u32::<LittleEndian>()?;
+        let mut unparsed = Vec::new();
+        cursor.read_to_end(&mut unparsed)?;
+
+        Ok(Self {
+            name: String::new(),
+            header,
+            vertices,
+            faces,
+            faces_data,
+            face_textures,
+            face_extras,
+            face_extra_textures,
+            rooms,
+            door_count,
+            unparsed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BlvFace, BlvFaceExtra, BlvHeaderRecord, DoorRecord, IndoorMap, Portal, RoomRecord,
+        HEADER_SIZES_OFFSET,
+    };
+
+    fn face(attributes: u32, vertex_count: u8) -> BlvFace {
+        BlvFace {
+            plane: [0, 0, 65536, -10],
+            z_calc: [0; 3],
+            attributes,
+            pointers: [0; 24],
+            face_extra_id: 0,
+            bitmap_id: 0,
+            room_id: 1,
+            back_room_id: 2,
+            bounding_box: [0; 6],
+            polygon_type: 1,
+            vertex_count,
+            unknown: [0; 2],
+        }
+    }
+
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn indoor_map_works() {
+        let rooms_data = u16s(&[0, 1, 0, 0, 3]);
+        let rooms_lights = u16s(&[4]);
+        let mut data = vec![0; BlvHeaderRecord::SIZE];
+        data[0] = 5;
+        for (i, size) in [4, rooms_data.len(), rooms_lights.len(), 6]
+            .into_iter()
+            .enumerate()
+        {
+            let offset = HEADER_SIZES_OFFSET + i * 4;
+            data[offset..offset + 4].copy_from_slice(&(size as u32).to_le_bytes());
+        }
+        data[BlvHeaderRecord::SIZE - 1] = 6;
+        data.extend(2u32.to_le_bytes());
+        for v in [1i16, 2, 3, -4, -5, -6] {
+            data.extend(v.to_le_bytes());
+        }
+        data.extend(1u32.to_le_bytes());
+        face(0x2001, 3).write(&mut data).unwrap();
+        data.extend(u16s(&[0, 1]));
+        data.extend(b"dirt\0\0\0\0\0\0");
+
+        data.extend(1u32.to_le_bytes());
+        let mut extra = vec![0; BlvFaceExtra::SIZE];
+        extra[26] = 12;
+        data.extend(extra);
+        data.extend(b"lava\0\0\0\0\0\0");
+
+        data.extend(1u32.to_le_bytes());
+        let mut room = vec![0; RoomRecord::SIZE];
+        // two floors, one portal, one light
+        room[4] = 2;
+        room[36] = 1;
+        room[84] = 1;
+        room[92] = 7;
+        data.extend(room);
+        data.extend(&rooms_data);
+        data.extend(&rooms_lights);
+        data.extend(1u32.to_le_bytes());
+        data.extend([9, 9]);
+
+        let mut map = IndoorMap::try_from(data.as_slice()).unwrap();
+        assert_eq!(map.header.faces_data_size, 4);
+        assert_eq!(map.header.unknown[0], 5);
+        assert_eq!(map.header.reserved.len(), 16);
+        assert_eq!(map.header.reserved[15], 6);
+        assert_eq!(map.vertices, vec![[1, 2, 3], [-4, -5, -6]]);
+        assert_eq!(map.faces, vec![face(0x2001, 3)]);
+        assert!(map.faces[0].is_portal() && map.faces[0].is_invisible());
+        assert_eq!(map.faces_data, vec![0, 1]);
+        assert_eq!(map.face_textures, vec!["dirt"]);
+        assert_eq!(map.face_extras[0].event_id, 12);
+        assert_eq!(map.face_extra_textures, vec!["lava"]);
+        assert_eq!(map.rooms.len(), 1);
+        assert_eq!(map.rooms[0].floors, vec![0, 1]);
+        assert_eq!(map.rooms[0].portals, vec![0]);
+        assert!(map.rooms[0].walls.is_empty());
+        assert_eq!(map.rooms[0].lights, vec![4]);
+        assert_eq!(map.rooms[0].water_level, 7);
+        assert_eq!(
+            map.portals(),
+            vec![Portal {
+                face_id: 0,
+                room_id: 1,
+                back_room_id: 2
+            }]
+        );
+        assert_eq!(map.door_count, 1);
+        assert_eq!(map.unparsed, vec![9, 9]);
+
+        assert!(IndoorMap::try_from(&data[..BlvHeaderRecord::SIZE + 10]).is_err());
+        // the room lists are cut short
+        let lists = data.len() - 6 - rooms_lights.len() - rooms_data.len();
+        let mut truncated = data[..lists].to_vec();
+        truncated.extend(u16s(&[0, 1, 0]));
+        assert!(IndoorMap::try_from(truncated.as_slice()).is_err());
+
+        // a door moving one vertex of two faces in one room
+        let door = DoorRecord {
+            attributes: 0,
+            id: 3,
+            time_since_triggered: 0,
+            direction: [0, 0, 65536],
+            move_length: 128,
+            open_speed: 10,
+            close_speed: 20,
+            pointers: [0; 32],
+            vertices: 1,
+            faces: 2,
+            rooms: 1,
+            offsets: 1,
+            state: 2,
+            unknown: [0; 2],
+        };
+        let mut section = Vec::new();
+        door.write(&mut section).unwrap();
+        map.header.doors_data_size = 2 * 11;
+        section.extend(u16s(&[5, 7, 8, 1, 16, 32, 0, 0, 0, 100, 200]));
+        let doors = map.doors(&section).unwrap();
+        assert_eq!(doors.len(), 1);
+        assert_eq!(
+            (doors[0].id, doors[0].state, doors[0].move_length),
+            (3, 2, 128)
+        );
+        assert_eq!(doors[0].vertex_ids, vec![5]);
+        assert_eq!(doors[0].face_ids, vec![7, 8]);
+        assert_eq!(doors[0].room_ids, vec![1]);
+        assert_eq!(doors[0].texture_deltas, vec![[16, 0], [32, 0]]);
+        assert_eq!(doors[0].offsets, vec![[0, 100, 200]]);
+        assert!(map.doors(&section[..section.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn polygons_work() {
+        let mut faces_data = Vec::new();
+        for array in [
+            [0, 1, 2, 0],
+            [0; 4],
+            [0; 4],
+            [0; 4],
+            [5, 6, 7, 5],
+            [8, 9, 10, 8],
+        ] {
+            faces_data.extend(array);
+        }
+        let mut map = IndoorMap {
+            faces: vec![face(1, 3), face(1, 3)],
+            faces_data,
+            face_textures: vec!["dirt".into(), "truncated".into()],
+            ..Default::default()
+        };
+
+        let polygons = map.polygons();
+        assert_eq!(polygons.len(), 1);
+        assert!(polygons[0].is_portal());
+        assert_eq!(polygons[0].vertex_ids, vec![0, 1, 2]);
+        assert_eq!(polygons[0].texels, vec![[5., 8.], [6., 9.], [7., 10.]]);
+        assert_eq!(polygons[0].texture, "dirt");
+
+        map.faces_data.extend_from_slice(&map.faces_data.clone());
+        assert_eq!(map.polygons().len(), 2);
+    }
+}
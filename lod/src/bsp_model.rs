@@ -1,20 +1,23 @@
 use std::{
     error::Error,
-    io::{Cursor, Read, Seek},
+    io::{Cursor, Read, Write},
     ops::{Add, Div, Mul, Sub},
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::utils::try_read_string_block;
+use crate::utils::{read_string_block, string_block};
 
 #[derive(Debug)]
 pub struct BSPModel {
     pub header: BSPModelHeader,
     pub vertices: Vec<[f32; 3]>,
     pub faces: Vec<BSPModelFace>,
-    unk: Vec<u8>,
+    /// Two bytes per face following the faces, not decoded
+    pub unk: Vec<u8>,
     pub texture_names: Vec<String>,
+    /// `texture_names` as stored, with the bytes after the terminator
+    pub texture_name_blocks: Vec<[u8; TEXTURE_NAME_MAX_SIZE]>,
     pub bsp_nodes: Vec<BSPNode>,
     pub indices: Vec<u32>,
 }
@@ -24,16 +27,18 @@ pub struct BSPModel {
 pub struct BSPModelHeader {
     pub name: String,
     pub name2: String,
+    /// `name` and `name2` as stored, with the bytes after the terminator
+    pub name_blocks: [[u8; MODEL_NAME_MAX_SIZE]; 2],
     pub attributes: i32,
     pub vertex_count: i32,
-    // p_vertexes: *mut i32,
+    /// Vertices pointer of the game, meaningless in the file but kept as read
+    pub unknown_a: [u8; 4],
     pub faces_count: i32, // faces_order_count ?
-    // convexFacetsCount: i32, ?? u16
-    // p_faces: *mut i32,
-    // p_unk_array: *mut i32,
+    /// Convex facets count, faces and ordering pointers, not decoded
+    pub unknown_b: [u8; 12],
     pub bsp_nodes_count: i32,
-    //unk03a: i32,
-    //unk03b: i32,
+    /// Two unknown values
+    pub unknown_c: [u8; 8],
     pub grid: [i32; 2],
     pub position: [i32; 3],
     pub bounding_box: BoundingBox<i32>,
@@ -100,6 +105,24 @@ pub struct BSPModelFace {
 }
 
 impl BSPModelFace {
+    /// Ids of the polygon vertices in `BSPModel::vertices`, in order
+    pub fn vertex_ids(&self) -> &[u16] {
+        let count = (self.vertices_count as usize).min(MAX_FACE_VERTICES_COUNT);
+        &self.vertices_ids[..count]
+    }
+
+    /// Texture coordinates of each polygon vertex in texels, the face offset applied
+    pub fn texels(&self) -> Vec<[f32; 2]> {
+        (0..self.vertex_ids().len())
+            .map(|i| {
+                [
+                    self.texture_u_ids[i] as f32 + self.texture_u as f32,
+                    self.texture_v_ids[i] as f32 + self.texture_v as f32,
+                ]
+            })
+            .collect()
+    }
+
     pub fn is_portal(&self) -> bool {
         (self.attributes & 0x00000001) != 0
     }
@@ -203,8 +226,8 @@ pub struct BSPNode {
     pub faces_count: i16,
 }
 
-const MODEL_NAME_MAX_SIZE: usize = 32;
-const TEXTURE_NAME_MAX_SIZE: usize = 10;
+pub const MODEL_NAME_MAX_SIZE: usize = 32;
+pub const TEXTURE_NAME_MAX_SIZE: usize = 10;
 
 pub(super) fn read_bsp_models(
     cursor: &mut Cursor<&[u8]>,
@@ -226,6 +249,7 @@ fn read_bsp_model(
         faces: Vec::with_capacity(header.faces_count as usize),
         unk: Vec::with_capacity((header.faces_count * 2) as usize),
         texture_names: Vec::with_capacity(header.faces_count as usize),
+        texture_name_blocks: Vec::with_capacity(header.faces_count as usize),
         bsp_nodes: Vec::new(),
         indices: Vec::new(),
         header,
@@ -249,8 +273,10 @@ fn read_bsp_model(
         model.unk.push(cursor.read_u8()?);
     }
     for _i in 0..model.header.faces_count {
-        let texture_name: String = try_read_string_block(cursor, TEXTURE_NAME_MAX_SIZE)?;
-        model.texture_names.push(texture_name);
+        let mut block = [0; TEXTURE_NAME_MAX_SIZE];
+        cursor.read_exact(&mut block)?;
+        model.texture_names.push(read_string_block(&block)?);
+        model.texture_name_blocks.push(block);
     }
     model.indices = decode_indices(&model);
     let bsp_nodes_count = model.header.bsp_nodes_count;
@@ -279,18 +305,27 @@ fn read_bsp_model_headers(
 }
 
 fn read_bsp_model_header(cursor: &mut Cursor<&[u8]>) -> Result<BSPModelHeader, Box<dyn Error>> {
+    let mut name_blocks = [[0; MODEL_NAME_MAX_SIZE]; 2];
+    for block in &mut name_blocks {
+        cursor.read_exact(block)?;
+    }
     let mut header = BSPModelHeader {
-        name: try_read_string_block(cursor, MODEL_NAME_MAX_SIZE)?,
-        name2: try_read_string_block(cursor, MODEL_NAME_MAX_SIZE)?,
+        name: read_string_block(&name_blocks[0])?,
+        name2: read_string_block(&name_blocks[1])?,
+        name_blocks,
         attributes: cursor.read_i32::<LittleEndian>()?,
         vertex_count: cursor.read_i32::<LittleEndian>()?,
         ..Default::default()
     };
-    cursor.seek(std::io::SeekFrom::Current(4))?;
+    cursor.read_exact(&mut header.unknown_a)?;
     header.faces_count = cursor.read_i32::<LittleEndian>()?;
-    cursor.seek(std::io::SeekFrom::Current(12))?;
+    cursor.read_exact(&mut header.unknown_b)?;
     header.bsp_nodes_count = cursor.read_i32::<LittleEndian>()?;
-    cursor.seek(std::io::SeekFrom::Current(16))?;
+    cursor.read_exact(&mut header.unknown_c)?;
+    header.grid = [
+        cursor.read_i32::<LittleEndian>()?,
+        cursor.read_i32::<LittleEndian>()?,
+    ];
     header.position = [
         cursor.read_i32::<LittleEndian>()?,
         cursor.read_i32::<LittleEndian>()?,
@@ -321,6 +356,73 @@ fn read_bsp_model_header(cursor: &mut Cursor<&[u8]>) -> Result<BSPModelHeader, B
     Ok(header)
 }
 
+/// Writes the models back the way `read_bsp_models` reads them, headers first
+pub(super) fn write_bsp_models<W: Write>(
+    writer: &mut W,
+    models: &[BSPModel],
+) -> Result<(), Box<dyn Error>> {
+    for model in models {
+        write_bsp_model_header(writer, &model.header)?;
+    }
+    for model in models {
+        write_bsp_model(writer, model)?;
+    }
+    Ok(())
+}
+
+fn write_bsp_model<W: Write>(writer: &mut W, model: &BSPModel) -> Result<(), Box<dyn Error>> {
+    for [x, y, z] in &model.vertices {
+        for value in [*x, -*z, *y] {
+            writer.write_i32::<LittleEndian>(value as i32)?;
+        }
+    }
+    for face in &model.faces {
+        writer.write_all(unsafe {
+            std::slice::from_raw_parts(
+                face as *const _ as *const u8,
+                std::mem::size_of::<BSPModelFace>(),
+            )
+        })?;
+    }
+    writer.write_all(&model.unk)?;
+    for (name, block) in model.texture_names.iter().zip(&model.texture_name_blocks) {
+        writer.write_all(&string_block(name, block)?)?;
+    }
+    for node in &model.bsp_nodes {
+        writer.write_i32::<LittleEndian>(node.front)?;
+        writer.write_i32::<LittleEndian>(node.back)?;
+        writer.write_i16::<LittleEndian>(node.face_id_offset)?;
+        writer.write_i16::<LittleEndian>(node.faces_count)?;
+    }
+    Ok(())
+}
+
+fn write_bsp_model_header<W: Write>(
+    writer: &mut W,
+    header: &BSPModelHeader,
+) -> Result<(), Box<dyn Error>> {
+    writer.write_all(&string_block(&header.name, &header.name_blocks[0])?)?;
+    writer.write_all(&string_block(&header.name2, &header.name_blocks[1])?)?;
+    writer.write_i32::<LittleEndian>(header.attributes)?;
+    writer.write_i32::<LittleEndian>(header.vertex_count)?;
+    writer.write_all(&header.unknown_a)?;
+    writer.write_i32::<LittleEndian>(header.faces_count)?;
+    writer.write_all(&header.unknown_b)?;
+    writer.write_i32::<LittleEndian>(header.bsp_nodes_count)?;
+    writer.write_all(&header.unknown_c)?;
+    let mut values = header.grid.to_vec();
+    values.extend(header.position);
+    for b in [&header.bounding_box, &header.bounding_box_bf] {
+        values.extend([b.min_x, b.min_y, b.min_z, b.max_x, b.max_y, b.max_z]);
+    }
+    values.extend(header.position_box);
+    values.push(header.bounding_radius);
+    for value in values {
+        writer.write_i32::<LittleEndian>(value)?;
+    }
+    Ok(())
+}
+
 fn decode_vertices(input: Vec<f32>) -> Vec<[f32; 3]> {
     input
         .chunks_exact(3)
@@ -349,7 +451,6 @@ fn decode_indices(model: &BSPModel) -> Vec<u32> {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
     use crate::{get_lod_path, odm::Odm, LodManager};
 
     #[test]
@@ -0,0 +1,151 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::image::Image;
+
+/// Counters to tune the cache budget
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    pub evicted_bytes: usize,
+    /// Bytes currently held by the cache
+    pub size: usize,
+}
+
+struct CacheEntry {
+    image: Arc<Image>,
+    size: usize,
+    last_used: u64,
+}
+
+/// Decoded images evicted least recently used first once over budget.
+/// Images are kept as palette indices, a quarter of the RGBA size, and shared so they can be
+/// expanded once the cache is no longer locked.
+#[derive(Default)]
+pub(crate) struct ImageCache {
+    entries: HashMap<String, CacheEntry>,
+    /// No limit when `None`
+    budget: Option<usize>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl ImageCache {
+    pub fn get(&mut self, key: &str) -> Option<Arc<Image>> {
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = self.tick;
+                self.stats.hits += 1;
+                Some(entry.image.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: String, image: Image) {
+        self.tick += 1;
+        let size = image.size();
+        let entry = CacheEntry {
+            image: Arc::new(image),
+            size,
+            last_used: self.tick,
+        };
+        if let Some(previous) = self.entries.insert(key, entry) {
+            self.stats.size -= previous.size;
+        }
+        self.stats.size += size;
+        if let Some(budget) = self.budget {
+            self.trim_to(budget);
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+        if let Some(budget) = budget {
+            self.trim_to(budget);
+        }
+    }
+
+    /// Evicts the least recently used images until the cache holds at most `bytes`.
+    pub fn trim_to(&mut self, bytes: usize) {
+        while self.stats.size > bytes {
+            let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.stats.size -= entry.size;
+                self.stats.evictions += 1;
+                self.stats.evicted_bytes += entry.size;
+            }
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImageCache;
+    use crate::image::Image;
+
+    fn image(pixels: usize) -> Image {
+        Image {
+            height: 1,
+            width: pixels,
+            data: vec![0; pixels],
+            palette: [0; 768],
+            transparency: false,
+        }
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let size = image(10).size();
+        let mut cache = ImageCache::default();
+        cache.set_budget(Some(2 * size));
+        cache.insert("a".into(), image(10));
+        cache.insert("b".into(), image(10));
+        assert!(cache.get("a").is_some());
+        cache.insert("c".into(), image(10));
+
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.get("a").unwrap().width, 10);
+        assert!(cache.get("c").is_some());
+        let stats = cache.stats();
+        assert_eq!(stats.size, 2 * size);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.evicted_bytes, size);
+        assert_eq!((stats.hits, stats.misses), (3, 1));
+    }
+
+    #[test]
+    fn trim_to_works() {
+        let mut cache = ImageCache::default();
+        cache.insert("a".into(), image(10));
+        cache.insert("a".into(), image(20));
+        cache.insert("b".into(), image(10));
+        assert_eq!(cache.stats().size, image(20).size() + image(10).size());
+
+        cache.trim_to(image(10).size());
+        assert_eq!(cache.stats().size, image(10).size());
+        assert!(cache.get("b").is_some());
+        cache.trim_to(0);
+        assert_eq!(cache.stats().size, 0);
+    }
+}
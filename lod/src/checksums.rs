@@ -0,0 +1,193 @@
+use std::{collections::BTreeMap, error::Error, fmt::Display, str::FromStr};
+
+use crate::{
+    lod::crc32,
+    progress::{NoProgress, ProgressCounter, ProgressSink},
+    LodManager,
+};
+
+/// CRC32 of every entry of an install, by lod path like "bitmaps/grastyl".
+/// Written as one `path crc32` line per entry, lines starting with `#` are comments.
+/// No manifests of the released versions ship with the crate, they are generated with
+/// `openmm-extract checksums` on a known good install.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChecksumManifest {
+    pub entries: BTreeMap<String, u32>,
+}
+
+impl ChecksumManifest {
+    /// Checksums of everything `lod_manager` has, run it on a known good install to get the
+    /// manifest of its version.
+    pub fn from_lod_manager(lod_manager: &LodManager) -> Self {
+        Self::from_lod_manager_with_progress(lod_manager, &NoProgress).unwrap_or_default()
+    }
+
+    /// Same as `from_lod_manager`, reporting each entry checksummed
+    pub fn from_lod_manager_with_progress(
+        lod_manager: &LodManager,
+        progress: &dyn ProgressSink,
+    ) -> Result<Self, Box<dyn Error>> {
+        let paths: Vec<String> = lod_manager
+            .archives()
+            .into_iter()
+            .flat_map(|archive| {
+                let directory = lod_manager.directory(archive);
+                lod_manager
+                    .files(archive)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(move |entry| Some(*entry) != directory)
+                    .map(move |entry| format!("{}/{}", archive, entry))
+            })
+            .collect();
+        let progress = ProgressCounter::new(progress, paths.len());
+        let mut entries = BTreeMap::new();
+        for path in paths {
+            if let Ok(data) = lod_manager.try_get_bytes(&path) {
+                entries.insert(path, crc32(data));
+            }
+            if !progress.step() {
+                break;
+            }
+        }
+        progress.result()?;
+        Ok(Self { entries })
+    }
+}
+
+impl FromStr for ChecksumManifest {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = BTreeMap::new();
+        for line in s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            let (path, crc) = line
+                .rsplit_once(char::is_whitespace)
+                .ok_or_else(|| format!("invalid checksum line: {}", line))?;
+            let crc = u32::from_str_radix(crc, 16)
+                .map_err(|_| format!("invalid checksum line: {}", line))?;
+            entries.insert(path.trim().to_lowercase(), crc);
+        }
+        Ok(Self { entries })
+    }
+}
+
+impl Display for ChecksumManifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (path, crc) in &self.entries {
+            writeln!(f, "{} {:08x}", path, crc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences between an install and a checksum manifest
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InstallReport {
+    pub missing: Vec<String>,
+    /// path, expected CRC32, actual CRC32
+    pub changed: Vec<(String, u32, u32)>,
+    /// Entries the manifest doesn't list, like the ones added by mods
+    pub extra: Vec<String>,
+}
+
+impl InstallReport {
+    /// Whether every entry of the manifest is there unchanged, extra entries are fine
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Display for InstallReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for path in &self.missing {
+            writeln!(f, "missing {}", path)?;
+        }
+        for (path, expected, actual) in &self.changed {
+            writeln!(f, "changed {} {:08x} -> {:08x}", path, expected, actual)?;
+        }
+        for path in &self.extra {
+            writeln!(f, "extra   {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the install of `lod_manager` with the manifest of a known good one
+pub fn verify_install(lod_manager: &LodManager, manifest: &ChecksumManifest) -> InstallReport {
+    verify_install_with_progress(lod_manager, manifest, &NoProgress).unwrap_or_default()
+}
+
+/// Same as `verify_install`, reporting each entry of the install checksummed
+pub fn verify_install_with_progress(
+    lod_manager: &LodManager,
+    manifest: &ChecksumManifest,
+    progress: &dyn ProgressSink,
+) -> Result<InstallReport, Box<dyn Error>> {
+    let actual = ChecksumManifest::from_lod_manager_with_progress(lod_manager, progress)?;
+    let mut report = InstallReport::default();
+    for (path, expected) in &manifest.entries {
+        match actual.entries.get(path) {
+            None => report.missing.push(path.clone()),
+            Some(crc) if crc != expected => report.changed.push((path.clone(), *expected, *crc)),
+            Some(_) => {}
+        }
+    }
+    report.extra = actual
+        .entries
+        .keys()
+        .filter(|path| !manifest.entries.contains_key(*path))
+        .cloned()
+        .collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_install, verify_install_with_progress, ChecksumManifest};
+    use crate::{lod::crc32, TempDir};
+
+    #[test]
+    fn verify_install_works() {
+        let dir = TempDir::new("checksums");
+        let lod_manager = dir.lod_manager(&[(
+            "games",
+            &[
+                ("same", b"same"),
+                ("changed", b"modded"),
+                ("extra", b"extra"),
+            ],
+        )]);
+
+        let manifest: ChecksumManifest = format!(
+            "# known good\ngames/same {:08x}\ngames/changed {:08x}\nGames/Missing 0000beef\n",
+            crc32(b"same"),
+            crc32(b"original")
+        )
+        .parse()
+        .unwrap();
+        let report = verify_install(&lod_manager, &manifest);
+        assert_eq!(report.missing, vec!["games/missing"]);
+        assert_eq!(
+            report.changed,
+            vec![("games/changed".into(), crc32(b"original"), crc32(b"modded"))]
+        );
+        assert_eq!(report.extra, vec!["games/extra"]);
+        assert!(!report.is_ok());
+
+        let generated = ChecksumManifest::from_lod_manager(&lod_manager);
+        assert_eq!(
+            generated.to_string().parse::<ChecksumManifest>().unwrap(),
+            generated
+        );
+        assert!(verify_install(&lod_manager, &generated).is_ok());
+        assert!("games/same nothex".parse::<ChecksumManifest>().is_err());
+
+        let cancel = |done: usize, _total: usize| done < 2;
+        assert!(verify_install_with_progress(&lod_manager, &manifest, &cancel).is_err());
+    }
+}
@@ -4,8 +4,13 @@ use std::{
 };
 
 use byteorder::{LittleEndian, ReadBytesExt};
+use image::RgbaImage;
 
-use crate::{lod_data::LodData, utils::try_read_name, LodManager};
+use crate::{
+    lod_data::LodData,
+    utils::{frame_at, frame_group, try_read_name},
+    LodManager,
+};
 
 pub struct DSFT {
     pub frames: Vec<DSFTFrame>,
@@ -101,8 +106,34 @@ impl DSFTFrame {
 impl DSFT {
     pub fn new(lod_manager: &LodManager) -> Result<Self, Box<dyn Error>> {
         let data = LodData::try_from(lod_manager.try_get_bytes("icons/dsft.bin")?)?;
-        let data = data.data.as_slice();
+        Self::try_from(data.data.as_slice())
+    }
+
+    /// Frames of the group `name`, like a monster action or an animated decoration
+    pub fn group(&self, name: &str) -> Option<&[DSFTFrame]> {
+        let name = name.to_lowercase();
+        let start = self
+            .frames
+            .iter()
+            .position(|f| f.is_group_start() && f.group_name().as_deref() == Some(&name))
+            .or_else(|| {
+                // single frame groups don't always have the start flag
+                self.frames
+                    .iter()
+                    .position(|f| f.group_name().as_deref() == Some(&name))
+            })?;
+        Some(frame_group(
+            &self.frames,
+            start,
+            DSFTFrame::is_not_group_end,
+        ))
+    }
+}
+
+impl TryFrom<&[u8]> for DSFT {
+    type Error = Box<dyn Error>;
 
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
         let mut cursor = Cursor::new(data);
 
         let mut frames = Vec::new();
@@ -128,11 +159,178 @@ impl DSFT {
     }
 }
 
+/// What an actor is doing, each action plays its own frame group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpriteAction {
+    Stand,
+    Walk,
+    Attack,
+    Shoot,
+    Stun,
+    Die,
+    Dead,
+    Fidget,
+}
+
+/// Frame of an action, `duration` is in 1/16 of a second
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationFrame {
+    pub sprite: String,
+    pub duration: u32,
+}
+
+impl AnimationFrame {
+    /// Sprite of the frame seen from `view`, 0 being the front. Sprites with several views have
+    /// the view appended to their name, the others are the same from every side.
+    pub fn image(&self, lod_manager: &LodManager, view: u8) -> Option<RgbaImage> {
+        let image = lod_manager
+            .sprite(&format!("{}{}", self.sprite, view))
+            .or_else(|| lod_manager.sprite(&self.sprite))?;
+        Some(image.to_rgba8())
+    }
+}
+
+/// Frames of an actor grouped by action. The frame group of each action comes from the monster
+/// list, the frames and their durations from the sprite frame table.
+#[derive(Debug, Clone, Default)]
+pub struct SpriteAnimation {
+    pub actions: Vec<(SpriteAction, Vec<AnimationFrame>)>,
+}
+
+impl SpriteAnimation {
+    /// Actions whose group is missing from `dsft` are left out
+    pub fn new(dsft: &DSFT, groups: &[(SpriteAction, &str)]) -> Self {
+        let actions = groups
+            .iter()
+            .filter_map(|(action, group)| {
+                let frames = dsft
+                    .group(group)?
+                    .iter()
+                    .filter_map(|frame| {
+                        Some(AnimationFrame {
+                            sprite: frame.sprite_name()?,
+                            duration: frame.time.max(0) as u32,
+                        })
+                    })
+                    .collect();
+                Some((*action, frames))
+            })
+            .collect();
+        Self { actions }
+    }
+
+    pub fn frames(&self, action: SpriteAction) -> Option<&[AnimationFrame]> {
+        self.actions
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, frames)| frames.as_slice())
+    }
+
+    /// Length of an action in 1/16 of a second
+    pub fn duration(&self, action: SpriteAction) -> u32 {
+        self.frames(action)
+            .map(|frames| frames.iter().map(|f| f.duration).sum())
+            .unwrap_or(0)
+    }
+
+    /// Frame shown `time` 1/16 of a second into an action, looping
+    pub fn frame_at(&self, action: SpriteAction, time: u32) -> Option<&AnimationFrame> {
+        let frames = self.frames(action)?;
+        let durations: Vec<u32> = frames.iter().map(|f| f.duration).collect();
+        frames.get(frame_at(&durations, time)?)
+    }
+
+    /// Every frame of an action seen from `view`, `None` when a sprite is missing
+    pub fn render(
+        &self,
+        lod_manager: &LodManager,
+        action: SpriteAction,
+        view: u8,
+    ) -> Option<Vec<RgbaImage>> {
+        self.frames(action)?
+            .iter()
+            .map(|frame| frame.image(lod_manager, view))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{get_lod_path, LodManager};
 
-    use super::DSFT;
+    use super::{SpriteAction, SpriteAnimation, DSFT};
+
+    /// A dsft.bin frame record
+    fn frame(group: &str, sprite: &str, time: i16, attributes: u16) -> Vec<u8> {
+        let mut data = group.as_bytes().to_vec();
+        data.resize(12, 0);
+        data.extend(sprite.as_bytes());
+        data.resize(24, 0);
+        data.resize(24 + 8 * 2 + 4, 0);
+        data.extend(attributes.to_le_bytes());
+        data.extend([0; 6]);
+        data.extend(time.to_le_bytes());
+        data.extend(0i16.to_le_bytes());
+        data
+    }
+
+    /// A dsft.bin with the given frame records and no group index
+    fn dsft(frames: &[Vec<u8>]) -> DSFT {
+        let mut data = (frames.len() as u32).to_le_bytes().to_vec();
+        data.extend(0u32.to_le_bytes());
+        data.extend(frames.concat());
+        DSFT::try_from(data.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn group_works() {
+        let dsft = dsft(&[
+            frame("null", "null", 0, 0),
+            frame("gobAst", "gobst", 8, 0x5),
+            frame("gobAst", "gobst1", 8, 0),
+            frame("Torch", "torch", 4, 0),
+        ]);
+        assert_eq!(dsft.frames[1].time, 8);
+        assert_eq!(dsft.group("GOBAST").unwrap().len(), 2);
+        assert_eq!(
+            dsft.group("gobast").unwrap()[1].sprite_name().as_deref(),
+            Some("gobst1")
+        );
+        assert_eq!(dsft.group("torch").unwrap().len(), 1);
+        assert!(dsft.group("missing").is_none());
+    }
+
+    #[test]
+    fn sprite_animation_works() {
+        let dsft = dsft(&[
+            frame("gobast", "gobst", 8, 0x5),
+            frame("gobast", "gobsta", 4, 0),
+            frame("gobAwa", "gobwa", 2, 0x5),
+            frame("gobAwa", "gobwaa", 2, 0x1),
+            frame("gobAwa", "gobwab", 2, 0),
+        ]);
+        let animation = SpriteAnimation::new(
+            &dsft,
+            &[
+                (SpriteAction::Stand, "gobast"),
+                (SpriteAction::Walk, "gobawa"),
+                (SpriteAction::Die, "gobadi"),
+            ],
+        );
+        assert_eq!(animation.actions.len(), 2);
+        assert_eq!(animation.frames(SpriteAction::Walk).unwrap().len(), 3);
+        assert!(animation.frames(SpriteAction::Die).is_none());
+        assert_eq!(animation.duration(SpriteAction::Stand), 12);
+        let sprite_at = |action, time| {
+            animation
+                .frame_at(action, time)
+                .map(|frame| frame.sprite.as_str())
+        };
+        assert_eq!(sprite_at(SpriteAction::Stand, 9), Some("gobsta"));
+        assert_eq!(sprite_at(SpriteAction::Stand, 12), Some("gobst"));
+        assert_eq!(sprite_at(SpriteAction::Walk, 5), Some("gobwab"));
+        assert_eq!(sprite_at(SpriteAction::Die, 0), None);
+    }
 
     #[test]
     fn read_declist_data_works() {
@@ -0,0 +1,127 @@
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{
+    lod_data::LodData,
+    utils::{frame_at, frame_group, try_read_name},
+    LodManager,
+};
+
+/// Texture frame table from dtft.bin, the animations of water, lava and the animated faces.
+pub struct DTFT {
+    pub frames: Vec<DTFTFrame>,
+}
+
+#[repr(C)]
+#[derive(Default, Clone)]
+pub struct DTFTFrame {
+    texture_name: [u8; 12],
+    pub texture_index: i16,
+    /// Frame duration, in 1/16 of a second like the sprite frames
+    pub time: i16,
+    /// Duration of the whole animation, only set on the first frame
+    pub time_total: i16,
+    pub attributes: i16,
+}
+
+impl DTFTFrame {
+    pub fn is_not_group_end(&self) -> bool {
+        (self.attributes & 0x0001) != 0
+    }
+
+    pub fn is_group_start(&self) -> bool {
+        (self.attributes & 0x0002) != 0
+    }
+
+    pub fn texture_name(&self) -> Option<String> {
+        try_read_name(&self.texture_name)
+    }
+}
+
+impl DTFT {
+    pub fn new(lod_manager: &LodManager) -> Result<Self, Box<dyn Error>> {
+        let data = LodData::try_from(lod_manager.try_get_bytes("icons/dtft.bin")?)?;
+        Self::try_from(data.data.as_slice())
+    }
+
+    /// Frames of the animation starting with the texture `name`, a single frame for still textures
+    pub fn animation(&self, name: &str) -> Option<&[DTFTFrame]> {
+        let start = self
+            .frames
+            .iter()
+            .position(|f| f.is_group_start() && f.texture_name().as_deref() == Some(name))?;
+        Some(frame_group(
+            &self.frames,
+            start,
+            DTFTFrame::is_not_group_end,
+        ))
+    }
+
+    /// Texture shown `time` 1/16 of a second into the animation starting with `name`
+    pub fn texture_at(&self, name: &str, time: u32) -> Option<String> {
+        let animation = self.animation(name)?;
+        let durations: Vec<u32> = animation.iter().map(|f| f.time.max(0) as u32).collect();
+        animation[frame_at(&durations, time)?].texture_name()
+    }
+}
+
+impl TryFrom<&[u8]> for DTFT {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let mut cursor = Cursor::new(data);
+
+        let mut frames = Vec::new();
+        let frame_count = cursor.read_u32::<LittleEndian>()?;
+        let frame_size = std::mem::size_of::<DTFTFrame>();
+
+        for _ in 0..frame_count {
+            let mut frame = DTFTFrame::default();
+            cursor.read_exact(unsafe {
+                std::slice::from_raw_parts_mut(&mut frame as *mut _ as *mut u8, frame_size)
+            })?;
+            frames.push(frame);
+        }
+
+        Ok(Self { frames })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DTFT;
+
+    fn frame(name: &str, time: i16, attributes: i16) -> Vec<u8> {
+        let mut data = name.as_bytes().to_vec();
+        data.resize(12, 0);
+        data.extend(0i16.to_le_bytes());
+        data.extend(time.to_le_bytes());
+        data.extend(0i16.to_le_bytes());
+        data.extend(attributes.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn animation_works() {
+        let mut data = 4u32.to_le_bytes().to_vec();
+        data.extend(frame("wtrtyl", 8, 3));
+        data.extend(frame("wtrtyla", 4, 1));
+        data.extend(frame("wtrtylb", 4, 0));
+        data.extend(frame("Stone", 0, 2));
+        let dtft = DTFT::try_from(data.as_slice()).unwrap();
+
+        assert_eq!(dtft.animation("wtrtyl").unwrap().len(), 3);
+        assert_eq!(dtft.animation("stone").unwrap().len(), 1);
+        assert!(dtft.animation("wtrtyla").is_none());
+        assert_eq!(dtft.texture_at("wtrtyl", 9).as_deref(), Some("wtrtyla"));
+        assert_eq!(
+            dtft.texture_at("wtrtyl", 16 + 12).as_deref(),
+            Some("wtrtylb")
+        );
+        assert_eq!(dtft.texture_at("stone", 5).as_deref(), Some("stone"));
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{image::get_atlas, lod_data::LodData, utils::try_read_name, LodManager};
+use crate::{
+    image::get_atlas_with_progress,
+    lod_data::LodData,
+    progress::{NoProgress, ProgressSink},
+    utils::try_read_name,
+    LodManager,
+};
 use byteorder::{LittleEndian, ReadBytesExt};
 use image::DynamicImage;
 use std::{
@@ -11,9 +17,45 @@ pub struct Dtile {
     tiles: Vec<Tile>,
 }
 
+/// Terrain kind of a tile set, drives the footstep sounds and the transitions between tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerrainGroup {
+    Grass,
+    Snow,
+    Desert,
+    Volcanic,
+    Dirt,
+    Water,
+    Badlands,
+    Swamp,
+    Tropical,
+    City,
+    Road,
+    Other(i16),
+}
+
+impl From<i16> for TerrainGroup {
+    fn from(tile_set: i16) -> Self {
+        match tile_set {
+            0 => TerrainGroup::Grass,
+            1 => TerrainGroup::Snow,
+            2 => TerrainGroup::Desert,
+            3 => TerrainGroup::Volcanic,
+            4 => TerrainGroup::Dirt,
+            5 => TerrainGroup::Water,
+            6 => TerrainGroup::Badlands,
+            7 => TerrainGroup::Swamp,
+            8 => TerrainGroup::Tropical,
+            9 => TerrainGroup::City,
+            10..=28 => TerrainGroup::Road,
+            _ => TerrainGroup::Other(tile_set),
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Default)]
-struct Tile {
+pub struct Tile {
     name: [u8; 16],
     id: i16,
     bitmap: i16,
@@ -79,6 +121,10 @@ impl Tile {
     pub fn name(&self) -> Option<String> {
         try_read_name(&self.name).map(|v| if v.is_empty() { "pending".into() } else { v })
     }
+
+    pub fn terrain_group(&self) -> TerrainGroup {
+        TerrainGroup::from(self.tile_set)
+    }
 }
 
 impl Dtile {
@@ -101,23 +147,32 @@ impl Dtile {
         Ok(Self { tiles })
     }
 
+    /// Index into dtile.bin of a tile from the odm tile map, `tile_data` comes from the odm header
+    fn tile_index(i: u8, tile_data: [u16; 8]) -> u16 {
+        let i = i as u16;
+        if (90..125).contains(&i) {
+            i - 90 + tile_data[1] // primary
+        } else if (126..161).contains(&i) {
+            i
+            //i - 126 + tile_data[3] // water
+        } else if (162..197).contains(&i) {
+            i - 162 + tile_data[5] // secondary
+        } else if i >= 198 {
+            i - 198 + tile_data[7] // roads
+        } else {
+            i // dirt
+        }
+    }
+
+    /// Tile of an odm tile map entry
+    pub fn tile(&self, i: u8, tile_data: [u16; 8]) -> Option<&Tile> {
+        self.tiles.get(Self::tile_index(i, tile_data) as usize)
+    }
+
     pub fn table(&self, tile_data: [u16; 8]) -> Option<TileTable> {
         let mut names_table: Vec<String> = Vec::with_capacity(256);
-        for i in 0_u16..=255_u16 {
-            let index = if (90..125).contains(&i) {
-                i - 90 + tile_data[1] // primary
-            } else if (126..161).contains(&i) {
-                i
-                //i - 126 + tile_data[3] // water
-            } else if (162..197).contains(&i) {
-                i - 162 + tile_data[5] // secondary
-            } else if i >= 198 {
-                i - 198 + tile_data[7] // roads
-            } else {
-                i // dirt
-            };
-
-            let tile = self.tiles.get(index as usize)?;
+        for i in 0..=255 {
+            let tile = self.tile(i, tile_data)?;
             names_table.push(tile.name().unwrap_or("pending".into()));
         }
 
@@ -192,8 +247,8 @@ impl TileTable {
     fn names_set(name_table: &[String; 256]) -> Vec<String> {
         let mut set: Vec<String> = name_table
             .iter()
-            .cloned()
             .filter(|d| !d.starts_with("drr")) // HACK
+            .cloned()
             .collect();
         set.sort_by(|a, b| {
             if a == "pending" {
@@ -208,14 +263,28 @@ impl TileTable {
         set
     }
 
+    /// Distinct tile bitmaps used by the table
+    pub fn names(&self) -> &[String] {
+        &self.names_set
+    }
+
     pub fn atlas_image(&self, lod_manager: &LodManager) -> Result<DynamicImage, Box<dyn Error>> {
+        self.atlas_image_with_progress(lod_manager, &NoProgress)
+    }
+
+    pub fn atlas_image_with_progress(
+        &self,
+        lod_manager: &LodManager,
+        progress: &dyn ProgressSink,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
         let ts: Vec<&str> = self.names_set.iter().map(|s| s.as_str()).collect();
-        get_atlas(lod_manager, ts.as_slice(), self.size.0 as usize)
+        get_atlas_with_progress(lod_manager, ts.as_slice(), self.size.0 as usize, progress)
     }
 }
 
+#[cfg(test)]
 mod tests {
-    use crate::{dtile::Dtile, get_lod_path, lod_data::LodData, odm::Odm, LodManager};
+    use crate::{dtile::Dtile, get_lod_path, odm::Odm, LodManager};
 
     #[test]
     fn read_dtile_data_works() {
@@ -0,0 +1,269 @@
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{lod_data::LodData, LodManager};
+
+/// Archives that can hold event scripts and string tables, events.lod in MM7/8 and icons.lod in MM6
+const EVENT_ARCHIVES: [&str; 2] = ["events", "icons"];
+/// Length, event id, step and opcode
+const COMMAND_HEADER_SIZE: usize = 5;
+
+fn read_entry(lod_manager: &LodManager, name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let data = EVENT_ARCHIVES
+        .iter()
+        .find_map(|archive| {
+            lod_manager
+                .try_get_bytes(format!("{}/{}", archive, name))
+                .ok()
+        })
+        .ok_or_else(|| format!("{} not found", name))?;
+    Ok(LodData::try_from(data)?.data)
+}
+
+/// String table from a .str file, strings are referenced by index from the event scripts.
+#[derive(Debug, Default)]
+pub struct Text {
+    pub strings: Vec<String>,
+}
+
+impl Text {
+    pub fn new(lod_manager: &LodManager, name: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from(read_entry(lod_manager, name)?.as_slice()))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.strings.get(index).map(|s| s.as_str())
+    }
+}
+
+impl From<&[u8]> for Text {
+    fn from(data: &[u8]) -> Self {
+        let data = data.strip_suffix(&[0]).unwrap_or(data);
+        let strings = if data.is_empty() {
+            Vec::new()
+        } else {
+            data.split(|&b| b == 0)
+                .map(|s| String::from_utf8_lossy(s).to_string())
+                .collect()
+        };
+        Self { strings }
+    }
+}
+
+/// Commands of the event scripts, the ones we don't know the layout of are kept as raw parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventCommand {
+    Exit,
+    EnterHouse {
+        house: u32,
+    },
+    PlaySound {
+        sound: u32,
+        x: i32,
+        y: i32,
+    },
+    /// Text shown when hovering the trigger, index into the map string table
+    Hint {
+        text: u8,
+    },
+    MazeInfo {
+        text: u8,
+    },
+    OpenChest {
+        chest: u8,
+    },
+    /// Jumps to `jump` when `variable` compares true with `value`
+    Compare {
+        variable: u16,
+        value: i32,
+        jump: u8,
+    },
+    Add {
+        variable: u16,
+        value: i32,
+    },
+    Subtract {
+        variable: u16,
+        value: i32,
+    },
+    Set {
+        variable: u16,
+        value: i32,
+    },
+    StatusText {
+        text: u8,
+    },
+    ShowMessage {
+        text: u8,
+    },
+    GoTo {
+        step: u8,
+    },
+    Other {
+        opcode: u8,
+        params: Vec<u8>,
+    },
+}
+
+impl EventCommand {
+    fn new(opcode: u8, params: &[u8]) -> Self {
+        Self::parse(opcode, params).unwrap_or_else(|_| EventCommand::Other {
+            opcode,
+            params: params.to_vec(),
+        })
+    }
+
+    fn parse(opcode: u8, params: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut cursor = Cursor::new(params);
+        let command = match opcode {
+            0x01 => EventCommand::Exit,
+            0x02 => EventCommand::EnterHouse {
+                house: cursor.read_u32::<LittleEndian>()?,
+            },
+            0x03 => EventCommand::PlaySound {
+                sound: cursor.read_u32::<LittleEndian>()?,
+                x: cursor.read_i32::<LittleEndian>()?,
+                y: cursor.read_i32::<LittleEndian>()?,
+            },
+            0x04 => EventCommand::Hint {
+                text: cursor.read_u8()?,
+            },
+            0x05 => EventCommand::MazeInfo {
+                text: cursor.read_u8()?,
+            },
+            0x07 => EventCommand::OpenChest {
+                chest: cursor.read_u8()?,
+            },
+            0x0e => EventCommand::Compare {
+                variable: cursor.read_u16::<LittleEndian>()?,
+                value: cursor.read_i32::<LittleEndian>()?,
+                jump: cursor.read_u8()?,
+            },
+            0x10 => EventCommand::Add {
+                variable: cursor.read_u16::<LittleEndian>()?,
+                value: cursor.read_i32::<LittleEndian>()?,
+            },
+            0x11 => EventCommand::Subtract {
+                variable: cursor.read_u16::<LittleEndian>()?,
+                value: cursor.read_i32::<LittleEndian>()?,
+            },
+            0x12 => EventCommand::Set {
+                variable: cursor.read_u16::<LittleEndian>()?,
+                value: cursor.read_i32::<LittleEndian>()?,
+            },
+            0x1d => EventCommand::StatusText {
+                text: cursor.read_u8()?,
+            },
+            0x1e => EventCommand::ShowMessage {
+                text: cursor.read_u8()?,
+            },
+            0x24 => EventCommand::GoTo {
+                step: cursor.read_u8()?,
+            },
+            _ => return Err("unknown opcode".into()),
+        };
+        Ok(command)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventStep {
+    pub event_id: u16,
+    pub step: u8,
+    pub command: EventCommand,
+}
+
+/// Event script from a .evt file, a flat list of steps grouped by event id.
+#[derive(Debug, Default)]
+pub struct EventScript {
+    pub steps: Vec<EventStep>,
+}
+
+impl EventScript {
+    pub fn new(lod_manager: &LodManager, name: &str) -> Result<Self, Box<dyn Error>> {
+        Self::try_from(read_entry(lod_manager, name)?.as_slice())
+    }
+
+    /// Steps of an event in execution order
+    pub fn event(&self, event_id: u16) -> impl Iterator<Item = &EventStep> {
+        self.steps.iter().filter(move |s| s.event_id == event_id)
+    }
+}
+
+impl TryFrom<&[u8]> for EventScript {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let mut cursor = Cursor::new(data);
+        let mut steps = Vec::new();
+        while (cursor.position() as usize) < data.len() {
+            // the length doesn't include the length byte itself
+            let size = cursor.read_u8()? as usize + 1;
+            if size < COMMAND_HEADER_SIZE {
+                return Err(format!("Malformed event command of {}B", size).into());
+            }
+            let event_id = cursor.read_u16::<LittleEndian>()?;
+            let step = cursor.read_u8()?;
+            let opcode = cursor.read_u8()?;
+            let mut params = vec![0; size - COMMAND_HEADER_SIZE];
+            cursor.read_exact(&mut params)?;
+            steps.push(EventStep {
+                event_id,
+                step,
+                command: EventCommand::new(opcode, &params),
+            });
+        }
+        Ok(Self { steps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventCommand, EventScript, Text};
+
+    #[test]
+    fn text_works() {
+        let text = Text::from(&b"first\0\0third\0"[..]);
+        assert_eq!(text.strings, vec!["first", "", "third"]);
+        assert_eq!(text.get(2), Some("third"));
+        assert!(Text::from(&[][..]).strings.is_empty());
+    }
+
+    #[test]
+    fn event_script_works() {
+        let data = [
+            // event 1: hint 3, set variable 0x0102 to 5, exit
+            5, 1, 0, 0, 0x04, 3, //
+            10, 1, 0, 1, 0x12, 2, 1, 5, 0, 0, 0, //
+            4, 1, 0, 2, 0x01, //
+            // event 2: unknown opcode
+            6, 2, 0, 0, 0x99, 7, 8,
+        ];
+        let script = EventScript::try_from(&data[..]).unwrap();
+        let commands: Vec<_> = script.event(1).map(|s| s.command.clone()).collect();
+        assert_eq!(
+            commands,
+            vec![
+                EventCommand::Hint { text: 3 },
+                EventCommand::Set {
+                    variable: 0x0102,
+                    value: 5
+                },
+                EventCommand::Exit,
+            ]
+        );
+        let other = script.event(2).next().unwrap();
+        assert_eq!(
+            other.command,
+            EventCommand::Other {
+                opcode: 0x99,
+                params: vec![7, 8]
+            }
+        );
+        assert!(EventScript::try_from(&[9, 1, 0][..]).is_err());
+    }
+}
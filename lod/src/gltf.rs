@@ -0,0 +1,671 @@
+//! Export of outdoor and indoor maps to glTF 2.0 so levels can be inspected in Blender or any
+//! other viewer. Textures are embedded as png, positions stay in game units with y up like the
+//! map viewer.
+
+use std::{collections::HashMap, error::Error, io::Cursor, path::Path};
+
+use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+use serde_json::{json, Value};
+
+use crate::{
+    billboard::BillboardManager,
+    blv::IndoorMap,
+    bsp_model::BSPModel,
+    impostor::Impostor,
+    odm::Odm,
+    progress::{NoProgress, ProgressCounter, ProgressSink},
+    terrain::TerrainMesh,
+    LodManager,
+};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+const GLB_MAGIC: u32 = 0x46546c67;
+const GLB_JSON_CHUNK: u32 = 0x4e4f534a;
+const GLB_BIN_CHUNK: u32 = 0x004e4942;
+
+/// Material index and size of its texture
+type TextureMaterial = (usize, (u32, u32));
+
+/// Triangles sharing a material
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Primitive {
+    pub positions: Vec<[f32; 3]>,
+    /// Left empty to let the viewer compute flat normals
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+    pub material: Option<usize>,
+}
+
+impl Primitive {
+    /// Adds a convex polygon as a triangle fan
+    fn push_polygon(&mut self, positions: &[[f32; 3]], uvs: &[[f32; 2]]) {
+        let first = self.positions.len() as u32;
+        self.positions.extend_from_slice(positions);
+        self.uvs.extend_from_slice(uvs);
+        for i in 1..positions.len().saturating_sub(1) as u32 {
+            self.indices.extend([first, first + i, first + i + 1]);
+        }
+    }
+}
+
+/// glTF document being built, everything goes into a single binary buffer
+#[derive(Debug, Default)]
+pub struct GltfScene {
+    nodes: Vec<Value>,
+    meshes: Vec<Value>,
+    materials: Vec<Value>,
+    textures: Vec<Value>,
+    images: Vec<Value>,
+    accessors: Vec<Value>,
+    buffer_views: Vec<Value>,
+    buffer: Vec<u8>,
+    /// Material and texture size by texture name, see `texture_material`
+    texture_materials: HashMap<String, Option<TextureMaterial>>,
+}
+
+impl GltfScene {
+    /// Material showing `image`. Sprites are alpha tested and double sided.
+    pub fn add_material(&mut self, name: &str, image: &DynamicImage, sprite: bool) -> usize {
+        let mut material = json!({
+            "name": name,
+            "pbrMetallicRoughness": { "metallicFactor": 0.0, "roughnessFactor": 1.0 },
+        });
+        let mut png = Vec::new();
+        if image
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .is_ok()
+        {
+            let view = self.push_view(&png, None);
+            self.images
+                .push(json!({ "bufferView": view, "mimeType": "image/png" }));
+            self.textures
+                .push(json!({ "source": self.images.len() - 1 }));
+            material["pbrMetallicRoughness"]["baseColorTexture"] =
+                json!({ "index": self.textures.len() - 1 });
+        }
+        if sprite {
+            material["alphaMode"] = json!("MASK");
+            material["doubleSided"] = json!(true);
+        }
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    /// Mesh made of `primitives`, returns its index for `add_node`. `None` when none of them
+    /// has triangles, glTF meshes need at least one primitive.
+    pub fn add_mesh(&mut self, name: &str, primitives: &[Primitive]) -> Option<usize> {
+        let primitives: Vec<&Primitive> = primitives
+            .iter()
+            .filter(|p| !p.indices.is_empty())
+            .collect();
+        if primitives.is_empty() {
+            return None;
+        }
+        let primitives: Vec<Value> = primitives
+            .into_iter()
+            .map(|p| {
+                let mut attributes = json!({ "POSITION": self.push_positions(&p.positions) });
+                if !p.normals.is_empty() {
+                    attributes["NORMAL"] = json!(self.push_floats(&p.normals, "VEC3"));
+                }
+                if !p.uvs.is_empty() {
+                    attributes["TEXCOORD_0"] = json!(self.push_floats(&p.uvs, "VEC2"));
+                }
+                let mut primitive = json!({
+                    "attributes": attributes,
+                    "indices": self.push_indices(&p.indices),
+                });
+                if let Some(material) = p.material {
+                    primitive["material"] = json!(material);
+                }
+                primitive
+            })
+            .collect();
+        self.meshes
+            .push(json!({ "name": name, "primitives": primitives }));
+        Some(self.meshes.len() - 1)
+    }
+
+    /// Instance of a mesh in the scene
+    pub fn add_node(&mut self, name: &str, mesh: usize, translation: [f32; 3]) {
+        self.nodes
+            .push(json!({ "name": name, "mesh": mesh, "translation": translation }));
+    }
+
+    /// The glTF json, with the buffer referenced as `uri` when given. Empty arrays are left
+    /// out since glTF doesn't allow them.
+    fn document(&self, uri: Option<String>) -> Value {
+        let mut scene = json!({});
+        if !self.nodes.is_empty() {
+            scene["nodes"] = json!((0..self.nodes.len()).collect::<Vec<_>>());
+        }
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "openmm" },
+            "scene": 0,
+            "scenes": [scene],
+        });
+        for (key, values) in [
+            ("nodes", &self.nodes),
+            ("meshes", &self.meshes),
+            ("materials", &self.materials),
+            ("textures", &self.textures),
+            ("images", &self.images),
+            ("accessors", &self.accessors),
+            ("bufferViews", &self.buffer_views),
+        ] {
+            if !values.is_empty() {
+                document[key] = json!(values);
+            }
+        }
+        if !self.buffer.is_empty() {
+            let mut buffer = json!({ "byteLength": self.buffer.len() });
+            if let Some(uri) = uri {
+                buffer["uri"] = json!(uri);
+            }
+            document["buffers"] = json!([buffer]);
+        }
+        document
+    }
+
+    /// Text glTF with the buffer embedded as a base64 data uri
+    pub fn to_gltf(&self) -> String {
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            base64(&self.buffer)
+        );
+        self.document(Some(uri)).to_string()
+    }
+
+    /// Binary glTF, without a binary chunk when the buffer is empty
+    pub fn to_glb(&self) -> Vec<u8> {
+        let mut json = self.document(None).to_string().into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut chunks = vec![(GLB_JSON_CHUNK, json)];
+        if !self.buffer.is_empty() {
+            let mut bin = self.buffer.clone();
+            bin.resize(bin.len().next_multiple_of(4), 0);
+            chunks.push((GLB_BIN_CHUNK, bin));
+        }
+
+        let length = 12
+            + chunks
+                .iter()
+                .map(|(_, chunk)| 8 + chunk.len())
+                .sum::<usize>();
+        let mut glb = Vec::with_capacity(length);
+        for value in [GLB_MAGIC, 2, length as u32] {
+            glb.extend(value.to_le_bytes());
+        }
+        for (kind, chunk) in chunks {
+            glb.extend((chunk.len() as u32).to_le_bytes());
+            glb.extend(kind.to_le_bytes());
+            glb.extend(chunk);
+        }
+        glb
+    }
+
+    /// Writes a .glb file, or a .gltf one with the buffer embedded for any other extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let glb = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("glb"));
+        if glb {
+            std::fs::write(path, self.to_glb())?;
+        } else {
+            std::fs::write(path, self.to_gltf())?;
+        }
+        Ok(())
+    }
+
+    /// Material of a bitmap or sprite by name with its size, made once per name. `None` when
+    /// the image can't be found.
+    fn texture_material(
+        &mut self,
+        lod_manager: &LodManager,
+        name: &str,
+        sprite: bool,
+    ) -> Option<TextureMaterial> {
+        let key = name.to_lowercase();
+        if let Some(material) = self.texture_materials.get(&key) {
+            return *material;
+        }
+        let image = if sprite {
+            lod_manager.sprite(&key)
+        } else {
+            lod_manager.bitmap(&key)
+        };
+        let material =
+            image.map(|image| (self.add_material(&key, &image, sprite), image.dimensions()));
+        self.texture_materials.insert(key, material);
+        material
+    }
+
+    fn push_view(&mut self, data: &[u8], target: Option<u32>) -> usize {
+        // accessors need their data aligned
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": data.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.buffer.extend_from_slice(data);
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_floats<const N: usize>(&mut self, values: &[[f32; N]], kind: &str) -> usize {
+        let data: Vec<u8> = values
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let view = self.push_view(&data, Some(ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": kind,
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn push_positions(&mut self, positions: &[[f32; 3]]) -> usize {
+        let accessor = self.push_floats(positions, "VEC3");
+        let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+        for position in positions {
+            for i in 0..3 {
+                min[i] = min[i].min(position[i]);
+                max[i] = max[i].max(position[i]);
+            }
+        }
+        self.accessors[accessor]["min"] = json!(min);
+        self.accessors[accessor]["max"] = json!(max);
+        accessor
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let data: Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let view = self.push_view(&data, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+/// Outdoor map with its terrain, models and decorations. Decorations become two crossed
+/// quads at their position since billboards don't exist in glTF.
+pub fn export_odm(lod_manager: &LodManager, odm: &Odm) -> Result<GltfScene, Box<dyn Error>> {
+    export_odm_with_progress(lod_manager, odm, &NoProgress)
+}
+
+/// Same as `export_odm`, reporting the terrain and each model and decoration exported
+pub fn export_odm_with_progress(
+    lod_manager: &LodManager,
+    odm: &Odm,
+    progress: &dyn ProgressSink,
+) -> Result<GltfScene, Box<dyn Error>> {
+    let progress = ProgressCounter::new(progress, 1 + odm.bsp_models.len() + odm.billboards.len());
+    let mut scene = GltfScene::default();
+
+    let tile_table = odm.tile_table(lod_manager)?;
+    let atlas = tile_table.atlas_image(lod_manager)?;
+    let terrain = TerrainMesh::new(odm, &tile_table);
+    let material = scene.add_material("terrain", &atlas, false);
+    if let Some(mesh) = scene.add_mesh(
+        "terrain",
+        &[Primitive {
+            positions: terrain.positions,
+            normals: terrain.normals,
+            uvs: terrain.uvs,
+            indices: terrain.indices,
+            material: Some(material),
+        }],
+    ) {
+        scene.add_node("terrain", mesh, [0.; 3]);
+    }
+    if !progress.step() {
+        progress.result()?;
+    }
+
+    for model in &odm.bsp_models {
+        let primitives = model_primitives(&mut scene, lod_manager, model);
+        if let Some(mesh) = scene.add_mesh(&model.header.name, &primitives) {
+            scene.add_node(&model.header.name, mesh, [0.; 3]);
+        }
+        if !progress.step() {
+            progress.result()?;
+        }
+    }
+
+    let billboard_manager = BillboardManager::new(lod_manager)?;
+    let mut sprite_meshes: HashMap<String, usize> = HashMap::new();
+    for billboard in &odm.billboards {
+        if !progress.step() {
+            progress.result()?;
+        }
+        if billboard.data.is_invisible() {
+            continue;
+        }
+        let name = billboard_manager
+            .sprite_name(
+                lod_manager,
+                &billboard.declist_name,
+                billboard.data.declist_id,
+            )
+            .unwrap_or_else(|| billboard.declist_name.clone());
+        let mesh = match sprite_meshes.get(&name) {
+            Some(mesh) => *mesh,
+            None => {
+                let Some((material, (width, height))) =
+                    scene.texture_material(lod_manager, &name, true)
+                else {
+                    continue;
+                };
+                let Some(mesh) = scene.add_mesh(
+                    &name,
+                    &[crossed_quads(width as f32, height as f32, material)],
+                ) else {
+                    continue;
+                };
+                sprite_meshes.insert(name.clone(), mesh);
+                mesh
+            }
+        };
+        let [x, y, z] = billboard.data.position;
+        scene.add_node(
+            &billboard.declist_name,
+            mesh,
+            [x as f32, z as f32, -y as f32],
+        );
+    }
+    Ok(scene)
+}
+
+/// Indoor map geometry, portals and invisible faces left out
+pub fn export_indoor(
+    lod_manager: &LodManager,
+    map: &IndoorMap,
+) -> Result<GltfScene, Box<dyn Error>> {
+    export_indoor_with_progress(lod_manager, map, &NoProgress)
+}
+
+/// Same as `export_indoor`, reporting each face exported
+pub fn export_indoor_with_progress(
+    lod_manager: &LodManager,
+    map: &IndoorMap,
+    progress: &dyn ProgressSink,
+) -> Result<GltfScene, Box<dyn Error>> {
+    let faces = map.polygons();
+    let progress = ProgressCounter::new(progress, faces.len());
+    let mut scene = GltfScene::default();
+    let vertices: Vec<[f32; 3]> = map
+        .vertices
+        .iter()
+        .map(|[x, y, z]| [*x as f32, *z as f32, -*y as f32])
+        .collect();
+
+    let mut primitives: HashMap<String, Primitive> = HashMap::new();
+    for face in faces {
+        if !progress.step() {
+            progress.result()?;
+        }
+        if face.is_portal() || face.is_invisible() {
+            continue;
+        }
+        let Some(positions) = face
+            .vertex_ids
+            .iter()
+            .map(|id| vertices.get(*id as usize).copied())
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let material = scene.texture_material(lod_manager, &face.texture, false);
+        let primitive = primitives
+            .entry(face.texture.to_lowercase())
+            .or_insert_with(|| Primitive {
+                material: material.map(|(material, _)| material),
+                ..Default::default()
+            });
+        primitive.push_polygon(&positions, &normalized(&face.texels, material));
+    }
+
+    let mut primitives: Vec<Primitive> = primitives.into_values().collect();
+    primitives.sort_by_key(|p| p.material);
+    if let Some(mesh) = scene.add_mesh(&map.name, &primitives) {
+        scene.add_node(&map.name, mesh, [0.; 3]);
+    }
+    Ok(scene)
+}
+
+/// Impostor of a sprite with its atlas. Unlike the decorations each side shows its own view,
+/// so back faces are culled.
+pub fn export_impostor(name: &str, impostor: &Impostor) -> GltfScene {
+    let mut scene = GltfScene::default();
+    let material = scene.add_material(name, &impostor.atlas.image, true);
+    scene.materials[material]["doubleSided"] = json!(false);
+    if let Some(mesh) = scene.add_mesh(
+        name,
+        &[Primitive {
+            positions: impostor.positions.clone(),
+            normals: impostor.normals.clone(),
+            uvs: impostor.uvs.clone(),
+            indices: impostor.indices.clone(),
+            material: Some(material),
+        }],
+    ) {
+        scene.add_node(name, mesh, [0.; 3]);
+    }
+    scene
+}
+
+/// Faces of a model grouped by texture
+fn model_primitives(
+    scene: &mut GltfScene,
+    lod_manager: &LodManager,
+    model: &BSPModel,
+) -> Vec<Primitive> {
+    let mut primitives: Vec<(String, Primitive)> = Vec::new();
+    for (face, texture) in model.faces.iter().zip(&model.texture_names) {
+        if face.is_portal() || face.is_invisible() {
+            continue;
+        }
+        let Some(positions) = face
+            .vertex_ids()
+            .iter()
+            .map(|id| model.vertices.get(*id as usize).copied())
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let material = scene.texture_material(lod_manager, texture, false);
+        let mut texels = face.texels();
+        for texel in &mut texels {
+            if face.flip_u() {
+                texel[0] = -texel[0];
+            }
+            if face.flip_v() {
+                texel[1] = -texel[1];
+            }
+        }
+        let i = match primitives.iter().position(|(name, _)| name == texture) {
+            Some(i) => i,
+            None => {
+                primitives.push((
+                    texture.clone(),
+                    Primitive {
+                        material: material.map(|(material, _)| material),
+                        ..Default::default()
+                    },
+                ));
+                primitives.len() - 1
+            }
+        };
+        primitives[i]
+            .1
+            .push_polygon(&positions, &normalized(&texels, material));
+    }
+    primitives.into_iter().map(|(_, p)| p).collect()
+}
+
+/// Texel coordinates divided by the texture size, unchanged when there is no texture
+fn normalized(texels: &[[f32; 2]], material: Option<TextureMaterial>) -> Vec<[f32; 2]> {
+    let (width, height) = material.map_or((1, 1), |(_, size)| size);
+    texels
+        .iter()
+        .map(|[u, v]| [u / width as f32, v / height as f32])
+        .collect()
+}
+
+/// Two vertical quads crossing on the y axis, standing on the origin
+fn crossed_quads(width: f32, height: f32, material: usize) -> Primitive {
+    let half = width / 2.;
+    let uvs = [[0., 1.], [1., 1.], [1., 0.], [0., 0.]];
+    let mut primitive = Primitive {
+        material: Some(material),
+        ..Default::default()
+    };
+    primitive.push_polygon(
+        &[
+            [-half, 0., 0.],
+            [half, 0., 0.],
+            [half, height, 0.],
+            [-half, height, 0.],
+        ],
+        &uvs,
+    );
+    primitive.push_polygon(
+        &[
+            [0., 0., half],
+            [0., 0., -half],
+            [0., height, -half],
+            [0., height, half],
+        ],
+        &uvs,
+    );
+    primitive
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, RgbaImage};
+    use serde_json::Value;
+
+    use super::{base64, crossed_quads, export_impostor, GltfScene, Primitive, GLB_MAGIC};
+    use crate::impostor::Impostor;
+
+    #[test]
+    fn base64_works() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn scene_export_works() {
+        let mut scene = GltfScene::default();
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(2, 2));
+        let material = scene.add_material("wall", &image, true);
+        let mut wall = Primitive {
+            material: Some(material),
+            ..Default::default()
+        };
+        wall.push_polygon(
+            &[
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [-1., 1., 0.],
+            ],
+            &[[0., 0.]; 5],
+        );
+        assert_eq!(wall.indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        let mesh = scene.add_mesh("wall", &[wall, crossed_quads(2., 3., material)]);
+        scene.add_node("wall", mesh.unwrap(), [1., 2., 3.]);
+        assert_eq!(scene.add_mesh("empty", &[Primitive::default()]), None);
+
+        let glb = scene.to_glb();
+        assert_eq!(&glb[..4], &GLB_MAGIC.to_le_bytes());
+        assert_eq!(
+            u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+            glb.len()
+        );
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        assert_eq!(glb.len() % 4, 0);
+        let document: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        assert_eq!(
+            document["meshes"][0]["primitives"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(document["materials"][0]["alphaMode"], "MASK");
+        assert_eq!(document["images"][0]["mimeType"], "image/png");
+        assert_eq!(
+            document["accessors"][0]["max"],
+            serde_json::json!([1., 1., 0.])
+        );
+        assert_eq!(
+            document["nodes"][0]["translation"],
+            serde_json::json!([1., 2., 3.])
+        );
+
+        let impostor = Impostor::from_views(vec![image.clone(); 8]);
+        let document: Value =
+            serde_json::from_slice(&export_impostor("gob", &impostor).to_gltf().into_bytes())
+                .unwrap();
+        assert_eq!(document["materials"][0]["doubleSided"], false);
+        assert_eq!(document["accessors"][0]["count"], 32);
+
+        let document: Value = serde_json::from_str(&scene.to_gltf()).unwrap();
+        let uri = document["buffers"][0]["uri"].as_str().unwrap();
+        assert!(uri.starts_with("data:application/octet-stream;base64,"));
+        assert_eq!(document["meshes"].as_array().unwrap().len(), 1);
+
+        let empty = GltfScene::default().to_glb();
+        let json_length = u32::from_le_bytes(empty[12..16].try_into().unwrap()) as usize;
+        assert_eq!(empty.len(), 20 + json_length);
+        let document: Value = serde_json::from_str(&GltfScene::default().to_gltf()).unwrap();
+        assert_eq!(document["scenes"], serde_json::json!([{}]));
+        for key in ["nodes", "meshes", "materials", "accessors", "buffers"] {
+            assert!(document.get(key).is_none());
+        }
+    }
+}
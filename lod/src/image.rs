@@ -1,13 +1,22 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-use image::{imageops, DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{write::ZlibEncoder, Compression};
+use image::{imageops, DynamicImage, GenericImageView, ImageBuffer, Rgba, RgbaImage};
 use std::{
+    collections::HashMap,
     error::Error,
-    io::{Cursor, Seek},
+    io::{Cursor, Write},
     path::Path,
 };
 
-use super::{palette::Palettes, zlib};
-use crate::LodManager;
+use super::{
+    palette::{color_distance, Palette, Palettes},
+    zlib,
+};
+use crate::{
+    layout::layout,
+    progress::{NoProgress, ProgressCounter, ProgressSink},
+    LodManager, Version,
+};
 
 #[derive(Debug)]
 pub(super) struct Image {
@@ -19,22 +28,69 @@ pub(super) struct Image {
 }
 
 const PALETTE_SIZE: usize = 256 * 3;
-const BITMAP_HEADER_SIZE: usize = 48;
-const SPRITE_HEADER_SIZE: usize = 32;
+const BITMAP_HEADER_SIZE: usize = BitmapHeader::SIZE;
+const SPRITE_HEADER_SIZE: usize = SpriteHeader::SIZE;
+/// Bitmaps have the image and three smaller levels
+const MIP_LEVELS: usize = 4;
+/// `BitmapHeader::flags` bit of MM7 and MM8 bitmaps drawn with their first color transparent.
+/// MM6 doesn't set the flags, its field holds whatever the packer left there.
+const BITMAP_TRANSPARENT: u32 = 0x0200;
+
+layout! {
+    /// Header of the bitmaps, icons and palettes
+    pub(crate) struct BitmapHeader {
+        name: [u8; 16],
+        pixels_size: u32,
+        compressed_size: u32,
+        width: u16,
+        height: u16,
+        width_ln2: u16,
+        height_ln2: u16,
+        width_minus_1: u16,
+        height_minus_1: u16,
+        palette_id: u16,
+        unknown: u16,
+        uncompressed_size: u32,
+        flags: u32,
+    }
+}
+
+layout! {
+    /// Header of the sprites, followed by a line table of `height` entries
+    pub(crate) struct SpriteHeader {
+        name: [u8; 12],
+        compressed_size: u32,
+        width: u16,
+        height: u16,
+        palette_id: u16,
+        unknown: u16,
+        y_skip: u16,
+        unknown_2: u16,
+        uncompressed_size: u32,
+    }
+}
 
 /// This is for bitmap images
+/// Bitmap read with the MM7 header fields, see the `(data, Version)` conversion
 impl TryFrom<&[u8]> for Image {
     type Error = Box<dyn Error>;
 
     fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
-        let mut cursor = Cursor::new(data);
-        cursor.seek(std::io::SeekFrom::Start(16))?;
-        let pixel_size = cursor.read_u32::<LittleEndian>()? as usize;
-        let compressed_size = cursor.read_u32::<LittleEndian>()? as usize;
-        let width = cursor.read_u16::<LittleEndian>()? as usize;
-        let height = cursor.read_u16::<LittleEndian>()? as usize;
-        cursor.seek(std::io::SeekFrom::Current(12))?;
-        let uncompressed_size = cursor.read_u32::<LittleEndian>()? as usize;
+        Image::try_from((data, Version::MM7))
+    }
+}
+
+/// Bitmap of the given game. The header is the same, but only MM7 and MM8 set its flags.
+impl TryFrom<(&[u8], Version)> for Image {
+    type Error = Box<dyn Error>;
+
+    fn try_from((data, version): (&[u8], Version)) -> Result<Self, Self::Error> {
+        let header = BitmapHeader::read(&mut &data[..])?;
+        let pixel_size = header.pixels_size as usize;
+        let compressed_size = header.compressed_size as usize;
+        let width = header.width as usize;
+        let height = header.height as usize;
+        let uncompressed_size = header.uncompressed_size as usize;
 
         if pixel_size == 0 {
             return Err("Pixel size is zero, this is not a valid image".into());
@@ -43,19 +99,33 @@ impl TryFrom<&[u8]> for Image {
             return Err("Not enough data".into());
         }
 
-        let compressed_data = &data[BITMAP_HEADER_SIZE..data.len() - PALETTE_SIZE];
-        let uncompressed_data =
-            zlib::decompress(compressed_data, compressed_size, uncompressed_size)?;
+        let pixel_data = &data[BITMAP_HEADER_SIZE..data.len() - PALETTE_SIZE];
+        let uncompressed_data = if uncompressed_size == 0 {
+            // Some MM6 bitmaps are stored without compression
+            pixel_data
+                .get(..pixel_size)
+                .ok_or("Not enough data for uncompressed pixels")?
+                .to_vec()
+        } else {
+            zlib::decompress(pixel_data, compressed_size, uncompressed_size)?
+        };
+        if uncompressed_data.len() < width * height {
+            return Err("Pixel data is smaller than the image".into());
+        }
 
         let palette_slice = &data[data.len() - PALETTE_SIZE..];
         let palette: [u8; PALETTE_SIZE] = palette_slice.try_into()?;
+        let transparency = match version {
+            Version::MM6 => false,
+            Version::MM7 | Version::MM8 => header.flags & BITMAP_TRANSPARENT != 0,
+        };
 
         Ok(Self {
             height,
             width,
             data: uncompressed_data,
             palette,
-            transparency: false,
+            transparency,
         })
     }
 }
@@ -68,20 +138,16 @@ impl TryFrom<(&[u8], &Palettes)> for Image {
         let palettes = data.1;
         let data = data.0;
 
-        let mut cursor = Cursor::new(data);
-        cursor.seek(std::io::SeekFrom::Start(12))?;
-
-        let compressed_size = cursor.read_u32::<LittleEndian>()? as usize;
-        let width = cursor.read_u16::<LittleEndian>()? as usize;
-        let height = cursor.read_u16::<LittleEndian>()? as usize;
+        let header = SpriteHeader::read(&mut &data[..])?;
+        let compressed_size = header.compressed_size as usize;
+        let width = header.width as usize;
+        let height = header.height as usize;
 
-        let palette_id = cursor.read_u16::<LittleEndian>()?;
         let palette = palettes
-            .get(palette_id)
+            .get(header.palette_id)
             .ok_or_else(|| "Palette not found!".to_string())?;
 
-        cursor.seek(std::io::SeekFrom::Current(6))?;
-        let uncompressed_size = cursor.read_u32::<LittleEndian>()? as usize;
+        let uncompressed_size = header.uncompressed_size as usize;
 
         let table_size: usize = height * 8;
 
@@ -108,39 +174,221 @@ impl TryFrom<(&[u8], &Palettes)> for Image {
     }
 }
 
+/// Sprite entry of an image, the inverse of the sprite decoding. Pixels with an alpha under
+/// half are transparent. The palette is the one of `palettes` closest to the opaque colors,
+/// their indices avoid 0 which sprites use for transparency.
+pub(crate) fn encode_sprite(
+    name: &str,
+    image: &DynamicImage,
+    palettes: &Palettes,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let rgba = image.to_rgba8();
+    let (width, height) = (rgba.width() as usize, rgba.height() as usize);
+    if width > i16::MAX as usize || height > u16::MAX as usize {
+        return Err(format!("Sprite {} is too big", name).into());
+    }
+    let opaque = |x: usize, y: usize| {
+        let pixel = rgba.get_pixel(x as u32, y as u32).0;
+        (pixel[3] >= 128).then_some([pixel[0], pixel[1], pixel[2]])
+    };
+
+    let mut counts: HashMap<[u8; 3], u64> = HashMap::new();
+    for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+        if let Some(color) = opaque(x, y) {
+            *counts.entry(color).or_default() += 1;
+        }
+    }
+    let (palette_id, indices) = nearest_palette(&counts, palettes)
+        .ok_or_else(|| format!("No palette to encode sprite {} with", name))?;
+
+    let mut table = Vec::with_capacity(height * 8);
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        let line = (0..width).filter(|&x| opaque(x, y).is_some());
+        let (start, end) = match (line.clone().next(), line.clone().next_back()) {
+            (Some(start), Some(end)) => (start, end),
+            _ => {
+                table.write_i16::<LittleEndian>(-1)?;
+                table.write_i16::<LittleEndian>(-1)?;
+                table.write_u32::<LittleEndian>(pixels.len() as u32)?;
+                continue;
+            }
+        };
+        table.write_i16::<LittleEndian>(start as i16)?;
+        table.write_i16::<LittleEndian>(end as i16)?;
+        table.write_u32::<LittleEndian>(pixels.len() as u32)?;
+        pixels.extend((start..=end).map(|x| opaque(x, y).map_or(0, |color| indices[&color])));
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&pixels)?;
+    let compressed = encoder.finish()?;
+
+    let mut sprite_name = [0; 12];
+    if name.len() >= sprite_name.len() {
+        return Err(format!("{:?} doesn't fit in a sprite name", name).into());
+    }
+    sprite_name[..name.len()].copy_from_slice(name.as_bytes());
+    // the game skips the empty lines at the bottom
+    let empty_bottom = (0..height)
+        .rev()
+        .take_while(|&y| (0..width).all(|x| opaque(x, y).is_none()))
+        .count();
+    let mut data = Vec::with_capacity(SPRITE_HEADER_SIZE + table.len() + compressed.len());
+    SpriteHeader {
+        name: sprite_name,
+        compressed_size: compressed.len() as u32,
+        width: width as u16,
+        height: height as u16,
+        palette_id,
+        unknown: 0,
+        y_skip: empty_bottom as u16,
+        unknown_2: 0,
+        uncompressed_size: pixels.len() as u32,
+    }
+    .write(&mut data)?;
+    data.extend_from_slice(&table);
+    data.extend_from_slice(&compressed);
+    Ok(data)
+}
+
+/// Id of the palette with the smallest error over the colors and their pixel counts, and the
+/// index of each color in it. Ties go to the lowest id.
+fn nearest_palette(
+    counts: &HashMap<[u8; 3], u64>,
+    palettes: &Palettes,
+) -> Option<(u16, HashMap<[u8; 3], u8>)> {
+    let mut best: Option<(u64, u16, HashMap<[u8; 3], u8>)> = None;
+    for (id, palette) in palettes.iter() {
+        let search = palette.opaque_search();
+        let mut error = 0;
+        let mut indices = HashMap::with_capacity(counts.len());
+        for (&color, &count) in counts {
+            let index = search.nearest(color);
+            error += color_distance(color, palette.color(index)) as u64 * count;
+            if best
+                .as_ref()
+                .is_some_and(|(best_error, _, _)| error > *best_error)
+            {
+                break;
+            }
+            indices.insert(color, index);
+        }
+        if best
+            .as_ref()
+            .is_none_or(|(best_error, _, _)| error < *best_error)
+        {
+            best = Some((error, id, indices));
+        }
+    }
+    best.map(|(_, id, indices)| (id, indices))
+}
+
+/// Expands the sprite lines. Each line has a table entry with its first and last opaque pixels
+/// and the offset of those pixels in `data`, lines without pixels have a negative start or end.
 fn process_sprite_data(
     data: &[u8],
     table: &[u8],
     width: usize,
     height: usize,
 ) -> Result<Vec<u8>, Box<dyn Error>> {
-    let img_size = width * height;
-    let mut img: Vec<u8> = vec![0; img_size];
-    let mut current: usize = 0;
+    let mut img: Vec<u8> = vec![0; width * height];
     let mut cursor = Cursor::new(table);
 
-    for _ in 0..height {
+    for line in img.chunks_exact_mut(width.max(1)).take(height) {
         let start = cursor.read_i16::<LittleEndian>()?;
         let end = cursor.read_i16::<LittleEndian>()?;
         let offset = cursor.read_u32::<LittleEndian>()? as usize;
 
         if start < 0 || end < 0 {
-            current += width - 1;
             continue;
         }
-
-        current += start as usize;
-        let chunk_size = (end - start + 1) as usize;
-        img[current..current + chunk_size].copy_from_slice(&data[offset..offset + chunk_size]);
-        current += width - start as usize;
+        let (start, end) = (start as usize, end as usize);
+        if start > end || end >= width {
+            return Err(format!("Sprite line {}..={} is outside the sprite", start, end).into());
+        }
+        let pixels = data
+            .get(offset..offset + end - start + 1)
+            .ok_or("Sprite line pixels are outside the data")?;
+        line[start..=end].copy_from_slice(pixels);
     }
     Ok(img)
 }
 
+/// Color effects applied to actors, like when they're hit or under a spell.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TintKind {
+    DamageFlash,
+    Stone,
+    Poison,
+}
+
+impl TintKind {
+    fn apply(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let [r, g, b] = rgb.map(|c| c as u32);
+        let luma = (r * 3 + g * 6 + b) / 10;
+        match self {
+            TintKind::DamageFlash => [(r + 255) / 2, g / 2, b / 2],
+            TintKind::Stone => [luma; 3],
+            TintKind::Poison => [luma / 2, (g + 255) / 2, luma / 2],
+        }
+        .map(|c| c.min(255) as u8)
+    }
+}
+
 impl Image {
+    /// Bytes used by the pixel indices and the palette
+    pub fn size(&self) -> usize {
+        self.data.len() + self.palette.len()
+    }
+
+    /// Remaps every palette entry to the palette color closest to its tinted version,
+    /// so the result only uses colors from the original palette like the game does.
+    pub fn tinted(&self, tint: TintKind) -> Self {
+        let palette = Palette { data: self.palette };
+        let search = palette.search();
+        let mut tinted = [0; PALETTE_SIZE];
+        for (index, color) in tinted.chunks_exact_mut(3).enumerate() {
+            let nearest = search.nearest(tint.apply(palette.color(index as u8)));
+            color.copy_from_slice(&palette.color(nearest));
+        }
+        Self {
+            height: self.height,
+            width: self.width,
+            data: self.data.clone(),
+            palette: tinted,
+            transparency: self.transparency,
+        }
+    }
+
     pub fn to_image_buffer(&self) -> Result<DynamicImage, Box<dyn Error>> {
+        self.level_image_buffer(&self.data, self.width, self.height)
+    }
+
+    /// The image then the smaller levels bitmaps store after it, each half the size of the
+    /// previous one. Levels missing from the data are left out.
+    pub fn mip_levels(&self) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+        let mut levels = Vec::with_capacity(MIP_LEVELS);
+        let (mut width, mut height, mut offset) = (self.width, self.height, 0);
+        while levels.len() < MIP_LEVELS && width > 0 && height > 0 {
+            let Some(pixels) = self.data.get(offset..offset + width * height) else {
+                break;
+            };
+            levels.push(self.level_image_buffer(pixels, width, height)?);
+            offset += width * height;
+            (width, height) = (width / 2, height / 2);
+        }
+        Ok(levels)
+    }
+
+    fn level_image_buffer(
+        &self,
+        data: &[u8],
+        width: usize,
+        height: usize,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
         let image = raw_to_image_buffer(
-            &self.data,
+            data,
             &self.palette,
             |index, pixel: &[u8; 3]| {
                 if self.transparency && index == self.data[0] {
@@ -149,8 +397,8 @@ impl Image {
                     Rgba([pixel[0], pixel[1], pixel[2], 255])
                 }
             },
-            self.width as u32,
-            self.height as u32,
+            width as u32,
+            height as u32,
         )?;
         Ok(DynamicImage::ImageRgba8(image))
     }
@@ -167,8 +415,8 @@ impl Image {
 }
 
 /// Converts the image into a versatile generic image buffer.
-/// The image contains more pixels than needed with dimensions (h*w) to account for mipmaps,
-/// but we are currently not utilizing those extra pixels.
+/// The data can hold more pixels than the (h*w) converted, bitmaps store their mipmaps after
+/// the image, see `Image::mip_levels`.
 /// # Panics
 /// if the input accesses outside the bounds of the palette.
 fn raw_to_image_buffer<P>(
@@ -177,11 +425,11 @@ fn raw_to_image_buffer<P>(
     pixel_converter: impl Fn(u8, &[u8; 3]) -> P,
     width: u32,
     height: u32,
-) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, Box<dyn Error>>
+) -> Result<ImageBuffer<P, Vec<u8>>, Box<dyn Error>>
 where
     P: image::Pixel<Subpixel = u8> + 'static,
 {
-    let mut image_buffer = ImageBuffer::<P, Vec<P::Subpixel>>::new(width, height);
+    let mut image_buffer = ImageBuffer::<P, Vec<u8>>::new(width, height);
 
     for (i, pi) in data[..(width * height) as usize].iter().enumerate() {
         let x = (i as u32).rem_euclid(width);
@@ -193,6 +441,21 @@ where
     Ok(image_buffer)
 }
 
+const PLACEHOLDER_SIZE: u32 = 64;
+const PLACEHOLDER_SQUARE_SIZE: u32 = 8;
+
+/// Checkerboard image used in place of assets that can't be decoded.
+pub(crate) fn placeholder() -> DynamicImage {
+    let image = ImageBuffer::from_fn(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, |x, y| {
+        if (x / PLACEHOLDER_SQUARE_SIZE + y / PLACEHOLDER_SQUARE_SIZE) & 1 == 0 {
+            Rgba([255, 0, 255, 255])
+        } else {
+            Rgba([0, 0, 0, 255])
+        }
+    });
+    DynamicImage::ImageRgba8(image)
+}
+
 fn join_images_in_grid(
     images: &[DynamicImage],
     grid_width: usize,
@@ -229,6 +492,17 @@ pub fn get_atlas(
     names: &[&str],
     row_size: usize,
 ) -> Result<DynamicImage, Box<dyn Error>> {
+    get_atlas_with_progress(lod_manager, names, row_size, &NoProgress)
+}
+
+/// Same as `get_atlas`, reporting each bitmap added to the atlas
+pub fn get_atlas_with_progress(
+    lod_manager: &LodManager,
+    names: &[&str],
+    row_size: usize,
+    progress: &dyn ProgressSink,
+) -> Result<DynamicImage, Box<dyn Error>> {
+    let progress = ProgressCounter::new(progress, names.len());
     let mut images: Vec<DynamicImage> = Vec::with_capacity(names.len());
 
     // HACK instead of using shaders I'll compose water in texture gen. :(
@@ -256,15 +530,321 @@ pub fn get_atlas(
         }
 
         images.push(image);
+        if !progress.step() {
+            break;
+        }
     }
+    progress.result()?;
     Ok(join_images_in_grid(&images, row_size, 128, 128))
 }
 
+/// Space left around each sprite of a sprite atlas so filtering doesn't bleed between them
+const SPRITE_ATLAS_PADDING: u32 = 1;
+
+/// Where a sprite is in a sprite atlas, in pixels and as texture coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+/// Sprites packed in one texture, with the rectangle of each sprite by name
+#[derive(Debug, Clone)]
+pub struct SpriteAtlas {
+    pub image: DynamicImage,
+    pub rects: HashMap<String, AtlasRect>,
+}
+
+/// Packs the sprites `names` into one transparent atlas, so billboards of different sprites
+/// can share a texture. The transparent pixels of the sprites stay transparent.
+pub fn get_sprite_atlas(
+    lod_manager: &LodManager,
+    names: &[&str],
+) -> Result<SpriteAtlas, Box<dyn Error>> {
+    let mut sprites = Vec::with_capacity(names.len());
+    for name in names {
+        let sprite = lod_manager
+            .sprite(name)
+            .ok_or_else(|| format!("sprite {} not found", name))?;
+        sprites.push((name.to_string(), sprite));
+    }
+    Ok(pack_sprites(sprites))
+}
+
+/// Shelf packing: the tallest sprites first, in rows as wide as a power of two
+pub(crate) fn pack_sprites(mut sprites: Vec<(String, DynamicImage)>) -> SpriteAtlas {
+    sprites.sort_by_key(|(name, image)| (std::cmp::Reverse(image.height()), name.clone()));
+    let padded = |size: u32| size + SPRITE_ATLAS_PADDING * 2;
+    let area: u32 = sprites
+        .iter()
+        .map(|(_, image)| padded(image.width()) * padded(image.height()))
+        .sum();
+    let widest = sprites
+        .iter()
+        .map(|(_, image)| padded(image.width()))
+        .max()
+        .unwrap_or(1);
+    let atlas_width = widest
+        .max((area as f32).sqrt().ceil() as u32)
+        .next_power_of_two();
+
+    let mut positions = Vec::with_capacity(sprites.len());
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for (_, image) in &sprites {
+        if x + padded(image.width()) > atlas_width {
+            (x, y, shelf_height) = (0, y + shelf_height, 0);
+        }
+        positions.push((x + SPRITE_ATLAS_PADDING, y + SPRITE_ATLAS_PADDING));
+        x += padded(image.width());
+        shelf_height = shelf_height.max(padded(image.height()));
+    }
+    let atlas_height = (y + shelf_height).max(1).next_power_of_two();
+
+    let mut atlas = RgbaImage::new(atlas_width, atlas_height);
+    let mut rects = HashMap::with_capacity(sprites.len());
+    for ((name, image), (x, y)) in sprites.into_iter().zip(positions) {
+        let (width, height) = image.dimensions();
+        imageops::replace(&mut atlas, &image.to_rgba8(), x as i64, y as i64);
+        let (atlas_width, atlas_height) = (atlas_width as f32, atlas_height as f32);
+        rects.insert(
+            name,
+            AtlasRect {
+                x,
+                y,
+                width,
+                height,
+                uv_min: [x as f32 / atlas_width, y as f32 / atlas_height],
+                uv_max: [
+                    (x + width) as f32 / atlas_width,
+                    (y + height) as f32 / atlas_height,
+                ],
+            },
+        );
+    }
+    SpriteAtlas {
+        image: DynamicImage::ImageRgba8(atlas),
+        rects,
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::get_atlas;
-    use crate::{get_lod_path, LodManager};
+    use super::{
+        get_atlas, pack_sprites, placeholder, process_sprite_data, Image, TintKind,
+        BITMAP_HEADER_SIZE, PALETTE_SIZE, SPRITE_ATLAS_PADDING,
+    };
+    use crate::{get_lod_path, LodManager, Version};
+    use flate2::{write::ZlibEncoder, Compression};
     use image::GenericImageView;
+    use std::io::Write;
+
+    fn bitmap_data(width: u16, height: u16, pixels: &[u8], compress: bool) -> Vec<u8> {
+        let stored = if compress {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(pixels).unwrap();
+            encoder.finish().unwrap()
+        } else {
+            pixels.to_vec()
+        };
+        let uncompressed_size = if compress { pixels.len() as u32 } else { 0 };
+
+        let mut data = vec![0; 16];
+        data.extend_from_slice(&(pixels.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&uncompressed_size.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        assert_eq!(data.len(), BITMAP_HEADER_SIZE);
+        data.extend_from_slice(&stored);
+        data.extend((0..PALETTE_SIZE).map(|i| i as u8));
+        data
+    }
+
+    #[test]
+    fn compressed_and_uncompressed_bitmaps_decode() {
+        let pixels = [0, 1, 2, 3, 4, 5];
+        for compress in [true, false] {
+            let data = bitmap_data(3, 2, &pixels, compress);
+            let image = Image::try_from(data.as_slice()).unwrap();
+            assert_eq!((image.width, image.height), (3, 2));
+            assert_eq!(image.data, pixels);
+        }
+    }
+
+    #[test]
+    fn mip_levels_decode() {
+        let pixels: Vec<u8> = (0..16 + 4 + 1).collect();
+        let data = bitmap_data(4, 4, &pixels, true);
+        let bitmap = Image::try_from(data.as_slice()).unwrap();
+        let levels = bitmap.mip_levels().unwrap();
+        let sizes: Vec<_> = levels.iter().map(|level| level.dimensions()).collect();
+        assert_eq!(sizes, vec![(4, 4), (2, 2), (1, 1)]);
+        // the palette holds 0, 1, 2, ... so the color of index i starts at 3 * i
+        assert_eq!(levels[1].get_pixel(1, 0).0, [51, 52, 53, 255]);
+        assert_eq!(levels[2].get_pixel(0, 0).0, [60, 61, 62, 255]);
+
+        let data = bitmap_data(4, 4, &pixels[..16], true);
+        let bitmap = Image::try_from(data.as_slice()).unwrap();
+        assert_eq!(bitmap.mip_levels().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tinting_stays_in_the_palette() {
+        let mut palette = [0; PALETTE_SIZE];
+        palette[3..6].copy_from_slice(&[200, 200, 200]);
+        palette[6..9].copy_from_slice(&[200, 20, 20]);
+        palette[9..12].copy_from_slice(&[60, 60, 60]);
+        let image = Image {
+            height: 1,
+            width: 2,
+            data: vec![0, 1],
+            palette,
+            transparency: true,
+        };
+
+        let flash = image.tinted(TintKind::DamageFlash);
+        assert_eq!(flash.data, image.data);
+        assert_eq!(flash.palette[3..6], [200, 20, 20]);
+        let stone = image.tinted(TintKind::Stone);
+        assert_eq!(stone.palette[6..9], [60, 60, 60]);
+        let buffer = flash.to_image_buffer().unwrap().to_rgba8();
+        assert_eq!(buffer.get_pixel(0, 0).0[3], 0);
+    }
+
+    #[test]
+    fn transparency_flag_needs_mm7() {
+        let mut data = bitmap_data(2, 1, &[0, 1], true);
+        data[44..48].copy_from_slice(&0x0200u32.to_le_bytes());
+        assert!(
+            Image::try_from((data.as_slice(), Version::MM7))
+                .unwrap()
+                .transparency
+        );
+        assert!(
+            Image::try_from((data.as_slice(), Version::MM8))
+                .unwrap()
+                .transparency
+        );
+        assert!(
+            !Image::try_from((data.as_slice(), Version::MM6))
+                .unwrap()
+                .transparency
+        );
+        let opaque = bitmap_data(2, 1, &[0, 1], true);
+        assert!(!Image::try_from(opaque.as_slice()).unwrap().transparency);
+    }
+
+    /// Decodes every bitmap of the installed game
+    #[test]
+    #[ignore = "needs the game data"]
+    fn all_bitmaps_decode() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let version = lod_manager.version("bitmaps").unwrap();
+        let failed: Vec<_> = lod_manager
+            .files("bitmaps")
+            .unwrap()
+            .into_iter()
+            // palettes share the archive and have no pixels
+            .filter(|name| !name.starts_with("pal"))
+            .filter(|name| {
+                let data = lod_manager
+                    .try_get_bytes(format!("bitmaps/{}", name))
+                    .unwrap();
+                Image::try_from((data, version)).is_err()
+            })
+            .collect();
+        assert!(failed.is_empty(), "{:?} failed to decode", failed);
+    }
+
+    #[test]
+    fn truncated_bitmap_is_an_error() {
+        let data = bitmap_data(4, 4, &[0, 1, 2, 3, 4, 5], false);
+        assert!(Image::try_from(data.as_slice()).is_err());
+    }
+
+    fn sprite_line(start: i16, end: i16, offset: u32) -> Vec<u8> {
+        let mut entry = start.to_le_bytes().to_vec();
+        entry.extend(end.to_le_bytes());
+        entry.extend(offset.to_le_bytes());
+        entry
+    }
+
+    #[test]
+    fn sprite_lines_decode() {
+        let data = [1, 2, 3, 4];
+        let table = [
+            sprite_line(1, 2, 0),
+            sprite_line(-1, -1, 0),
+            sprite_line(0, 0, 3),
+        ]
+        .concat();
+        let pixels = process_sprite_data(&data, &table, 3, 3).unwrap();
+        assert_eq!(pixels, vec![0, 1, 2, 0, 0, 0, 4, 0, 0]);
+
+        for bad_line in [
+            sprite_line(2, 1, 0),
+            sprite_line(0, 3, 0),
+            sprite_line(0, 1, 3),
+        ] {
+            let table = [bad_line, sprite_line(-1, -1, 0), sprite_line(-1, -1, 0)].concat();
+            assert!(process_sprite_data(&data, &table, 3, 3).is_err());
+        }
+        assert!(process_sprite_data(&data, &table[..8], 3, 3).is_err());
+    }
+
+    #[test]
+    fn sprites_are_packed() {
+        let sprite = |width, height, color| {
+            image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                width,
+                height,
+                image::Rgba(color),
+            ))
+        };
+        let atlas = pack_sprites(vec![
+            ("tree".into(), sprite(20, 40, [0, 255, 0, 255])),
+            ("rock".into(), sprite(30, 10, [128, 128, 128, 255])),
+            ("ghost".into(), sprite(8, 8, [0, 0, 0, 0])),
+        ]);
+        assert_eq!(atlas.image.dimensions(), (64, 64));
+        assert_eq!(atlas.rects.len(), 3);
+
+        let tree = atlas.rects["tree"];
+        assert_eq!(
+            (tree.x, tree.y),
+            (SPRITE_ATLAS_PADDING, SPRITE_ATLAS_PADDING)
+        );
+        assert_eq!(atlas.image.get_pixel(tree.x, tree.y).0, [0, 255, 0, 255]);
+        let rock = atlas.rects["rock"];
+        assert!(rock.x >= tree.x + tree.width + SPRITE_ATLAS_PADDING);
+        assert_eq!(atlas.image.get_pixel(rock.x + 29, rock.y + 9).0[0], 128);
+        assert_eq!(
+            rock.uv_max,
+            [(rock.x + 30) as f32 / 64., (rock.y + 10) as f32 / 64.]
+        );
+        let ghost = atlas.rects["ghost"];
+        assert_eq!(atlas.image.get_pixel(ghost.x, ghost.y).0[3], 0);
+
+        // nothing overlaps
+        let rects: Vec<_> = atlas.rects.values().collect();
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                assert!(
+                    a.x + a.width <= b.x
+                        || b.x + b.width <= a.x
+                        || a.y + a.height <= b.y
+                        || b.y + b.height <= a.y
+                );
+            }
+        }
+        assert_eq!(pack_sprites(Vec::new()).image.dimensions(), (1, 1));
+    }
 
     #[test]
     fn join_images() {
@@ -279,4 +859,13 @@ mod test {
         .unwrap();
         assert_eq!(atlas_image.dimensions(), (128 * 2, 128 * 3));
     }
+
+    #[test]
+    fn placeholder_is_a_checkerboard() {
+        let image = placeholder();
+        assert_eq!(image.dimensions(), (64, 64));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 255, 255]);
+        assert_eq!(image.get_pixel(8, 0).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(8, 8).0, [255, 0, 255, 255]);
+    }
 }
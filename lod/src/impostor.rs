@@ -0,0 +1,147 @@
+//! Impostor meshes of monster sprites for engines without billboards: the eight views of a
+//! sprite are packed into an atlas and shown on four vertical quads crossing on the y axis,
+//! each side of a quad showing the view seen from its direction.
+
+use std::{error::Error, f32::consts::FRAC_PI_4};
+
+use image::DynamicImage;
+
+use crate::{image::pack_sprites, LodManager, SpriteAtlas};
+
+/// Views of a monster sprite, 0 being the front and the others going around it
+pub const IMPOSTOR_VIEWS: u8 = 8;
+/// Views stored in the sprites, the others are mirrored
+const STORED_VIEWS: u8 = 5;
+
+/// Impostor of a sprite in pixels, standing on the origin and facing +z
+#[derive(Debug, Clone)]
+pub struct Impostor {
+    /// Views keyed `view0` to `view7`
+    pub atlas: SpriteAtlas,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl Impostor {
+    /// Impostor of a sprite like the frames of the sprite frame table, e.g. "gobst". Views 5
+    /// to 7 aren't stored, they are views 3 to 1 mirrored. A sprite without views is
+    /// shown the same from every side.
+    pub fn new(lod_manager: &LodManager, sprite: &str) -> Result<Self, Box<dyn Error>> {
+        let views = (0..IMPOSTOR_VIEWS)
+            .map(|view| {
+                lod_manager
+                    .sprite(&format!("{}{}", sprite, view))
+                    .or_else(|| {
+                        let mirrored = IMPOSTOR_VIEWS - view;
+                        (view >= STORED_VIEWS)
+                            .then(|| lod_manager.sprite(&format!("{}{}", sprite, mirrored)))
+                            .flatten()
+                            .map(|image| image.fliph())
+                    })
+                    .or_else(|| lod_manager.sprite(sprite))
+                    .ok_or_else(|| format!("sprite {} not found", sprite))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_views(views))
+    }
+
+    /// Impostor of the given views, in view order
+    pub fn from_views(views: Vec<DynamicImage>) -> Self {
+        let atlas = pack_sprites(
+            views
+                .into_iter()
+                .enumerate()
+                .map(|(view, image)| (format!("view{}", view), image))
+                .collect(),
+        );
+        let mut impostor = Self {
+            atlas,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+        };
+        for view in 0..IMPOSTOR_VIEWS {
+            let Some(rect) = impostor.atlas.rects.get(&format!("view{}", view)).copied() else {
+                continue;
+            };
+            // the side seen when looking at the sprite from `angle`, counterclockwise from +z
+            let angle = view as f32 * FRAC_PI_4;
+            let normal = [angle.sin(), 0., angle.cos()];
+            let half = rect.width as f32 / 2.;
+            let right = [angle.cos() * half, 0., -angle.sin() * half];
+            let height = rect.height as f32;
+
+            let first = impostor.positions.len() as u32;
+            for (side, top, uv) in [
+                (-1., 0., [rect.uv_min[0], rect.uv_max[1]]),
+                (1., 0., rect.uv_max),
+                (1., height, [rect.uv_max[0], rect.uv_min[1]]),
+                (-1., height, rect.uv_min),
+            ] {
+                impostor
+                    .positions
+                    .push([right[0] * side, top, right[2] * side]);
+                impostor.normals.push(normal);
+                impostor.uvs.push(uv);
+            }
+            impostor
+                .indices
+                .extend([0, 1, 2, 0, 2, 3].iter().map(|i| first + i));
+        }
+        impostor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    use super::{Impostor, IMPOSTOR_VIEWS};
+
+    #[test]
+    fn impostor_works() {
+        let views: Vec<DynamicImage> = (0..IMPOSTOR_VIEWS)
+            .map(|view| {
+                DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                    10 + view as u32 * 2,
+                    20,
+                    Rgba([view, 0, 0, 255]),
+                ))
+            })
+            .collect();
+        let impostor = Impostor::from_views(views);
+        assert_eq!(impostor.atlas.rects.len(), 8);
+        assert_eq!((impostor.positions.len(), impostor.indices.len()), (32, 48));
+
+        // the front faces +z, left to right along +x
+        assert_eq!(impostor.normals[0], [0., 0., 1.]);
+        assert_eq!(impostor.positions[0], [-5., 0., 0.]);
+        assert_eq!(impostor.positions[2], [5., 20., 0.]);
+        let front = impostor.atlas.rects["view0"];
+        assert_eq!(impostor.uvs[3], front.uv_min);
+
+        // the back is seen from -z, its right side is -x
+        let back = &impostor.positions[16..20];
+        assert!((back[1][0] + 9.).abs() < 1e-4 && back[1][2].abs() < 1e-4);
+        assert!((impostor.normals[16][2] + 1.).abs() < 1e-6);
+
+        // every side is wound counterclockwise seen from its normal
+        for face in impostor.indices.chunks(6) {
+            let [a, b, c] = [0, 1, 2].map(|i| impostor.positions[face[i] as usize]);
+            let (u, v) = (
+                [b[0] - a[0], b[1] - a[1], b[2] - a[2]],
+                [c[0] - a[0], c[1] - a[1], c[2] - a[2]],
+            );
+            let cross = [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ];
+            let n = impostor.normals[face[0] as usize];
+            assert!(cross[0] * n[0] + cross[1] * n[1] + cross[2] * n[2] > 0.);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fmt::Write, ops::Range};
+
+use crate::{
+    blv::{BlvFace, BlvHeaderRecord, DoorRecord, RoomRecord},
+    image::{BitmapHeader, SpriteHeader},
+    layout::{markdown, FieldLayout},
+    lod_data::{DataHeader, LodData},
+    odm::OdmHeader,
+};
+
+const BYTES_PER_LINE: usize = 16;
+
+/// A parsed header field and the bytes it was read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub range: Range<usize>,
+    pub value: String,
+}
+
+fn read_fields(data: &[u8], layout: &[FieldLayout]) -> Vec<Field> {
+    layout
+        .iter()
+        .map_while(|field| {
+            Some(Field {
+                name: field.name,
+                range: field.offset..field.offset + field.size,
+                value: field.value(data)?,
+            })
+        })
+        .collect()
+}
+
+/// Field tables of the records `annotate` knows, in markdown
+pub fn layouts() -> String {
+    [
+        ("Bitmap header", BitmapHeader::layout()),
+        ("Sprite header", SpriteHeader::layout()),
+        ("Data header", DataHeader::layout()),
+        ("Outdoor map header", OdmHeader::layout()),
+        ("Indoor map header", BlvHeaderRecord::layout()),
+        ("Indoor map face", BlvFace::layout()),
+        ("Indoor map room", RoomRecord::layout()),
+        ("Indoor map door", DoorRecord::layout()),
+    ]
+    .iter()
+    .map(|(title, layout)| format!("### {}\n\n{}", title, markdown(layout)))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Header fields of an entry, picked from the archive it comes from. Entries we don't know the
+/// layout of get no fields.
+pub fn annotate(archive: &str, data: &[u8]) -> Vec<Field> {
+    match archive.to_lowercase().as_str() {
+        "bitmaps" | "icons" if crate::image::Image::try_from(data).is_ok() => {
+            read_fields(data, &BitmapHeader::layout())
+        }
+        "sprites" => read_fields(data, &SpriteHeader::layout()),
+        _ => match LodData::try_from(data) {
+            Ok(LodData {
+                header: Some(header),
+                ..
+            }) if header.len() == DataHeader::SIZE => read_fields(data, &DataHeader::layout()),
+            Ok(LodData {
+                header: Some(_), ..
+            }) => read_fields(data, &BitmapHeader::layout()),
+            _ => Vec::new(),
+        },
+    }
+}
+
+/// Decompressed contents of a map entry with the fields of its header. The header layout is
+/// picked from the extension, entries other than .odm and .blv maps get nothing.
+pub fn annotate_contents(entry: &str, data: &[u8]) -> Option<(Vec<u8>, Vec<Field>)> {
+    let entry = entry.to_lowercase();
+    let layout = if entry.ends_with(".odm") {
+        OdmHeader::layout()
+    } else if entry.ends_with(".blv") {
+        BlvHeaderRecord::layout()
+    } else {
+        return None;
+    };
+    let contents = LodData::try_from(data).ok()?.data;
+    let fields = read_fields(&contents, &layout);
+    Some((contents, fields))
+}
+
+/// Hex and ascii dump, each line lists the fields starting on it.
+pub fn hexdump(data: &[u8], fields: &[Field]) -> String {
+    let mut dump = String::new();
+    for (line, bytes) in data.chunks(BYTES_PER_LINE).enumerate() {
+        let start = line * BYTES_PER_LINE;
+        let _ = write!(dump, "{:08x}  ", start);
+        for i in 0..BYTES_PER_LINE {
+            match bytes.get(i) {
+                Some(b) => {
+                    let _ = write!(dump, "{:02x} ", b);
+                }
+                None => dump.push_str("   "),
+            }
+        }
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+            .collect();
+        let _ = write!(dump, " {:<16}", ascii);
+
+        let line_range = start..start + BYTES_PER_LINE;
+        let annotations: Vec<String> = fields
+            .iter()
+            .filter(|f| line_range.contains(&f.range.start))
+            .map(|f| format!("{:x}:{}={}", f.range.start, f.name, f.value))
+            .collect();
+        if !annotations.is_empty() {
+            let _ = write!(dump, "  {}", annotations.join(" "));
+        }
+        dump.push('\n');
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::{annotate, annotate_contents, hexdump, layouts, Field};
+
+    #[test]
+    fn annotate_works() {
+        let mut map = vec![0; 104];
+        map.extend(12u32.to_le_bytes());
+        map.extend([0; 28]);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&map).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut data = (compressed.len() as u32).to_le_bytes().to_vec();
+        data.extend((map.len() as u32).to_le_bytes());
+        data.extend(compressed);
+        let fields = annotate("games", &data);
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[1].value, "136");
+        assert!(annotate("games", b"plain text").is_empty());
+
+        let (contents, fields) = annotate_contents("D01.blv", &data).unwrap();
+        assert_eq!(contents, map);
+        assert_eq!(
+            fields[1],
+            Field {
+                name: "faces_data_size",
+                range: 104..108,
+                value: "12".into()
+            }
+        );
+        assert_eq!(fields.len(), 6);
+        assert!(annotate_contents("d01.dlv", &data).is_none());
+
+        let mut sprite = b"rok1".to_vec();
+        sprite.resize(32, 0);
+        sprite[16] = 40;
+        let fields = annotate("sprites", &sprite);
+        assert_eq!(fields.len(), 9);
+        assert_eq!(
+            fields[2],
+            Field {
+                name: "width",
+                range: 16..18,
+                value: "40".into()
+            }
+        );
+        assert_eq!(fields[0].value, "\"rok1\"");
+
+        let dump = hexdump(&sprite, &fields);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("00000000  72 6f 6b 31 00"));
+        assert!(lines[0].ends_with("0:name=\"rok1\" c:compressed_size=0"));
+        assert!(lines[1].contains("10:width=40"));
+
+        assert!(layouts().contains("| 0x10 | 4 | pixels_size |"));
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Name of the directory holding the lod files, its case depends on the release
+const DATA_DIR: &str = "data";
+/// Name of the directory holding the video archives, next to the data directory
+const ANIMS_DIR: &str = "anims";
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case(extension))
+}
+
+/// Files of a directory with the given extension whatever its case, e.g. `ICONS.LOD`.
+pub(crate) fn list_files<P: AsRef<Path>>(
+    path: P,
+    extension: &str,
+) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut files: Vec<PathBuf> = fs::read_dir(&path)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| has_extension(path, extension))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+pub(crate) fn list_lod_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, std::io::Error> {
+    list_files(path, "lod")
+}
+
+/// Directory with the lod files of an install. `path` can be the data directory itself or the
+/// game directory, where the data directory is `Data` in the GOG release and `DATA` on the CD.
+/// Falls back to `path` when no lod files are found, so the error mentions the given directory.
+pub fn find_data_dir<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    let has_lods = |dir: &Path| list_lod_files(dir).is_ok_and(|files| !files.is_empty());
+    if has_lods(path) {
+        return path.to_path_buf();
+    }
+    find_dir(path, DATA_DIR)
+        .filter(|dir| has_lods(dir))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Subdirectory of `path` matched case-insensitively
+fn find_dir(path: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(path)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|dir| {
+            dir.is_dir()
+                && dir
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case(name))
+        })
+}
+
+/// Video archives of an install, in the data directory or in the anims directory beside it.
+pub(crate) fn list_vid_files(data_dir: &Path) -> Vec<PathBuf> {
+    let anims_dir = data_dir.parent().and_then(|game| find_dir(game, ANIMS_DIR));
+    [Some(data_dir.to_path_buf()), anims_dir]
+        .into_iter()
+        .flatten()
+        .flat_map(|dir| list_files(dir, "vid").unwrap_or_default())
+        .collect()
+}
+
+/// Sound archives of an install, audio.snd in the data directory
+pub(crate) fn list_snd_files(data_dir: &Path) -> Vec<PathBuf> {
+    list_files(data_dir, "snd").unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{find_data_dir, list_lod_files, list_snd_files, list_vid_files};
+    use crate::TempDir;
+
+    #[test]
+    fn find_data_dir_works() {
+        let dir = TempDir::new("install");
+        let root = dir.path();
+        let data = root.join("DATA");
+        fs::create_dir_all(&data).unwrap();
+        fs::write(data.join("ICONS.LOD"), b"").unwrap();
+        fs::write(data.join("readme.txt"), b"").unwrap();
+        fs::write(data.join("AUDIO.SND"), b"").unwrap();
+        let anims = root.join("Anims");
+        fs::create_dir_all(&anims).unwrap();
+        fs::write(anims.join("Anims1.VID"), b"").unwrap();
+
+        let found = find_data_dir(root);
+        let direct = find_data_dir(&data);
+        let lod_files = list_lod_files(&data);
+        let missing = find_data_dir(root.join("missing"));
+        let vid_files = list_vid_files(&data);
+        let snd_files = list_snd_files(&data);
+
+        assert_eq!(found, data);
+        assert_eq!(direct, data);
+        assert_eq!(lod_files.unwrap(), vec![data.join("ICONS.LOD")]);
+        assert_eq!(missing, root.join("missing"));
+        assert_eq!(vid_files, vec![anims.join("Anims1.VID")]);
+        assert_eq!(snd_files, vec![data.join("AUDIO.SND")]);
+    }
+}
@@ -0,0 +1,276 @@
+//! Declarative description of the fixed size binary records. `layout!` turns a field list into
+//! the struct, its reader and writer, and the offset table used to annotate dumps, so the
+//! offsets are written down once.
+
+use std::io::{Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+/// How the bytes of a field are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FieldKind {
+    /// Fixed size block, shown as a string when it holds one
+    Bytes,
+    U8,
+    U16,
+    U32,
+    I16,
+    I32,
+    /// Array of `u16`
+    U16s,
+    /// Array of `i16`
+    I16s,
+    /// Array of `i32`
+    I32s,
+}
+
+/// Position of a field in its record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FieldLayout {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+    pub kind: FieldKind,
+}
+
+impl FieldLayout {
+    /// Value of the field read from its bytes, `None` when the data is too short
+    pub fn value(&self, data: &[u8]) -> Option<String> {
+        let bytes = data.get(self.offset..self.offset + self.size)?;
+        let mut cursor = bytes;
+        Some(match self.kind {
+            FieldKind::Bytes => {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                let text = &bytes[..end];
+                if !text.is_empty() && text.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+                    format!("{:?}", String::from_utf8_lossy(text))
+                } else {
+                    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+                }
+            }
+            FieldKind::U8 => bytes[0].to_string(),
+            FieldKind::U16 => cursor.read_u16::<LittleEndian>().ok()?.to_string(),
+            FieldKind::U32 => cursor.read_u32::<LittleEndian>().ok()?.to_string(),
+            FieldKind::I16 => cursor.read_i16::<LittleEndian>().ok()?.to_string(),
+            FieldKind::I32 => cursor.read_i32::<LittleEndian>().ok()?.to_string(),
+            FieldKind::U16s => format!(
+                "{:?}",
+                bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>()
+            ),
+            FieldKind::I16s => format!(
+                "{:?}",
+                bytes
+                    .chunks_exact(2)
+                    .map(|c| i16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>()
+            ),
+            FieldKind::I32s => format!(
+                "{:?}",
+                bytes
+                    .chunks_exact(4)
+                    .map(|c| i32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect::<Vec<_>>()
+            ),
+        })
+    }
+}
+
+/// Markdown table of a layout, for the format notes
+pub(crate) fn markdown(layout: &[FieldLayout]) -> String {
+    let mut table = String::from("| offset | size | field |\n|---|---|---|\n");
+    for field in layout {
+        table.push_str(&format!(
+            "| 0x{:02x} | {} | {} |\n",
+            field.offset, field.size, field.name
+        ));
+    }
+    table
+}
+
+/// A field type `layout!` knows how to read and write
+pub(crate) trait LayoutField: Sized {
+    const SIZE: usize;
+    const KIND: FieldKind;
+    fn read_field<R: Read>(reader: &mut R) -> std::io::Result<Self>;
+    fn write_field<W: Write>(&self, writer: &mut W) -> std::io::Result<()>;
+}
+
+macro_rules! impl_layout_field {
+    ($ty:ty, $kind:ident, $read:ident, $write:ident $(, $endian:ty)?) => {
+        impl LayoutField for $ty {
+            const SIZE: usize = std::mem::size_of::<$ty>();
+            const KIND: FieldKind = FieldKind::$kind;
+
+            fn read_field<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+                reader.$read$(::<$endian>)?()
+            }
+
+            fn write_field<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+                writer.$write$(::<$endian>)?(*self)
+            }
+        }
+    };
+}
+
+impl_layout_field!(u8, U8, read_u8, write_u8);
+impl_layout_field!(u16, U16, read_u16, write_u16, LittleEndian);
+impl_layout_field!(u32, U32, read_u32, write_u32, LittleEndian);
+impl_layout_field!(i16, I16, read_i16, write_i16, LittleEndian);
+impl_layout_field!(i32, I32, read_i32, write_i32, LittleEndian);
+
+impl<const N: usize> LayoutField for [u8; N] {
+    const SIZE: usize = N;
+    const KIND: FieldKind = FieldKind::Bytes;
+
+    fn read_field<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let mut bytes = [0; N];
+        reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn write_field<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(self)
+    }
+}
+
+impl<const N: usize> LayoutField for [u16; N] {
+    const SIZE: usize = N * 2;
+    const KIND: FieldKind = FieldKind::U16s;
+
+    fn read_field<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let mut values = [0; N];
+        reader.read_u16_into::<LittleEndian>(&mut values)?;
+        Ok(values)
+    }
+
+    fn write_field<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.iter()
+            .try_for_each(|v| writer.write_u16::<LittleEndian>(*v))
+    }
+}
+
+impl<const N: usize> LayoutField for [i16; N] {
+    const SIZE: usize = N * 2;
+    const KIND: FieldKind = FieldKind::I16s;
+
+    fn read_field<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let mut values = [0; N];
+        reader.read_i16_into::<LittleEndian>(&mut values)?;
+        Ok(values)
+    }
+
+    fn write_field<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.iter()
+            .try_for_each(|v| writer.write_i16::<LittleEndian>(*v))
+    }
+}
+
+impl<const N: usize> LayoutField for [i32; N] {
+    const SIZE: usize = N * 4;
+    const KIND: FieldKind = FieldKind::I32s;
+
+    fn read_field<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let mut values = [0; N];
+        reader.read_i32_into::<LittleEndian>(&mut values)?;
+        Ok(values)
+    }
+
+    fn write_field<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.iter()
+            .try_for_each(|v| writer.write_i32::<LittleEndian>(*v))
+    }
+}
+
+/// Declares a record read field by field in order, little endian, without padding.
+/// Generates `SIZE`, `read`, `write` and `layout`.
+macro_rules! layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field:ident: $ty:ty,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        $vis struct $name {
+            $($(#[$field_meta])* pub $field: $ty,)*
+        }
+
+        #[allow(dead_code)]
+        impl $name {
+            pub const SIZE: usize = 0 $(+ <$ty as $crate::layout::LayoutField>::SIZE)*;
+
+            pub fn read<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+                Ok(Self {
+                    $($field: <$ty as $crate::layout::LayoutField>::read_field(reader)?,)*
+                })
+            }
+
+            pub fn write<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+                $($crate::layout::LayoutField::write_field(&self.$field, writer)?;)*
+                Ok(())
+            }
+
+            pub(crate) fn layout() -> Vec<$crate::layout::FieldLayout> {
+                let mut fields = Vec::new();
+                let mut offset = 0;
+                $(
+                    let size = <$ty as $crate::layout::LayoutField>::SIZE;
+                    fields.push($crate::layout::FieldLayout {
+                        name: stringify!($field),
+                        offset,
+                        size,
+                        kind: <$ty as $crate::layout::LayoutField>::KIND,
+                    });
+                    offset += size;
+                )*
+                let _ = offset;
+                fields
+            }
+        }
+    };
+}
+
+pub(crate) use layout;
+
+#[cfg(test)]
+mod tests {
+    use super::{markdown, FieldKind};
+
+    layout! {
+        struct Record {
+            name: [u8; 4],
+            count: u16,
+            delta: i32,
+            ids: [u16; 2],
+        }
+    }
+
+    #[test]
+    fn layout_works() {
+        let bytes = [
+            b'r', b'o', b'k', 0, 3, 0, 0xfe, 0xff, 0xff, 0xff, 1, 0, 2, 0,
+        ];
+        assert_eq!(Record::SIZE, bytes.len());
+
+        let record = Record::read(&mut &bytes[..]).unwrap();
+        assert_eq!(record.name, *b"rok\0");
+        assert_eq!((record.count, record.delta, record.ids), (3, -2, [1, 2]));
+        let mut written = Vec::new();
+        record.write(&mut written).unwrap();
+        assert_eq!(written, bytes);
+        assert!(Record::read(&mut &bytes[..10]).is_err());
+
+        let layout = Record::layout();
+        assert_eq!(layout.len(), 4);
+        assert_eq!((layout[2].name, layout[2].offset), ("delta", 6));
+        assert_eq!(layout[3].kind, FieldKind::U16s);
+        let values: Vec<_> = layout.iter().map(|f| f.value(&bytes).unwrap()).collect();
+        assert_eq!(values, vec!["\"rok\"", "3", "-2", "[1, 2]"]);
+        assert_eq!(layout[0].value(&[0, 1, 2, 3]).unwrap(), "00010203");
+        assert!(markdown(&layout).contains("| 0x06 | 4 | delta |"));
+    }
+}
@@ -1,71 +1,234 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::error::Error;
-use std::fs::{self};
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 use ::image::DynamicImage;
+use audio::{SndArchive, Sound};
+use cache::ImageCache;
 use lod::Lod;
+use map_deps::MapDependencies;
 use palette::Palettes;
+use preload::PreloadManifest;
+use progress::{NoProgress, ProgressCounter, ProgressSink};
+use video::VidArchive;
 
 pub mod bsp_model;
 pub mod dtile;
 pub mod odm;
 
+pub mod audio;
 pub mod billboard;
+pub mod blv;
+mod cache;
+pub mod checksums;
+pub use cache::CacheStats;
 pub mod ddeclist;
 pub mod dsft;
-pub mod image;
+pub mod dtft;
+pub mod events;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+mod image;
+pub mod impostor;
+pub mod inspect;
+pub mod install;
+mod layout;
+pub use image::{
+    get_atlas, get_atlas_with_progress, get_sprite_atlas, AtlasRect, SpriteAtlas, TintKind,
+};
 
 mod lod;
-pub mod lod_data;
+pub use lod::{DuplicatePolicy, LodDiff, LodWriter, Version};
+mod lod_data;
+pub mod map_deps;
+pub mod map_stats;
 pub mod palette;
+pub mod pcx;
+pub mod preload;
+pub mod prelude;
+pub mod progress;
+pub mod save;
+pub mod terrain;
 mod utils;
+pub mod video;
 mod zlib;
 
+/// Decoded images remembered by `LodManager::recent_assets`
+const RECENT_ASSETS: usize = 32;
+
 pub const ENV_OPENMM_6_PATH: &str = "OPENMM_6_PATH";
 
+/// Archives looked up first by `LodManager::find`, after the patch archives
+const STANDARD_ARCHIVES: [&str; 6] = ["new", "bitmaps", "sprites", "icons", "games", "events"];
+
+/// Lookup order of an archive: patches like mm7_patch.lod override everything, then the
+/// standard archives in their usual order, then any other archive by name.
+fn archive_precedence(name: &str) -> (usize, &str) {
+    if name.contains("patch") {
+        (0, name)
+    } else if let Some(i) = STANDARD_ARCHIVES.iter().position(|a| *a == name) {
+        (1 + i, name)
+    } else {
+        (1 + STANDARD_ARCHIVES.len(), name)
+    }
+}
+
 pub struct LodManager {
     lods: HashMap<String, Lod>,
+    strict: bool,
+    /// Decoded images by lod path
+    cache: Mutex<ImageCache>,
+    /// Lod paths of the last decoded images, newest last
+    recent: Mutex<VecDeque<String>>,
+    /// Video archives by lowercased name, e.g. `anims1`
+    videos: HashMap<String, VidArchive>,
+    /// Sound archives by lowercased name, e.g. `audio`
+    sounds: HashMap<String, SndArchive>,
+    /// Sprite palettes, parsed from bitmaps.lod by the first sprite decoded
+    palettes: OnceLock<Palettes>,
+}
+
+/// Where an entry comes from, to tell which archive or patch supplied an asset that looks wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetSource {
+    pub archive: String,
+    pub entry: String,
+    /// Lod file of the archive
+    pub file: PathBuf,
+    /// Whether the archive is a patch overriding the standard archives
+    pub patch: bool,
+    /// Other archives with an entry of the same name, in `archives()` order
+    pub shadowed: Vec<String>,
+}
+
+impl std::fmt::Display for AssetSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} from {}",
+            self.archive,
+            self.entry,
+            self.file.display()
+        )?;
+        if self.patch {
+            write!(f, " (patch)")?;
+        }
+        if !self.shadowed.is_empty() {
+            write!(f, ", also in {}", self.shadowed.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Configuration of a `LodManager`, every option has a default so only the ones that matter
+/// need to be set.
+#[derive(Debug, Default, Clone)]
+pub struct LodManagerBuilder {
+    path: Option<PathBuf>,
+    version: Option<Version>,
+    cache_size: Option<usize>,
+    strict: bool,
+    duplicates: DuplicatePolicy,
+}
+
+impl LodManagerBuilder {
+    /// Directory with the lod files or the game directory containing it, `get_lod_path()` when
+    /// not set
+    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Refuses archives made for another game, any version is accepted when not set
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// See `LodManager::set_cache_budget`, the cache is unbounded when not set
+    pub fn cache_size(mut self, bytes: usize) -> Self {
+        self.cache_size = Some(bytes);
+        self
+    }
+
+    /// See `LodManager::set_strict`
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// How entries with the same name in an archive are resolved, the last one wins by default
+    /// like in the game
+    pub fn duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicates = policy;
+        self
+    }
+
+    pub fn build(self) -> Result<LodManager, Box<dyn Error>> {
+        let path = self.path.unwrap_or_else(|| get_lod_path().into());
+        let data_dir = install::find_data_dir(path);
+        let lod_files = install::list_lod_files(&data_dir)?;
+        let lod_map = LodManager::create_lod_file_map(lod_files, self.duplicates)?;
+        if let Some(version) = self.version {
+            if let Some((name, lod)) = lod_map.iter().find(|(_, lod)| lod.version() != version) {
+                return Err(format!(
+                    "{name}.lod is for {:?}, expected {:?}",
+                    lod.version(),
+                    version
+                )
+                .into());
+            }
+        }
+
+        let mut cache = ImageCache::default();
+        cache.set_budget(self.cache_size);
+        Ok(LodManager {
+            lods: lod_map,
+            strict: self.strict,
+            cache: Mutex::new(cache),
+            recent: Mutex::default(),
+            videos: LodManager::open_optional_archives(
+                install::list_vid_files(&data_dir),
+                |path| VidArchive::open(path),
+            ),
+            sounds: LodManager::open_optional_archives(
+                install::list_snd_files(&data_dir),
+                |path| SndArchive::open(path),
+            ),
+            palettes: OnceLock::new(),
+        })
+    }
 }
 
 impl LodManager {
-    pub fn new<P>(path: P) -> Result<Self, Box<dyn Error>>
-    where
-        P: AsRef<Path>,
-    {
-        let lod_files = Self::list_lod_files(path)?;
-        let lod_map = Self::create_lod_file_map(lod_files)?;
-        Ok(Self { lods: lod_map })
+    pub fn builder() -> LodManagerBuilder {
+        LodManagerBuilder::default()
     }
 
-    fn list_lod_files<P>(path: P) -> Result<Vec<PathBuf>, std::io::Error>
+    /// Same as `LodManager::builder().path(path).build()`
+    pub fn new<P>(path: P) -> Result<Self, Box<dyn Error>>
     where
         P: AsRef<Path>,
     {
-        let mut lod_files = Vec::new();
-        let entries = fs::read_dir(&path)?;
-
-        for entry in entries {
-            let entry = entry?;
-            let file_name = entry.file_name();
-            if let Some(name) = file_name.to_str() {
-                if name.to_lowercase().ends_with(".lod") {
-                    lod_files.push(Path::join(path.as_ref(), name));
-                }
-            }
-        }
+        Self::builder().path(path).build()
+    }
 
-        Ok(lod_files)
+    /// When strict, images that fail to decode are reported as missing,
+    /// otherwise they are replaced by a placeholder so a single bad asset doesn't break loading.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
     }
 
     fn create_lod_file_map(
         lod_files: Vec<PathBuf>,
+        duplicates: DuplicatePolicy,
     ) -> Result<HashMap<String, Lod>, Box<dyn Error>> {
         let mut lod_file_map: HashMap<String, Lod> = HashMap::new();
 
         for path in lod_files.iter() {
-            let lod = Lod::open(path)?;
+            let lod = Lod::open_with(path, duplicates)?;
             let key = path
                 .file_stem()
                 .ok_or("file should have a .lod extension")?
@@ -77,23 +240,58 @@ impl LodManager {
         Ok(lod_file_map)
     }
 
-    pub fn try_get_bytes<P: AsRef<Path>>(&self, path: P) -> Result<&[u8], Box<dyn Error>> {
-        let lod_archive: String = path
-            .as_ref()
+    /// Archives by lowercased file stem. Archives that can't be read are skipped with a
+    /// warning, videos and sounds are optional.
+    fn open_optional_archives<T>(
+        files: Vec<PathBuf>,
+        open: impl Fn(&Path) -> Result<T, Box<dyn Error>>,
+    ) -> HashMap<String, T> {
+        let mut file_map = HashMap::new();
+        for path in files.iter() {
+            let Some(key) = path.file_stem() else {
+                continue;
+            };
+            match open(path) {
+                Ok(archive) => {
+                    file_map.insert(key.to_string_lossy().to_lowercase(), archive);
+                }
+                Err(e) => log::warn!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        file_map
+    }
+
+    /// Splits `archive/entry`, the archive name is lowercased like the lod file map keys.
+    fn split_path(path: &Path) -> Result<(String, String), Box<dyn Error>> {
+        let lod_archive = path
             .parent()
             .ok_or("invalid path")?
             .to_string_lossy()
+            .to_lowercase();
+        let lod_entry = path
+            .file_name()
+            .ok_or("invalid lod entry")?
+            .to_string_lossy()
             .to_string();
+        Ok((lod_archive, lod_entry))
+    }
+
+    /// Whether `archive/entry` exists, the lookup is case-insensitive
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        Self::split_path(path.as_ref()).is_ok_and(|(archive, entry)| {
+            self.lods
+                .get(&archive)
+                .is_some_and(|lod| lod.contains(&entry))
+        })
+    }
+
+    /// Raw bytes of `archive/entry`, the lookup is case-insensitive
+    pub fn try_get_bytes<P: AsRef<Path>>(&self, path: P) -> Result<&[u8], Box<dyn Error>> {
+        let (lod_archive, lod_entry) = Self::split_path(path.as_ref())?;
         let lod = self
             .lods
             .get(&lod_archive)
             .ok_or(format!("lod file not found in {lod_archive} "))?;
-        let lod_entry: String = path
-            .as_ref()
-            .file_name()
-            .ok_or("invalid lod entry")?
-            .to_string_lossy()
-            .to_string();
         let lod_data = lod.try_get_bytes(&lod_entry).ok_or(format!(
             "unable to open lod entry {:?}",
             path.as_ref().to_str()
@@ -101,27 +299,423 @@ impl LodManager {
         Ok(lod_data)
     }
 
-    fn palettes(&self) -> Result<Palettes, Box<dyn Error>> {
-        // TODO cache palettes
+    /// Archive names in lookup order, see `find`
+    pub fn archives(&self) -> Vec<&str> {
+        let mut archives: Vec<&str> = self.lods.keys().map(|k| k.as_str()).collect();
+        archives.sort_by_key(|name| archive_precedence(name));
+        archives
+    }
+
+    /// Looks an entry up in every archive, returning the archive it was found in and the data.
+    /// When several archives have it the first in `archives()` order wins.
+    pub fn find(&self, name: &str) -> Option<(&str, &[u8])> {
+        self.archives()
+            .into_iter()
+            .find_map(|archive| Some((archive, self.lods.get(archive)?.try_get_bytes(name)?)))
+    }
+
+    /// Entries matching a glob pattern like `gm*` in every archive, as `(archive, entry)` in
+    /// `archives()` order then by name. `sprites/gm*` limits the search to the matching archives.
+    pub fn glob(&self, pattern: &str) -> Vec<(&str, &str)> {
+        let (archive_pattern, entry_pattern) = pattern.rsplit_once('/').unwrap_or(("*", pattern));
+        self.archives()
+            .into_iter()
+            .filter(|archive| utils::glob_match(archive_pattern, archive))
+            .flat_map(|archive| {
+                let mut entries: Vec<(&str, &str)> = self.lods[archive]
+                    .files()
+                    .into_iter()
+                    .filter(|entry| utils::glob_match(entry_pattern, entry))
+                    .map(|entry| (archive, entry))
+                    .collect();
+                entries.sort();
+                entries
+            })
+            .collect()
+    }
+
+    /// Source of `archive/entry`, or of a bare entry name resolved like `find`
+    pub fn source(&self, path: &str) -> Option<AssetSource> {
+        let (archive, entry) = match path.rsplit_once('/') {
+            Some((archive, entry)) => (archive.to_lowercase(), entry.to_lowercase()),
+            None => (self.find(path)?.0.to_string(), path.to_lowercase()),
+        };
+        let lod = self.lods.get(&archive)?;
+        if !lod.contains(&entry) {
+            return None;
+        }
+        let shadowed = self
+            .archives()
+            .into_iter()
+            .filter(|other| *other != archive && self.lods[*other].contains(&entry))
+            .map(String::from)
+            .collect();
+        Some(AssetSource {
+            patch: archive_precedence(&archive).0 == 0,
+            file: lod.path().to_path_buf(),
+            archive,
+            entry,
+            shadowed,
+        })
+    }
+
+    /// Lists the entries of an archive, `archive` is the lod file name without extension
+    pub fn files(&self, archive: &str) -> Option<Vec<&str>> {
+        self.lods.get(archive).map(|lod| lod.files())
+    }
+
+    /// Name of the directory entry of an archive, which `files` lists with the other entries
+    pub fn directory(&self, archive: &str) -> Option<&str> {
+        self.lods
+            .get(&archive.to_lowercase())
+            .map(|lod| lod.directory())
+    }
+
+    /// Entries of `archive` that differ in `newer`, e.g. another install or a patched copy.
+    /// `None` when either of them doesn't have the archive.
+    pub fn diff(&self, archive: &str, newer: &LodManager) -> Option<LodDiff> {
+        let archive = archive.to_lowercase();
+        Some(self.lods.get(&archive)?.diff(newer.lods.get(&archive)?))
+    }
+
+    /// Game version of an archive
+    pub fn version(&self, archive: &str) -> Option<Version> {
+        self.lods
+            .get(&archive.to_lowercase())
+            .map(|lod| lod.version())
+    }
+
+    /// Extracts a whole archive to `path`: images as png, compressed data unpacked and other
+    /// entries as they are. Sprites need the palettes of bitmaps.lod.
+    pub fn save_archive<P: AsRef<Path>>(
+        &self,
+        archive: &str,
+        path: P,
+    ) -> Result<(), Box<dyn Error>> {
+        self.save_archive_with_progress(archive, path, &NoProgress)
+    }
+
+    /// Same as `save_archive`, reporting each entry written. Cancelling leaves the entries
+    /// written so far in `path`.
+    pub fn save_archive_with_progress<P: AsRef<Path>>(
+        &self,
+        archive: &str,
+        path: P,
+        progress: &dyn ProgressSink,
+    ) -> Result<(), Box<dyn Error>> {
+        let lod = self
+            .lods
+            .get(&archive.to_lowercase())
+            .ok_or(format!("lod file not found in {archive}"))?;
+        lod.save_all(path.as_ref(), self.palettes()?, progress)
+    }
+
+    /// Video names of every video archive, sorted
+    pub fn video_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.videos.values().flat_map(|vid| vid.names()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Smacker file of a video like `3dologo` or a house video, from whichever video archive
+    /// has it, read from the archive file. Decoding the frames is left to the caller.
+    pub fn video(&self, name: &str) -> Option<Vec<u8>> {
+        let mut archives: Vec<&String> = self.videos.keys().collect();
+        archives.sort();
+        archives
+            .into_iter()
+            .find_map(|archive| self.videos[archive].smk(name))
+    }
+
+    /// Sound names of every sound archive, sorted
+    pub fn sound_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.sounds.values().flat_map(|snd| snd.names()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Sound like `swing` from whichever sound archive has it, read from the archive file.
+    /// Sounds that fail to decode are skipped with a warning.
+    pub fn sound(&self, name: &str) -> Option<Sound> {
+        let mut archives: Vec<&String> = self.sounds.keys().collect();
+        archives.sort();
+        let archive = archives
+            .into_iter()
+            .find(|archive| self.sounds[*archive].contains(name))?;
+        match self.sounds[archive].sound(name) {
+            Ok(sound) => Some(sound),
+            Err(e) => {
+                log::warn!("Unable to read {} from {}: {}", name, archive, e);
+                None
+            }
+        }
+    }
+
+    /// Sprite palettes from bitmaps.lod
+    pub fn palettes(&self) -> Result<&Palettes, Box<dyn Error>> {
+        if let Some(palettes) = self.palettes.get() {
+            return Ok(palettes);
+        }
         let bitmaps_lod = self
             .lods
             .get("bitmaps")
             .ok_or("expected to have bitmaps.lod")?;
         let palettes = palette::Palettes::try_from(bitmaps_lod)?;
-        Ok(palettes)
+        Ok(self.palettes.get_or_init(|| palettes))
     }
 
     pub fn sprite(&self, name: &str) -> Option<DynamicImage> {
-        let sprite = self.try_get_bytes(format!("sprites/{}", name)).ok()?;
+        let path = format!("sprites/{}", name.to_lowercase());
+        if let Some(image) = self.cached(&path) {
+            return Some(image);
+        }
+        let sprite = self.try_get_bytes(&path).ok()?;
+        let palettes = self.palettes().ok()?;
+        let sprite = crate::image::Image::try_from((sprite, palettes));
+        self.decoded(&path, sprite)
+    }
+
+    /// Sprite with a palette tint effect applied, see [`TintKind`](crate::image::TintKind)
+    pub fn sprite_tinted(&self, name: &str, tint: crate::image::TintKind) -> Option<DynamicImage> {
+        let path = format!("sprites/{}", name.to_lowercase());
+        let key = format!("{}:{:?}", path, tint);
+        if let Some(image) = self.cached(&key) {
+            return Some(image);
+        }
+        let sprite = self.try_get_bytes(&path).ok()?;
         let palettes = self.palettes().ok()?;
-        let sprite = crate::image::Image::try_from((sprite, &palettes)).ok()?;
-        sprite.to_image_buffer().ok()
+        let sprite =
+            crate::image::Image::try_from((sprite, palettes)).map(|sprite| sprite.tinted(tint));
+        self.decoded(&key, sprite)
     }
 
     pub fn bitmap(&self, name: &str) -> Option<DynamicImage> {
+        let path = format!("bitmaps/{}", name.to_lowercase());
+        if let Some(image) = self.cached(&path) {
+            return Some(image);
+        }
+        let bitmap = self.try_get_bytes(&path).ok()?;
+        let bitmap = crate::image::Image::try_from((bitmap, self.version("bitmaps")?));
+        self.decoded(&path, bitmap)
+    }
+
+    /// Bitmap with the smaller levels stored after it, largest first. Not cached.
+    pub fn bitmap_mip_levels(&self, name: &str) -> Option<Vec<DynamicImage>> {
         let bitmap = self.try_get_bytes(format!("bitmaps/{}", name)).ok()?;
-        let bitmap = crate::image::Image::try_from(bitmap).ok()?;
-        bitmap.to_image_buffer().ok()
+        crate::image::Image::try_from((bitmap, self.version("bitmaps")?))
+            .ok()?
+            .mip_levels()
+            .ok()
+    }
+
+    /// Interface image from icons.lod: HUD parts, buttons, portraits and item pictures.
+    /// Most share the bitmap layout without mipmaps, the bigger ones are pcx pictures.
+    pub fn icon(&self, name: &str) -> Option<DynamicImage> {
+        let path = format!("icons/{}", name.to_lowercase());
+        if let Some(image) = self.cached(&path) {
+            return Some(image);
+        }
+        let data = self.try_get_bytes(&path).ok()?;
+        let icon = crate::image::Image::try_from((data, self.version("icons")?)).or_else(|e| {
+            pcx::Pcx::try_from(data)
+                .map_err(|_| e)?
+                .to_indexed_image()
+                .ok_or_else(|| "24 bit pcx icons are not supported".into())
+        });
+        self.decoded(&path, icon)
+    }
+
+    /// Fullscreen picture like the title or a loading screen, stored as pcx in any archive.
+    /// The `.pcx` extension can be left out, failures are handled like `set_strict` says.
+    pub fn screen(&self, name: &str) -> Option<DynamicImage> {
+        let (_, data) = self
+            .find(name)
+            .or_else(|| self.find(&format!("{}.pcx", name)))?;
+        // some archives store them compressed
+        let pcx = pcx::Pcx::try_from(data).or_else(|_| {
+            lod_data::LodData::try_from(data)
+                .and_then(|data| pcx::Pcx::try_from(data.data.as_slice()))
+        });
+        match pcx.and_then(|pcx| pcx.to_image_buffer()) {
+            Ok(image) => Some(image),
+            Err(e) => self.decode_failed(name, e),
+        }
+    }
+
+    /// Decodes everything the map depends on so it's already cached when the map is built.
+    /// Assets that are missing or fail to decode are skipped.
+    pub fn precache(&self, dependencies: &MapDependencies) {
+        let _ = self.precache_with_progress(dependencies, &NoProgress);
+    }
+
+    /// Same as `precache`, reporting each asset decoded so it can be cancelled when the map is
+    /// no longer needed.
+    pub fn precache_with_progress(
+        &self,
+        dependencies: &MapDependencies,
+        progress: &dyn ProgressSink,
+    ) -> Result<(), Box<dyn Error>> {
+        let progress = ProgressCounter::new(
+            progress,
+            dependencies.bitmaps.len() + dependencies.sprites.len(),
+        );
+        for name in &dependencies.bitmaps {
+            let _ = self.bitmap(name);
+            if !progress.step() {
+                return progress.result();
+            }
+        }
+        for name in &dependencies.sprites {
+            let _ = self.sprite(name);
+            if !progress.step() {
+                return progress.result();
+            }
+        }
+        Ok(())
+    }
+
+    /// Limits the memory used by decoded images, `None` lets the cache grow unbounded.
+    pub fn set_cache_budget(&self, bytes: Option<usize>) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.set_budget(bytes);
+        }
+    }
+
+    /// Evicts least recently used images until the cache holds at most `bytes`,
+    /// useful after unloading a map.
+    pub fn trim_cache_to(&self, bytes: usize) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.trim_to(bytes);
+        }
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache
+            .lock()
+            .map(|cache| cache.stats())
+            .unwrap_or_default()
+    }
+
+    /// Decodes the manifest entries in parallel into the cache. Entries that are missing,
+    /// fail to decode or aren't bitmaps or sprites are skipped.
+    pub fn preload(&self, manifest: &PreloadManifest) {
+        let _ = self.preload_with_progress(manifest, &NoProgress);
+    }
+
+    /// Same as `preload`, reporting each image decoded. Cancelling leaves the images decoded so
+    /// far in the cache.
+    pub fn preload_with_progress(
+        &self,
+        manifest: &PreloadManifest,
+        progress: &dyn ProgressSink,
+    ) -> Result<(), Box<dyn Error>> {
+        let progress = ProgressCounter::new(progress, manifest.paths.len());
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = manifest.paths.len().div_ceil(threads).max(1);
+        std::thread::scope(|s| {
+            for paths in manifest.paths.chunks(chunk_size) {
+                let progress = &progress;
+                s.spawn(move || {
+                    for path in paths {
+                        match path.split_once('/') {
+                            Some(("bitmaps", name)) => {
+                                let _ = self.bitmap(name);
+                            }
+                            Some(("sprites", name)) => {
+                                let _ = self.sprite(name);
+                            }
+                            _ => {}
+                        }
+                        if !progress.step() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        progress.result()
+    }
+
+    /// Lod paths of the last decoded images, oldest first, for diagnostics
+    pub fn recent_assets(&self) -> Vec<String> {
+        self.recent
+            .lock()
+            .map(|recent| recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Sources of the last decoded images, oldest first, for the debug tools
+    pub fn recent_sources(&self) -> Vec<AssetSource> {
+        self.recent_assets()
+            .iter()
+            .filter_map(|asset| {
+                // keys may carry a tint or a failure note, see `decoded`
+                let path = asset.split([':', ' ']).next()?;
+                self.source(path)
+            })
+            .collect()
+    }
+
+    /// Paths of the plain images in the cache, tinted variants are left out.
+    fn cached_paths(&self) -> Vec<String> {
+        self.cache
+            .lock()
+            .map(|cache| {
+                cache
+                    .keys()
+                    .filter(|key| !key.contains(':'))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Expands a cached image after releasing the lock so other threads aren't held up
+    fn cached(&self, key: &str) -> Option<DynamicImage> {
+        let image = self.cache.lock().ok()?.get(key)?;
+        image.to_image_buffer().ok()
+    }
+
+    /// Converts the decoded image and caches it, failures are not cached so they're reported
+    /// every time.
+    fn decoded(
+        &self,
+        key: &str,
+        image: Result<crate::image::Image, Box<dyn Error>>,
+    ) -> Option<DynamicImage> {
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT_ASSETS {
+                recent.pop_front();
+            }
+            let failed = if image.is_err() { " (failed)" } else { "" };
+            recent.push_back(format!("{key}{failed}"));
+        }
+        let rgba = image.and_then(|mut image| {
+            let rgba = image.to_image_buffer()?;
+            // mipmaps are not used
+            image.data.truncate(image.width * image.height);
+            if let Ok(mut cache) = self.cache.lock() {
+                cache.insert(key.to_string(), image);
+            }
+            Ok(rgba)
+        });
+        match rgba {
+            Ok(rgba) => Some(rgba),
+            Err(e) => self.decode_failed(key, e),
+        }
+    }
+
+    /// Nothing when strict, a placeholder otherwise
+    fn decode_failed(&self, key: &str, e: Box<dyn Error>) -> Option<DynamicImage> {
+        if self.strict {
+            log::warn!("Unable to decode {}: {}", key, e);
+            None
+        } else {
+            log::warn!("Unable to decode {}: {}, using a placeholder", key, e);
+            Some(crate::image::placeholder())
+        }
     }
 }
 
@@ -133,9 +727,65 @@ pub fn get_lod_path() -> String {
     env::var(ENV_OPENMM_6_PATH).unwrap_or("./target/mm6/data".into())
 }
 
+/// Fixture directory under the system temp dir, removed when dropped so a failing assert
+/// doesn't leave it behind
+#[cfg(test)]
+pub(crate) struct TempDir(PathBuf);
+
+/// `(archive, [(entry, data)])` pairs for `TempDir::write_lods`
+#[cfg(test)]
+pub(crate) type LodContents<'a> = [(&'a str, &'a [(&'a str, &'a [u8])])];
+
+#[cfg(test)]
+impl TempDir {
+    /// Empty directory, `name` only makes it easy to recognize
+    pub fn new(name: &str) -> Self {
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path =
+            env::temp_dir().join(format!("openmm_{}_{}_{}", name, std::process::id(), count));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.0.join(path)
+    }
+
+    /// Writes an MM6 `<archive>.lod` holding `entries` for each archive
+    pub fn write_lods(&self, archives: &LodContents) {
+        for (archive, entries) in archives {
+            let mut writer = LodWriter::new("GameMMVI", archive).unwrap();
+            for (name, data) in *entries {
+                writer.add(name, data.to_vec()).unwrap();
+            }
+            writer.write(self.join(format!("{archive}.lod"))).unwrap();
+        }
+    }
+
+    /// `LodManager` over the lods `write_lods` writes for `archives`
+    pub fn lod_manager(&self, archives: &LodContents) -> LodManager {
+        self.write_lods(archives);
+        LodManager::new(self.path()).unwrap()
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
 
     #[test]
     fn lod_manager_works() {
@@ -145,6 +795,205 @@ mod tests {
         assert_eq!(17676, grastyl.unwrap().len());
     }
 
+    #[test]
+    fn decode_failures_fall_back_to_placeholder() {
+        let mut lod_manager = LodManager {
+            lods: HashMap::new(),
+            strict: false,
+            cache: Mutex::default(),
+            recent: Mutex::default(),
+            videos: HashMap::new(),
+            sounds: HashMap::new(),
+            palettes: OnceLock::new(),
+        };
+        let placeholder = lod_manager.decoded("broken", Err("bad data".into()));
+        assert!(placeholder.is_some());
+        assert_eq!(lod_manager.recent_assets(), vec!["broken (failed)"]);
+        assert!(lod_manager.cached("broken").is_none());
+
+        lod_manager.set_strict(true);
+        assert!(lod_manager
+            .decoded("broken", Err("bad data".into()))
+            .is_none());
+    }
+
+    #[test]
+    fn bitmap_cache_ignores_case() {
+        let dir = TempDir::new("bitmap");
+        let mut writer = LodWriter::new("GameMMVI", "bitmaps").unwrap();
+        writer
+            .add_bitmap("Grass", 3, 2, &[0, 1, 2, 3, 4, 5], &[0; 768].into())
+            .unwrap();
+        writer.write(dir.join("bitmaps.lod")).unwrap();
+        let lod_manager = LodManager::new(dir.path()).unwrap();
+
+        assert!(lod_manager.bitmap("GRASS").is_some());
+        assert!(lod_manager.bitmap("grass").is_some());
+        assert_eq!(
+            PreloadManifest::from_cache(&lod_manager).paths,
+            vec!["bitmaps/grass"]
+        );
+    }
+
+    #[test]
+    fn preload_can_be_cancelled() {
+        let lod_manager = LodManager {
+            lods: HashMap::new(),
+            strict: false,
+            cache: Mutex::default(),
+            recent: Mutex::default(),
+            videos: HashMap::new(),
+            sounds: HashMap::new(),
+            palettes: OnceLock::new(),
+        };
+        let manifest: PreloadManifest = "bitmaps/a\nbitmaps/b\nsprites/c".parse().unwrap();
+        assert!(lod_manager
+            .preload_with_progress(&manifest, &|_, _| true)
+            .is_ok());
+        assert!(lod_manager
+            .preload_with_progress(&manifest, &|done, _| done < 2)
+            .is_err());
+    }
+
+    #[test]
+    fn builder_works() {
+        let dir = TempDir::new("builder");
+        dir.write_lods(&[("icons", &[("raw", b"raw data")])]);
+        fs::write(dir.join("broken.vid"), [9, 0, 0, 0]).unwrap();
+        fs::write(dir.join("broken.snd"), [9, 0, 0, 0]).unwrap();
+        let wav = Sound {
+            channels: 1,
+            sample_rate: 22050,
+            bits_per_sample: 8,
+            samples: vec![1, 2],
+        };
+        let mut snd = 1u32.to_le_bytes().to_vec();
+        snd.extend(b"Swing");
+        snd.resize(4 + 40, 0);
+        snd.extend(52u32.to_le_bytes());
+        snd.extend((wav.to_wav().len() as u32).to_le_bytes());
+        snd.extend(wav.to_wav());
+        fs::write(dir.join("audio.snd"), snd).unwrap();
+
+        let lod_manager = LodManager::builder()
+            .path(dir.path())
+            .version(Version::MM6)
+            .cache_size(1024)
+            .strict(true)
+            .build()
+            .unwrap();
+        let wrong_version = LodManager::builder()
+            .path(dir.path())
+            .version(Version::MM7)
+            .build();
+        // sounds are read from the archive file when asked for
+        let sound = lod_manager.sound("SWING");
+
+        assert!(lod_manager.strict);
+        assert_eq!(lod_manager.try_get_bytes("icons/raw").unwrap(), b"raw data");
+        assert_eq!(lod_manager.try_get_bytes("ICONS/Raw").unwrap(), b"raw data");
+        assert!(lod_manager.contains("Icons/RAW"));
+        assert!(lod_manager.video_names().is_empty());
+        assert_eq!(lod_manager.sound_names(), vec!["swing"]);
+        assert_eq!(sound, Some(wav));
+        assert!(lod_manager.sound("missing").is_none());
+        assert!(!lod_manager.contains("icons/missing"));
+        assert!(wrong_version.is_err());
+        let path = dir.path().to_path_buf();
+        drop(dir);
+        assert!(LodManager::builder().path(path).build().is_err());
+    }
+
+    #[test]
+    fn diff_works() {
+        let (old_dir, new_dir) = (TempDir::new("diff_old"), TempDir::new("diff_new"));
+        let old = old_dir.lod_manager(&[("games", &[("same", b"same"), ("changed", b"old")])]);
+        let new = new_dir.lod_manager(&[("games", &[("same", b"same"), ("changed", b"new")])]);
+
+        let diff = old.diff("Games", &new).unwrap();
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].0, "changed");
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert!(old.diff("games", &old).unwrap().is_empty());
+        assert!(old.diff("icons", &new).is_none());
+    }
+
+    #[test]
+    fn save_archive_works() {
+        let dir = TempDir::new("save");
+        let lod_manager = dir.lod_manager(&[("bitmaps", &[("notes.txt", b"raw data")])]);
+        let out = dir.join("out");
+
+        assert!(lod_manager.save_archive("BITMAPS", &out).is_ok());
+        assert!(lod_manager.save_archive("games", &out).is_err());
+        assert_eq!(fs::read(out.join("notes.txt")).unwrap(), b"raw data");
+    }
+
+    #[test]
+    fn find_follows_precedence() {
+        let dir = TempDir::new("find");
+        let lod_manager = dir.lod_manager(&[
+            ("icons", &[("dup", b"icons"), ("only", b"icons")]),
+            ("mm6_patch", &[("dup", b"patch")]),
+            ("custom", &[("dup", b"custom"), ("extra", b"custom")]),
+        ]);
+
+        assert_eq!(lod_manager.archives(), vec!["mm6_patch", "icons", "custom"]);
+        assert_eq!(lod_manager.find("dup"), Some(("mm6_patch", &b"patch"[..])));
+        assert_eq!(lod_manager.find("ONLY"), Some(("icons", &b"icons"[..])));
+        assert_eq!(lod_manager.find("extra"), Some(("custom", &b"custom"[..])));
+        assert_eq!(lod_manager.find("missing"), None);
+        assert_eq!(
+            lod_manager.glob("DUP"),
+            vec![("mm6_patch", "dup"), ("icons", "dup"), ("custom", "dup")]
+        );
+        assert_eq!(lod_manager.glob("ICONS/?n*"), vec![("icons", "only")]);
+        assert_eq!(lod_manager.glob("*/e*"), vec![("custom", "extra")]);
+        assert!(lod_manager.glob("games/*").is_empty());
+
+        let source = lod_manager.source("DUP").unwrap();
+        assert_eq!((source.archive.as_str(), source.patch), ("mm6_patch", true));
+        assert_eq!(source.file, dir.join("mm6_patch.lod"));
+        assert_eq!(source.shadowed, vec!["icons", "custom"]);
+        let source = lod_manager.source("icons/dup").unwrap();
+        assert!(!source.patch);
+        assert_eq!(source.shadowed, vec!["mm6_patch", "custom"]);
+        assert!(source.to_string().ends_with("also in mm6_patch, custom"));
+        assert!(lod_manager.source("icons/extra").is_none());
+        assert!(lod_manager.source("missing").is_none());
+    }
+
+    #[test]
+    fn icon_works() {
+        let dir = TempDir::new("icon");
+        let mut writer = LodWriter::new("GameMMVI", "icons").unwrap();
+        let palette = palette::Palette::from([0; palette::PALETTE_SIZE]);
+        writer
+            .add_bitmap("button", 2, 1, &[0, 1], &palette)
+            .unwrap();
+        // an uncompressed 1x1 pcx
+        let mut pcx = vec![0; 128];
+        pcx[..4].copy_from_slice(&[0x0a, 5, 0, 8]);
+        pcx[65..68].copy_from_slice(&[1, 1, 0]);
+        pcx.extend([0, 0x0c]);
+        pcx.extend([7; palette::PALETTE_SIZE]);
+        writer.add("title", pcx).unwrap();
+        writer.add("broken.pcx", vec![0x0a; 4]).unwrap();
+        writer.write(dir.join("icons.lod")).unwrap();
+        let mut lod_manager = LodManager::new(dir.path()).unwrap();
+
+        assert_eq!(lod_manager.icon("button").unwrap().width(), 2);
+        assert!(lod_manager.cached("icons/button").is_some());
+        assert_eq!(lod_manager.icon("title").unwrap().width(), 1);
+        assert!(lod_manager.cached("icons/title").is_some());
+        assert!(lod_manager.icon("missing").is_none());
+
+        assert_eq!(lod_manager.screen("title").unwrap().width(), 1);
+        assert_eq!(lod_manager.screen("broken").unwrap().width(), 64);
+        lod_manager.set_strict(true);
+        assert!(lod_manager.screen("broken").is_none());
+    }
+
     #[test]
     fn sprite_works() {
         let lod_path = get_lod_path();
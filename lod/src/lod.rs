@@ -1,24 +1,54 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     error::Error,
     fs::{self, File},
-    io::{BufReader, Cursor, Read, Seek, SeekFrom},
-    path::Path,
+    io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{write::ZlibEncoder, Compression, Crc};
+use image::DynamicImage;
 
-use crate::{lod_data::LodData, palette, utils::try_read_string};
+use crate::{
+    lod_data::LodData,
+    palette::{self, Palette, Palettes},
+    progress::{ProgressCounter, ProgressSink},
+    utils::try_read_string,
+};
 
 #[allow(dead_code)]
 pub(super) struct Lod {
     version: Version,
     files: HashMap<String, Vec<u8>>,
+    /// File the archive was read from
+    path: PathBuf,
+    /// Name of the directory entry, which is listed with the files
+    directory: String,
+}
+
+/// Which entry wins when an archive has several with the same name, names being compared
+/// case-insensitively
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    First,
+    /// The game engine keeps the last one
+    #[default]
+    Last,
+    /// Refuses the archive
+    Error,
 }
 
 impl Lod {
     pub(super) fn open<P: AsRef<Path>>(path: P) -> Result<Lod, Box<dyn std::error::Error>> {
-        let file: File = File::open(path)?;
+        Self::open_with(path, DuplicatePolicy::default())
+    }
+
+    pub(super) fn open_with<P: AsRef<Path>>(
+        path: P,
+        duplicates: DuplicatePolicy,
+    ) -> Result<Lod, Box<dyn std::error::Error>> {
+        let file: File = File::open(&path)?;
         let mut buf_reader = BufReader::new(file);
 
         let magic = try_read_string(&mut buf_reader)?;
@@ -29,25 +59,92 @@ impl Lod {
         let version = Version::try_from(try_read_string(&mut buf_reader)?.as_str())?;
 
         let file_headers = read_file_headers(&mut buf_reader)?;
-        let files = read_files(file_headers, buf_reader)?;
+        let file_size = buf_reader.get_ref().metadata()?.len();
+        if let Some(fh) = file_headers.iter().find(|fh| {
+            fh.offset
+                .checked_add(fh.size)
+                .is_none_or(|end| end > file_size)
+        }) {
+            return Err(format!("lod entry {} ends past the end of the archive", fh.name).into());
+        }
+        let directory = file_headers[0].name.to_lowercase();
+        let files = read_files(file_headers, buf_reader, duplicates)?;
+
+        Ok(Lod {
+            version,
+            files,
+            path: path.as_ref().to_path_buf(),
+            directory,
+        })
+    }
+
+    pub(super) fn version(&self) -> Version {
+        self.version
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
 
-        Ok(Lod { version, files })
+    pub(super) fn directory(&self) -> &str {
+        &self.directory
     }
 
     pub(super) fn files(&self) -> Vec<&str> {
         self.files.keys().map(|f| f.as_str()).collect()
     }
 
+    /// Entries are indexed by their lowercase name, like the games lookups are case-insensitive.
     pub(super) fn try_get_bytes<'a>(&'a self, name: &str) -> Option<&'a [u8]> {
-        self.files.get(name).map(|v| v.as_slice())
+        self.files.get(&name.to_lowercase()).map(|v| v.as_slice())
+    }
+
+    pub(super) fn contains(&self, name: &str) -> bool {
+        self.files.contains_key(&name.to_lowercase())
     }
 
-    fn save_all(&self, path: &Path, palettes: &palette::Palettes) -> Result<(), Box<dyn Error>> {
+    /// Compares the entries of two archives, `other` being the newer one. The directory entries
+    /// span every other entry, so they are left out.
+    pub(super) fn diff(&self, other: &Lod) -> LodDiff {
+        let mut diff = LodDiff::default();
+        for (name, data) in &self.files {
+            if *name == self.directory {
+                continue;
+            }
+            match other.files.get(name) {
+                None => diff.removed.push((name.clone(), crc32(data))),
+                Some(other_data) if data != other_data => {
+                    diff.changed
+                        .push((name.clone(), crc32(data), crc32(other_data)));
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, data) in &other.files {
+            if *name != other.directory && !self.files.contains_key(name) {
+                diff.added.push((name.clone(), crc32(data)));
+            }
+        }
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
+    }
+
+    /// Writes every entry to `path`: images as png, compressed data unpacked, anything else as is.
+    /// Each entry written is reported to `progress`.
+    pub(super) fn save_all(
+        &self,
+        path: &Path,
+        palettes: &palette::Palettes,
+        progress: &dyn ProgressSink,
+    ) -> Result<(), Box<dyn Error>> {
         fs::create_dir_all(path)?;
+        let progress = ProgressCounter::new(progress, self.files.len());
         for file in &self.files {
             let file_name = file.0;
             let data = file.1.as_slice();
-            if let Ok(image) = crate::image::Image::try_from(data) {
+            if let Ok(image) = crate::image::Image::try_from((data, self.version())) {
                 if let Err(e) = image.save(path.join(format!("{}.png", file_name))) {
                     println!("Error saving image {} : {}", file_name, e);
                 }
@@ -59,8 +156,53 @@ impl Lod {
                 if let Err(e) = lod_data.dump(path.join(file_name)) {
                     println!("Error saving lod data {} : {}", file_name, e)
                 }
+            } else if let Err(e) = fs::write(path.join(file_name), data) {
+                println!("Error saving {} : {}", file_name, e)
+            }
+            if !progress.step() {
+                break;
             }
         }
+        progress.result()
+    }
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// Entries that differ between two versions of an archive, with the CRC32 of their data.
+#[derive(Debug, Default)]
+pub struct LodDiff {
+    pub added: Vec<(String, u32)>,
+    pub removed: Vec<(String, u32)>,
+    /// name, old CRC32, new CRC32
+    pub changed: Vec<(String, u32, u32)>,
+}
+
+impl LodDiff {
+    pub fn new<P: AsRef<Path>>(old: P, new: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Lod::open(old)?.diff(&Lod::open(new)?))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl std::fmt::Display for LodDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, crc) in &self.added {
+            writeln!(f, "+ {:<16} {:08x}", name, crc)?;
+        }
+        for (name, crc) in &self.removed {
+            writeln!(f, "- {:<16} {:08x}", name, crc)?;
+        }
+        for (name, old_crc, new_crc) in &self.changed {
+            writeln!(f, "~ {:<16} {:08x} -> {:08x}", name, old_crc, new_crc)?;
+        }
         Ok(())
     }
 }
@@ -70,7 +212,17 @@ fn read_file_headers(buf_reader: &mut BufReader<File>) -> Result<Vec<FileHeader>
     let initial_file_header: FileHeader = read_file_header(buf_reader)?;
     let initial_offset = initial_file_header.offset;
     let num_files = initial_file_header.count as usize;
-    let mut file_headers = Vec::with_capacity(num_files);
+    // a corrupt count can't make us reserve more headers than the file holds
+    let file_len = buf_reader.get_ref().metadata()?.len();
+    let max_files = file_len.saturating_sub(FILE_INDEX_OFFSET) as usize / FILE_HEADER_SIZE;
+    if num_files > max_files {
+        return Err(format!(
+            "lod has {} entries, only {} fit in the file",
+            num_files, max_files
+        )
+        .into());
+    }
+    let mut file_headers = Vec::with_capacity(num_files + 1);
     file_headers.push(initial_file_header);
     for _ in 0..num_files {
         let mut file_header = read_file_header(buf_reader)?;
@@ -90,18 +242,29 @@ fn read_file_header(buf_reader: &mut BufReader<File>) -> Result<FileHeader, Box<
 fn read_files(
     file_headers: Vec<FileHeader>,
     mut buf_reader: BufReader<File>,
+    duplicates: DuplicatePolicy,
 ) -> Result<HashMap<String, Vec<u8>>, Box<dyn Error>> {
     let mut files: HashMap<String, Vec<u8>> = HashMap::new();
     for fh in file_headers {
+        let name = fh.name.to_lowercase();
+        if files.contains_key(&name) {
+            match duplicates {
+                DuplicatePolicy::First => continue,
+                DuplicatePolicy::Last => {}
+                DuplicatePolicy::Error => {
+                    return Err(format!("duplicate lod entry {}", fh.name).into())
+                }
+            }
+        }
         let buf = read_file(&mut buf_reader, &fh)?;
-        files.insert(fh.name.to_lowercase(), buf);
+        files.insert(name, buf);
     }
     Ok(files)
 }
 
 fn read_file(buf_reader: &mut BufReader<File>, fh: &FileHeader) -> Result<Vec<u8>, Box<dyn Error>> {
-    buf_reader.seek(SeekFrom::Start(fh.offset as u64))?;
-    let mut buf = vec![0; fh.size];
+    buf_reader.seek(SeekFrom::Start(fh.offset))?;
+    let mut buf = vec![0; fh.size.try_into()?];
     buf_reader.read_exact(&mut buf)?;
     Ok(buf.to_vec())
 }
@@ -109,9 +272,10 @@ fn read_file(buf_reader: &mut BufReader<File>, fh: &FileHeader) -> Result<Vec<u8
 #[derive(Debug)]
 struct FileHeader {
     name: String,
-    offset: i32,
-    size: usize,
-    count: i32,
+    /// From the start of the archive once read, the index stores them from the directory
+    offset: u64,
+    size: u64,
+    count: u32,
 }
 
 const FILE_HEADER_SIZE: usize = 32;
@@ -125,21 +289,346 @@ impl TryFrom<&[u8; FILE_HEADER_SIZE]> for FileHeader {
         let name: &str = std::str::from_utf8(&data[0..first_zero_idx])?;
 
         let mut cursor = Cursor::new(&data[16..]);
-        let offset = cursor.read_i32::<LittleEndian>()?;
-        let size = cursor.read_i32::<LittleEndian>()?;
-        let _ = cursor.read_i32::<LittleEndian>()?;
-        let count = cursor.read_i32::<LittleEndian>()?;
+        let offset = cursor.read_u32::<LittleEndian>()?;
+        let size = cursor.read_u32::<LittleEndian>()?;
+        let _ = cursor.read_u32::<LittleEndian>()?;
+        let count = cursor.read_u32::<LittleEndian>()?;
         Ok(FileHeader {
             name: name.to_string(),
-            offset,
-            size: size as usize,
+            offset: offset.into(),
+            size: size.into(),
             count,
         })
     }
 }
 
+const LOD_HEADER_FIELD_SIZE: usize = 80;
+const LOD_NAME_SIZE: usize = 16;
+const BITMAP_HEADER_SIZE: usize = 48;
+
+/// Builds a new lod archive with a single directory, like the ones shipped with the games.
+pub struct LodWriter {
+    version: String,
+    directory: String,
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl LodWriter {
+    /// `version` is the game version string, e.g. "GameMMVI", `directory` is the name of the
+    /// archive directory, e.g. "bitmaps".
+    pub fn new(version: &str, directory: &str) -> Result<Self, Box<dyn Error>> {
+        Version::try_from(version)?;
+        Ok(Self {
+            version: version.to_string(),
+            directory: entry_name(directory)?,
+            entries: BTreeMap::new(),
+        })
+    }
+
+    /// Adds an entry as is, replacing any entry with the same name.
+    pub fn add(&mut self, name: &str, data: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        self.entries.insert(entry_name(name)?, data);
+        Ok(())
+    }
+
+    /// Adds every file in `path`, packing back what `save_all` extracts: png images become
+    /// bitmaps named after the file without extension, with a palette made of their own colors,
+    /// and game data is compressed again, see `add_data`. Other files are added as they are.
+    /// Sprites need the sprite palettes, a sprites archive is packed with `add_sprite_dir`.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        self.add_files(path.as_ref(), None)
+    }
+
+    /// Same as `add_dir` for an extracted sprites.lod, png images become sprites using the
+    /// closest of `palettes`, see `add_sprite`.
+    pub fn add_sprite_dir<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        palettes: &Palettes,
+    ) -> Result<(), Box<dyn Error>> {
+        self.add_files(path.as_ref(), Some(palettes))
+    }
+
+    fn add_files(
+        &mut self,
+        path: &Path,
+        palettes: Option<&Palettes>,
+    ) -> Result<(), Box<dyn Error>> {
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .ok_or("invalid file name")?
+                .to_string_lossy()
+                .to_lowercase();
+            if let Some(stem) = name.strip_suffix(".png") {
+                let image = image::open(&path)?;
+                if let Some(palettes) = palettes {
+                    self.add_sprite(stem, &image, palettes)?;
+                    continue;
+                }
+                if self.directory == "sprites" {
+                    return Err(format!(
+                        "{} is a sprite, sprites are packed with add_sprite_dir",
+                        path.display()
+                    )
+                    .into());
+                }
+                let (pixels, palette) = own_palette(&image)
+                    .ok_or_else(|| format!("{} has more than 256 colors", path.display()))?;
+                let (width, height) = (image.width().try_into()?, image.height().try_into()?);
+                self.add_bitmap(stem, width, height, &pixels, &palette)?;
+            } else if compressed_header(&name).is_some() {
+                self.add_data(&name, &fs::read(&path)?)?;
+            } else {
+                self.add(&name, fs::read(&path)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds an image as a sprite with the palette of `palettes` closest to its colors. Pixels
+    /// with an alpha under half are transparent.
+    pub fn add_sprite(
+        &mut self,
+        name: &str,
+        image: &DynamicImage,
+        palettes: &Palettes,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = entry_name(name)?;
+        let data = crate::image::encode_sprite(&name, image, palettes)?;
+        self.entries.insert(name, data);
+        Ok(())
+    }
+
+    /// Adds game data zlib compressed with the header the game expects: tables like dtile.bin
+    /// have a bitmap-like header, maps and map deltas the size header. Other entries are stored
+    /// as they are.
+    pub fn add_data(&mut self, name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let name = entry_name(name)?;
+        let Some(header) = compressed_header(&name) else {
+            self.entries.insert(name, data.to_vec());
+            return Ok(());
+        };
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        let mut entry = Vec::with_capacity(BITMAP_HEADER_SIZE + compressed.len());
+        match header {
+            CompressedHeader::Table => {
+                write_name(&mut entry, &name, LOD_NAME_SIZE)?;
+                entry.write_u32::<LittleEndian>(data.len() as u32)?;
+                entry.write_u32::<LittleEndian>(compressed.len() as u32)?;
+                // no size nor palette
+                entry.resize(entry.len() + 16, 0);
+                entry.write_u32::<LittleEndian>(data.len() as u32)?;
+                // flags
+                entry.write_u32::<LittleEndian>(0)?;
+            }
+            CompressedHeader::Size => {
+                entry.write_u32::<LittleEndian>(compressed.len() as u32)?;
+                entry.write_u32::<LittleEndian>(data.len() as u32)?;
+            }
+        }
+        entry.extend_from_slice(&compressed);
+        self.entries.insert(name, entry);
+        Ok(())
+    }
+
+    /// Adds an 8-bit bitmap with zlib compressed pixels and its palette.
+    pub fn add_bitmap(
+        &mut self,
+        name: &str,
+        width: u16,
+        height: u16,
+        pixels: &[u8],
+        palette: &Palette,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = entry_name(name)?;
+        let pixel_size = width as usize * height as usize;
+        if pixels.len() != pixel_size || pixel_size == 0 {
+            return Err("Pixel data doesn't match the bitmap size".into());
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(pixels)?;
+        let compressed = encoder.finish()?;
+
+        let mut data =
+            Vec::with_capacity(BITMAP_HEADER_SIZE + compressed.len() + palette.data.len());
+        write_name(&mut data, &name, LOD_NAME_SIZE)?;
+        data.write_u32::<LittleEndian>(pixel_size as u32)?;
+        data.write_u32::<LittleEndian>(compressed.len() as u32)?;
+        data.write_u16::<LittleEndian>(width)?;
+        data.write_u16::<LittleEndian>(height)?;
+        data.write_u16::<LittleEndian>(log2(width))?;
+        data.write_u16::<LittleEndian>(log2(height))?;
+        data.write_u16::<LittleEndian>(width.saturating_sub(1))?;
+        data.write_u16::<LittleEndian>(height.saturating_sub(1))?;
+        // palette id and unknown
+        data.write_u32::<LittleEndian>(0)?;
+        data.write_u32::<LittleEndian>(pixel_size as u32)?;
+        // flags
+        data.write_u32::<LittleEndian>(0)?;
+        data.extend_from_slice(&compressed);
+        data.extend_from_slice(&palette.data);
+
+        self.entries.insert(name, data);
+        Ok(())
+    }
+
+    /// Adds an image as a bitmap, mapping its colors to the closest ones in `palette`.
+    pub fn add_image(
+        &mut self,
+        name: &str,
+        image: &DynamicImage,
+        palette: &Palette,
+    ) -> Result<(), Box<dyn Error>> {
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let pixels = palette.quantize(rgb.as_raw(), width as usize, false);
+        self.add_bitmap(
+            name,
+            width.try_into()?,
+            height.try_into()?,
+            &pixels,
+            palette,
+        )
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        let index_size = self.entries.len() * FILE_HEADER_SIZE;
+        let data_size: usize = self.entries.values().map(|data| data.len()).sum();
+        let directory_offset = FILE_INDEX_OFFSET as usize + FILE_HEADER_SIZE;
+
+        let mut header = Vec::with_capacity(FILE_INDEX_OFFSET as usize);
+        header.extend_from_slice(b"LOD\0");
+        write_name(&mut header, &self.version, LOD_HEADER_FIELD_SIZE)?;
+        write_name(&mut header, "openmm", LOD_HEADER_FIELD_SIZE)?;
+        header.write_u32::<LittleEndian>(100)?;
+        header.write_u32::<LittleEndian>(0)?;
+        // directories count
+        header.write_u32::<LittleEndian>(1)?;
+        header.resize(FILE_INDEX_OFFSET as usize, 0);
+        writer.write_all(&header)?;
+
+        write_file_header(
+            writer,
+            &self.directory,
+            directory_offset,
+            index_size + data_size,
+            self.entries.len(),
+        )?;
+        let mut offset = index_size;
+        for (name, data) in &self.entries {
+            write_file_header(writer, name, offset, data.len(), 0)?;
+            offset += data.len();
+        }
+        for data in self.entries.values() {
+            writer.write_all(data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Header in front of the compressed game data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompressedHeader {
+    /// Bitmap header without image, used by the tables of icons.lod
+    Table,
+    /// Compressed and uncompressed sizes, used by the maps
+    Size,
+}
+
+/// Header of the entries the game stores compressed, by extension
+fn compressed_header(name: &str) -> Option<CompressedHeader> {
+    match name.rsplit_once('.')?.1 {
+        "bin" => Some(CompressedHeader::Table),
+        "odm" | "blv" | "dlv" | "ddm" => Some(CompressedHeader::Size),
+        _ => None,
+    }
+}
+
+/// Palette indices of an image with at most 256 colors and the palette of its colors in order
+/// of appearance, so extracted bitmaps are packed back without losing colors.
+fn own_palette(image: &DynamicImage) -> Option<(Vec<u8>, Palette)> {
+    let rgb = image.to_rgb8();
+    let mut colors: Vec<[u8; 3]> = Vec::new();
+    let mut indices = HashMap::new();
+    let mut pixels = Vec::with_capacity(rgb.len() / 3);
+    for pixel in rgb.pixels() {
+        let index = match indices.get(&pixel.0) {
+            Some(&index) => index,
+            None if colors.len() < 256 => {
+                let index = colors.len() as u8;
+                colors.push(pixel.0);
+                indices.insert(pixel.0, index);
+                index
+            }
+            None => return None,
+        };
+        pixels.push(index);
+    }
+    let mut palette = [0; palette::PALETTE_SIZE];
+    for (i, color) in colors.iter().enumerate() {
+        palette[i * 3..i * 3 + 3].copy_from_slice(color);
+    }
+    Some((pixels, Palette::from(palette)))
+}
+
+/// Lod entry names are lowercase and must fit in 15 bytes plus the terminator.
+fn entry_name(name: &str) -> Result<String, Box<dyn Error>> {
+    if name.is_empty() || name.len() >= LOD_NAME_SIZE || !name.is_ascii() {
+        return Err(format!("Invalid lod entry name: {:?}", name).into());
+    }
+    Ok(name.to_lowercase())
+}
+
+/// Writes a zero padded string field.
+fn write_name(data: &mut Vec<u8>, name: &str, size: usize) -> Result<(), Box<dyn Error>> {
+    if name.len() >= size {
+        return Err(format!("{:?} doesn't fit in {}B", name, size).into());
+    }
+    data.extend_from_slice(name.as_bytes());
+    data.resize(data.len() + size - name.len(), 0);
+    Ok(())
+}
+
+fn write_file_header<W: Write>(
+    writer: &mut W,
+    name: &str,
+    offset: usize,
+    size: usize,
+    count: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut header = Vec::with_capacity(FILE_HEADER_SIZE);
+    write_name(&mut header, name, LOD_NAME_SIZE)?;
+    header.write_u32::<LittleEndian>(offset.try_into()?)?;
+    header.write_u32::<LittleEndian>(size.try_into()?)?;
+    header.write_u32::<LittleEndian>(0)?;
+    header.write_u32::<LittleEndian>(count.try_into()?)?;
+    writer.write_all(&header)?;
+    Ok(())
+}
+
+fn log2(size: u16) -> u16 {
+    size.checked_ilog2().unwrap_or(0) as u16
+}
+
 // Enum to represent different versions of the games
-enum Version {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
     MM6,
     MM7,
     MM8,
@@ -160,7 +649,7 @@ impl TryFrom<&str> for Version {
 
 #[cfg(test)]
 mod tests {
-    use crate::{get_lod_path, lod::Lod};
+    use crate::{get_lod_path, lod::Lod, progress::NoProgress, TempDir};
 
     use super::*;
     use std::path::Path;
@@ -173,19 +662,19 @@ mod tests {
         let bitmaps_lod = Lod::open(lod_path.join("BITMAPS.LOD")).unwrap();
 
         let palettes = palette::Palettes::try_from(&bitmaps_lod).unwrap();
-        let _ = bitmaps_lod.save_all(&lod_path.join("bitmaps_lod"), &palettes);
+        let _ = bitmaps_lod.save_all(&lod_path.join("bitmaps_lod"), &palettes, &NoProgress);
 
         let games_lod = Lod::open(lod_path.join("games.lod")).unwrap();
-        let _ = games_lod.save_all(&lod_path.join("games_lod"), &palettes);
+        let _ = games_lod.save_all(&lod_path.join("games_lod"), &palettes, &NoProgress);
 
         let sprites_lod = Lod::open(lod_path.join("SPRITES.LOD")).unwrap();
-        let _ = sprites_lod.save_all(&lod_path.join("sprites_lod"), &palettes);
+        let _ = sprites_lod.save_all(&lod_path.join("sprites_lod"), &palettes, &NoProgress);
 
         let icons_lod = Lod::open(lod_path.join("icons.lod")).unwrap();
-        let _ = icons_lod.save_all(&lod_path.join("icons_lod"), &palettes);
+        let _ = icons_lod.save_all(&lod_path.join("icons_lod"), &palettes, &NoProgress);
 
         let new_lod = Lod::open(lod_path.join("new.lod")).unwrap();
-        let _ = new_lod.save_all(&lod_path.join("new_lod"), &palettes);
+        let _ = new_lod.save_all(&lod_path.join("new_lod"), &palettes, &NoProgress);
     }
 
     #[test]
@@ -208,6 +697,205 @@ mod tests {
         assert_eq!(goblin_image.height(), 289);
     }
 
+    fn lod_with(files: &[(&str, &[u8])]) -> Lod {
+        Lod {
+            version: Version::MM6,
+            files: files
+                .iter()
+                .map(|(name, data)| (name.to_string(), data.to_vec()))
+                .collect(),
+            path: PathBuf::new(),
+            directory: String::new(),
+        }
+    }
+
+    #[test]
+    fn written_lod_can_be_read_back() {
+        let mut palette = Palette {
+            data: [0; crate::palette::PALETTE_SIZE],
+        };
+        palette.data[3..6].copy_from_slice(&[255, 255, 255]);
+
+        let mut writer = LodWriter::new("GameMMVI", "bitmaps").unwrap();
+        writer.add("Raw", b"raw data".to_vec()).unwrap();
+        writer
+            .add_bitmap("checker", 2, 2, &[0, 1, 1, 0], &palette)
+            .unwrap();
+        assert!(writer.add("a_name_too_long_for_lod", vec![]).is_err());
+
+        let dir = TempDir::new("writer");
+        let path = dir.join("bitmaps.lod");
+        writer.write(&path).unwrap();
+        let lod = Lod::open(&path).unwrap();
+
+        assert_eq!(lod.try_get_bytes("raw"), Some(&b"raw data"[..]));
+        assert_eq!(lod.try_get_bytes("RAW"), Some(&b"raw data"[..]));
+        assert!(lod.contains("Checker"));
+        assert!(!lod.contains("missing"));
+        assert_eq!(lod.directory(), "bitmaps");
+        let bitmap = crate::image::Image::try_from(lod.try_get_bytes("checker").unwrap()).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (2, 2));
+        assert_eq!(bitmap.data, vec![0, 1, 1, 0]);
+        assert_eq!(bitmap.palette, palette.data);
+    }
+
+    #[test]
+    fn add_dir_packs_extracted_files() {
+        let dir = TempDir::new("add_dir");
+        let image = image::RgbImage::from_fn(2, 2, |x, _| image::Rgb([x as u8 * 200, 10, 20]));
+        image.save(dir.join("Checker.png")).unwrap();
+        fs::write(dir.join("oute3.odm"), b"map data").unwrap();
+        fs::write(dir.join("dtile.bin"), b"tile table").unwrap();
+        fs::write(dir.join("notes.txt"), b"raw data").unwrap();
+
+        let mut writer = LodWriter::new("GameMMVI", "games").unwrap();
+        writer.add_dir(dir.path()).unwrap();
+        let path = dir.join("games.lod");
+        writer.write(&path).unwrap();
+        let lod = Lod::open(&path).unwrap();
+
+        let bitmap = crate::image::Image::try_from(lod.try_get_bytes("checker").unwrap()).unwrap();
+        assert_eq!(bitmap.data, vec![0, 1, 0, 1]);
+        assert_eq!(&bitmap.palette[..6], &[0, 10, 20, 200, 10, 20]);
+        for (name, data) in [
+            ("oute3.odm", &b"map data"[..]),
+            ("dtile.bin", b"tile table"),
+        ] {
+            let entry = lod.try_get_bytes(name).unwrap();
+            assert_ne!(entry, data);
+            let lod_data = LodData::try_from(entry).unwrap();
+            assert_eq!(lod_data.data, data);
+        }
+        assert_eq!(lod.try_get_bytes("dtile.bin").unwrap()[..9], *b"dtile.bin");
+        assert_eq!(lod.try_get_bytes("notes.txt"), Some(&b"raw data"[..]));
+    }
+
+    fn sprite_palettes() -> palette::Palettes {
+        let palette = |colors: &[[u8; 3]]| {
+            let mut data = vec![0; 48];
+            data.extend(colors.iter().flatten());
+            data.resize(48 + palette::PALETTE_SIZE, 0);
+            data
+        };
+        let gray = palette(&[[0, 0, 0], [128, 128, 128]]);
+        let warm = palette(&[[0, 0, 0], [250, 0, 0], [0, 250, 0]]);
+        palette::Palettes::try_from(&lod_with(&[("pal001", &gray), ("pal002", &warm)])).unwrap()
+    }
+
+    #[test]
+    fn sprites_are_encoded() {
+        let palettes = sprite_palettes();
+        let image = image::RgbaImage::from_fn(3, 3, |x, y| match (x, y) {
+            (1, 0) | (0, 1) => image::Rgba([255, 0, 0, 255]),
+            (2, 1) => image::Rgba([0, 240, 10, 255]),
+            _ => image::Rgba([0, 0, 0, 0]),
+        });
+        let mut writer = LodWriter::new("GameMMVI", "sprites").unwrap();
+        writer
+            .add_sprite("Gobst", &DynamicImage::ImageRgba8(image.clone()), &palettes)
+            .unwrap();
+        let data = &writer.entries["gobst"];
+
+        let sprite = crate::image::Image::try_from((data.as_slice(), &palettes)).unwrap();
+        assert_eq!((sprite.width, sprite.height), (3, 3));
+        // the warm palette is closer, transparent pixels and the gap in line 1 are 0
+        assert_eq!(sprite.data, vec![0, 1, 0, 1, 0, 2, 0, 0, 0]);
+        assert_eq!(sprite.palette, palettes.get(2).unwrap().data);
+        let decoded = sprite.to_image_buffer().unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(0, 0).0[3], 0);
+        assert_eq!(decoded.get_pixel(1, 0).0, [250, 0, 0, 255]);
+        // y_skip counts the empty lines at the bottom
+        assert_eq!(&data[24..26], &1u16.to_le_bytes());
+
+        let dir = TempDir::new("sprites");
+        image.save(dir.join("gobst.png")).unwrap();
+        let as_bitmaps = LodWriter::new("GameMMVI", "sprites")
+            .unwrap()
+            .add_dir(dir.path());
+        assert!(as_bitmaps.is_err());
+        let mut writer = LodWriter::new("GameMMVI", "sprites").unwrap();
+        writer.add_sprite_dir(dir.path(), &palettes).unwrap();
+        assert_eq!(&writer.entries["gobst"], data);
+    }
+
+    #[test]
+    fn offsets_past_2gb_work() {
+        let mut writer = LodWriter::new("GameMMVI", "games").unwrap();
+        writer.add("far", b"x".to_vec()).unwrap();
+        let mut data = Vec::new();
+        writer.write_to(&mut data).unwrap();
+        // move the entry past i32::MAX, the file stays sparse
+        let relative: u32 = 0x8000_0000;
+        let offset_field = FILE_INDEX_OFFSET as usize + FILE_HEADER_SIZE + LOD_NAME_SIZE;
+        data[offset_field..offset_field + 4].copy_from_slice(&relative.to_le_bytes());
+        let absolute = FILE_INDEX_OFFSET + FILE_HEADER_SIZE as u64 + relative as u64;
+
+        let dir = TempDir::new("far");
+        let path = dir.join("games.lod");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&data[..data.len() - 1]).unwrap();
+        file.seek(SeekFrom::Start(absolute)).unwrap();
+        file.write_all(b"y").unwrap();
+        let lod = Lod::open(&path).unwrap();
+        assert_eq!(lod.try_get_bytes("far"), Some(&b"y"[..]));
+        file.set_len(absolute).unwrap();
+        assert!(Lod::open(&path).is_err());
+    }
+
+    #[test]
+    fn corrupt_entry_count_is_an_error() {
+        let mut writer = LodWriter::new("GameMMVI", "games").unwrap();
+        writer.add("one", b"x".to_vec()).unwrap();
+        let mut data = Vec::new();
+        writer.write_to(&mut data).unwrap();
+        let count_field = FILE_INDEX_OFFSET as usize + 28;
+        data[count_field..count_field + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let dir = TempDir::new("count");
+        let path = dir.join("games.lod");
+        fs::write(&path, data).unwrap();
+
+        let error = Lod::open(&path).err().unwrap().to_string();
+        assert!(error.contains("fit in the file"), "{}", error);
+    }
+
+    #[test]
+    fn duplicate_policy_works() {
+        let mut writer = LodWriter::new("GameMMVI", "icons").unwrap();
+        writer.add("aa", b"first".to_vec()).unwrap();
+        writer.add("ab", b"last".to_vec()).unwrap();
+        let mut data = Vec::new();
+        writer.write_to(&mut data).unwrap();
+        // rename the second entry so both have the same name
+        let name_field = FILE_INDEX_OFFSET as usize + 2 * FILE_HEADER_SIZE;
+        data[name_field..name_field + 2].copy_from_slice(b"AA");
+
+        let dir = TempDir::new("dup");
+        let path = dir.join("icons.lod");
+        fs::write(&path, data).unwrap();
+        let last = Lod::open(&path).unwrap();
+        let first = Lod::open_with(&path, DuplicatePolicy::First).unwrap();
+
+        assert_eq!(last.try_get_bytes("aa"), Some(&b"last"[..]));
+        assert_eq!(first.try_get_bytes("aa"), Some(&b"first"[..]));
+        assert!(Lod::open_with(&path, DuplicatePolicy::Error).is_err());
+    }
+
+    #[test]
+    fn diff_works() {
+        let old = lod_with(&[("same", b"abc"), ("removed", b"123"), ("changed", b"old")]);
+        let new = lod_with(&[("same", b"abc"), ("added", b"456"), ("changed", b"new")]);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec![("added".to_string(), crc32(b"456"))]);
+        assert_eq!(diff.removed, vec![("removed".to_string(), crc32(b"123"))]);
+        assert_eq!(
+            diff.changed,
+            vec![("changed".to_string(), crc32(b"old"), crc32(b"new"))]
+        );
+        assert!(old.diff(&old).is_empty());
+    }
+
     #[test]
     fn get_sprite() {
         let lod_path = get_lod_path();
@@ -6,6 +6,8 @@ use std::{
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
+use crate::layout::layout;
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct LodData<'a> {
@@ -30,7 +32,7 @@ impl<'a> TryFrom<&'a [u8]> for LodData<'a> {
     }
 }
 
-fn decompress_with_48_bytes_header(data: &[u8]) -> Result<LodData, Box<dyn Error>> {
+fn decompress_with_48_bytes_header(data: &[u8]) -> Result<LodData<'_>, Box<dyn Error>> {
     let mut cursor = Cursor::new(data);
     cursor.seek(std::io::SeekFrom::Start(20))?;
     let compressed_size = cursor.read_u32::<LittleEndian>()? as usize;
@@ -42,12 +44,24 @@ fn decompress_with_48_bytes_header(data: &[u8]) -> Result<LodData, Box<dyn Error
     })
 }
 
-fn decompress_with_8_bytes_header(data: &[u8]) -> Result<LodData, Box<dyn Error>> {
-    let compressed_size = u32::from_le_bytes(data[0..=3].try_into()?) as usize;
-    let decompressed_size = u32::from_le_bytes(data[4..=7].try_into()?) as usize;
+layout! {
+    /// Header of the compressed game data like maps and tables
+    pub(crate) struct DataHeader {
+        compressed_size: u32,
+        uncompressed_size: u32,
+    }
+}
+
+fn decompress_with_8_bytes_header(data: &[u8]) -> Result<LodData<'_>, Box<dyn Error>> {
+    let header = DataHeader::read(&mut &data[..])?;
     Ok(LodData {
-        header: Some(&data[..8]),
-        data: super::zlib::decompress(&data[8..], compressed_size, decompressed_size)?.to_vec(),
+        header: Some(&data[..DataHeader::SIZE]),
+        data: super::zlib::decompress(
+            &data[DataHeader::SIZE..],
+            header.compressed_size as usize,
+            header.uncompressed_size as usize,
+        )?
+        .to_vec(),
     })
 }
 
@@ -0,0 +1,58 @@
+use std::{collections::BTreeSet, error::Error};
+
+use crate::{billboard::BillboardManager, odm::Odm, LodManager};
+
+/// Bitmaps and sprites a map needs, so they can be decoded before the map is built.
+#[derive(Debug, Default)]
+pub struct MapDependencies {
+    pub bitmaps: BTreeSet<String>,
+    pub sprites: BTreeSet<String>,
+}
+
+impl MapDependencies {
+    pub fn new(lod_manager: &LodManager, odm: &Odm) -> Result<Self, Box<dyn Error>> {
+        let mut dependencies = Self::default();
+        dependencies.bitmaps.insert(odm.sky_texture.to_lowercase());
+        dependencies
+            .bitmaps
+            .extend(odm.tile_table(lod_manager)?.names().iter().cloned());
+        for model in &odm.bsp_models {
+            dependencies.bitmaps.extend(
+                model
+                    .texture_names
+                    .iter()
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.to_lowercase()),
+            );
+        }
+
+        let billboard_manager = BillboardManager::new(lod_manager)?;
+        for billboard in &odm.billboards {
+            if let Some(name) = billboard_manager.sprite_name(
+                lod_manager,
+                &billboard.declist_name,
+                billboard.data.declist_id,
+            ) {
+                dependencies.sprites.insert(name);
+            }
+        }
+        Ok(dependencies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MapDependencies;
+    use crate::{get_lod_path, odm::Odm, LodManager};
+
+    #[test]
+    fn map_dependencies_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let odm = Odm::new(&lod_manager, "oute3.odm").unwrap();
+        let dependencies = MapDependencies::new(&lod_manager, &odm).unwrap();
+        assert!(dependencies
+            .bitmaps
+            .contains(&odm.sky_texture.to_lowercase()));
+        assert!(!dependencies.sprites.is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{
+    ddeclist::DDecList,
+    odm::Odm,
+    progress::{NoProgress, ProgressCounter, ProgressSink},
+    LodManager,
+};
+
+/// Counts of what we were able to parse out of a map, useful to track format coverage.
+#[derive(Debug, Default)]
+pub struct MapStats {
+    pub name: String,
+    pub bsp_models: usize,
+    pub faces: usize,
+    pub vertices: usize,
+    pub billboards: usize,
+    pub lights: usize,
+    pub spawn_points: usize,
+    pub unparsed_size: usize,
+}
+
+impl MapStats {
+    pub fn new(odm: &Odm, d_declist: &DDecList) -> Self {
+        let lights = odm
+            .billboards
+            .iter()
+            .filter(|b| {
+                d_declist
+                    .items
+                    .get(b.data.declist_id as usize)
+                    .is_some_and(|item| item.light_radius > 0)
+            })
+            .count();
+
+        Self {
+            name: odm.name.clone(),
+            bsp_models: odm.bsp_models.len(),
+            faces: odm.bsp_models.iter().map(|m| m.faces.len()).sum(),
+            vertices: odm.bsp_models.iter().map(|m| m.vertices.len()).sum(),
+            billboards: odm.billboards.len(),
+            lights,
+            spawn_points: odm.spawn_points.len(),
+            unparsed_size: odm.unparsed.len(),
+        }
+    }
+}
+
+impl Display for MapStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<12} models:{:>4} faces:{:>6} vertices:{:>6} billboards:{:>4} lights:{:>3} spawns:{:>3} unparsed:{:>7}B",
+            self.name,
+            self.bsp_models,
+            self.faces,
+            self.vertices,
+            self.billboards,
+            self.lights,
+            self.spawn_points,
+            self.unparsed_size
+        )
+    }
+}
+
+/// Coverage report over every map stored in games.lod
+#[derive(Debug, Default)]
+pub struct MapStatsReport {
+    pub maps: Vec<MapStats>,
+    /// Maps that failed to parse along with the reason
+    pub errors: Vec<(String, String)>,
+    /// Maps in a format we don't parse yet
+    pub unsupported: Vec<String>,
+}
+
+impl MapStatsReport {
+    pub fn new(lod_manager: &LodManager) -> Result<Self, Box<dyn Error>> {
+        Self::with_progress(lod_manager, &NoProgress)
+    }
+
+    /// Same as `new`, reporting each map checked
+    pub fn with_progress(
+        lod_manager: &LodManager,
+        progress: &dyn ProgressSink,
+    ) -> Result<Self, Box<dyn Error>> {
+        let d_declist = DDecList::new(lod_manager)?;
+        let mut files = lod_manager
+            .files("games")
+            .ok_or("expected to have games.lod")?;
+        files.sort();
+
+        let progress = ProgressCounter::new(progress, files.len());
+        let mut report = Self::default();
+        for file in files {
+            if file.ends_with(".odm") {
+                match Odm::new(lod_manager, file) {
+                    Ok(odm) => report.maps.push(MapStats::new(&odm, &d_declist)),
+                    Err(e) => report.errors.push((file.to_string(), e.to_string())),
+                }
+            } else if file.ends_with(".blv") {
+                report.unsupported.push(file.to_string());
+            }
+            if !progress.step() {
+                break;
+            }
+        }
+        progress.result()?;
+        Ok(report)
+    }
+}
+
+impl Display for MapStatsReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for map in &self.maps {
+            writeln!(f, "{}", map)?;
+        }
+        for (name, error) in &self.errors {
+            writeln!(f, "{:<12} error: {}", name, error)?;
+        }
+        writeln!(
+            f,
+            "parsed: {}, failed: {}, unsupported: {} ({})",
+            self.maps.len(),
+            self.errors.len(),
+            self.unsupported.len(),
+            self.unsupported.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MapStatsReport;
+    use crate::{get_lod_path, LodManager};
+
+    #[test]
+    fn map_stats_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let report = MapStatsReport::new(&lod_manager).unwrap();
+        assert!(report.errors.is_empty());
+        let oute3 = report.maps.iter().find(|m| m.name == "oute3.odm").unwrap();
+        assert_eq!(oute3.bsp_models, 85);
+    }
+}
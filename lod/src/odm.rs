@@ -3,14 +3,15 @@ use std::{
     io::{Cursor, Read, Seek},
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::{
-    billboard::{read_billboards, Billboard},
-    bsp_model::{read_bsp_models, BSPModel},
+    billboard::{read_billboards, write_billboards, Billboard},
+    bsp_model::{read_bsp_models, write_bsp_models, BSPModel},
     dtile::{Dtile, TileTable},
+    layout::layout,
     lod_data::LodData,
-    utils::try_read_string_block,
+    utils::{read_string_block, string_block},
     LodManager,
 };
 
@@ -21,7 +22,19 @@ pub const ODM_AREA: usize = ODM_SIZE * ODM_SIZE;
 pub const ODM_TILE_SCALE: f32 = 512.;
 pub const ODM_HEIGHT_SCALE: f32 = 32.;
 
-const HEIGHT_MAP_OFFSET: u64 = 176;
+layout! {
+    /// Fixed part of an outdoor map before the terrain maps
+    pub(crate) struct OdmHeader {
+        unknown: [u8; 64],
+        version: [u8; 32],
+        sky_texture: [u8; 32],
+        ground_texture: [u8; 32],
+        /// Tile sets of the four terrain groups with their first tile
+        tile_data: [u16; 8],
+    }
+}
+
+const HEIGHT_MAP_OFFSET: u64 = OdmHeader::SIZE as u64;
 const HEIGHT_MAP_SIZE: usize = ODM_AREA;
 
 const TILE_MAP_OFFSET: u64 = HEIGHT_MAP_OFFSET + HEIGHT_MAP_SIZE as u64;
@@ -30,41 +43,135 @@ const TILEMAP_SIZE: usize = ODM_AREA;
 const ATTRIBUTE_MAP_OFFSET: u64 = TILE_MAP_OFFSET + ATTRIBUTE_MAP_SIZE as u64;
 const ATTRIBUTE_MAP_SIZE: usize = ODM_AREA;
 
+/// Offsets into the face id list for each terrain cell
+const CELL_MAP_SIZE: usize = ODM_AREA * 4;
+
+/// Where monsters and items are generated when the map is first visited
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SpawnPoint {
+    pub position: [i32; 3],
+    pub radius: u16,
+    /// 2 for monsters, 3 for items
+    pub kind: u16,
+    /// Monster or treasure level
+    pub index: u16,
+    pub attributes: u16,
+}
+
+/// Face ids used by the terrain cells with their count, then the per cell offsets into them
+fn read_cell_data(cursor: &mut Cursor<&[u8]>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let face_id_count = cursor.read_u32::<LittleEndian>()?;
+    let size = face_id_count as usize * 2 + CELL_MAP_SIZE;
+    if cursor.position() as usize + size > cursor.get_ref().len() {
+        return Err("Cell data is truncated".into());
+    }
+    let mut cell_data = face_id_count.to_le_bytes().to_vec();
+    cell_data.resize(4 + size, 0);
+    cursor.read_exact(&mut cell_data[4..])?;
+    Ok(cell_data)
+}
+
+fn read_spawn_points(cursor: &mut Cursor<&[u8]>) -> Result<Vec<SpawnPoint>, Box<dyn Error>> {
+    let count = cursor.read_u32::<LittleEndian>()? as usize;
+    let mut spawn_points = Vec::with_capacity(count.min(ODM_AREA));
+    for _ in 0..count {
+        spawn_points.push(SpawnPoint {
+            position: [
+                cursor.read_i32::<LittleEndian>()?,
+                cursor.read_i32::<LittleEndian>()?,
+                cursor.read_i32::<LittleEndian>()?,
+            ],
+            radius: cursor.read_u16::<LittleEndian>()?,
+            kind: cursor.read_u16::<LittleEndian>()?,
+            index: cursor.read_u16::<LittleEndian>()?,
+            attributes: cursor.read_u16::<LittleEndian>()?,
+        });
+    }
+    Ok(spawn_points)
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Odm {
     pub name: String,
+    /// Bytes before the version, not decoded
+    pub unknown_header: [u8; 64],
     pub odm_version: String,
     pub sky_texture: String,
     pub ground_texture: String,
+    /// Version, sky and ground texture as stored, with the bytes after the terminator
+    pub name_blocks: [[u8; 32]; 3],
     pub tile_data: [u16; 8],
     pub height_map: [u8; HEIGHT_MAP_SIZE],
     pub tile_map: [u8; TILEMAP_SIZE],
     pub attribute_map: [u8; ATTRIBUTE_MAP_SIZE],
     pub bsp_models: Vec<BSPModel>,
     pub billboards: Vec<Billboard>,
+    /// Terrain cell face lists as stored in the file, see `read_cell_data`
+    pub cell_data: Vec<u8>,
+    pub spawn_points: Vec<SpawnPoint>,
+    /// Bytes left after the last section we know how to parse
+    pub unparsed: Vec<u8>,
 }
 
 impl Odm {
     pub fn new(lod_manager: &LodManager, name: &str) -> Result<Self, Box<dyn Error>> {
-        let data = LodData::try_from(lod_manager.try_get_bytes(&format!("games/{}", name))?)?;
-        let data = data.data.as_slice();
+        let data = LodData::try_from(lod_manager.try_get_bytes(format!("games/{}", name))?)?;
+        let mut map = Self::try_from(data.data.as_slice())?;
+        map.name = name.into();
+        Ok(map)
+    }
+
+    /// The map as stored in games.lod before compression
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut data = Vec::new();
+        let [version, sky_texture, ground_texture] = &self.name_blocks;
+        OdmHeader {
+            unknown: self.unknown_header,
+            version: string_block(&self.odm_version, version)?,
+            sky_texture: string_block(&self.sky_texture, sky_texture)?,
+            ground_texture: string_block(&self.ground_texture, ground_texture)?,
+            tile_data: self.tile_data,
+        }
+        .write(&mut data)?;
+        data.extend_from_slice(&self.height_map);
+        data.extend_from_slice(&self.tile_map);
+        data.extend_from_slice(&self.attribute_map);
+
+        data.write_u32::<LittleEndian>(self.bsp_models.len() as u32)?;
+        write_bsp_models(&mut data, &self.bsp_models)?;
+        data.write_u32::<LittleEndian>(self.billboards.len() as u32)?;
+        write_billboards(&mut data, &self.billboards)?;
 
+        // maps whose spawn points couldn't be read keep them in `unparsed`
+        if !self.cell_data.is_empty() {
+            data.extend_from_slice(&self.cell_data);
+            data.write_u32::<LittleEndian>(self.spawn_points.len() as u32)?;
+            for spawn_point in &self.spawn_points {
+                for value in spawn_point.position {
+                    data.write_i32::<LittleEndian>(value)?;
+                }
+                for value in [
+                    spawn_point.radius,
+                    spawn_point.kind,
+                    spawn_point.index,
+                    spawn_point.attributes,
+                ] {
+                    data.write_u16::<LittleEndian>(value)?;
+                }
+            }
+        }
+        data.extend_from_slice(&self.unparsed);
+        Ok(data)
+    }
+}
+
+impl TryFrom<&[u8]> for Odm {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
         let mut cursor = Cursor::new(data);
-        cursor.seek(std::io::SeekFrom::Start(2 * 32))?;
-        let odm_version = try_read_string_block(&mut cursor, 32)?;
-        let sky_texture = try_read_string_block(&mut cursor, 32)?;
-        let ground_texture = try_read_string_block(&mut cursor, 32)?;
-        let tile_data: [u16; 8] = [
-            cursor.read_u16::<LittleEndian>()?,
-            cursor.read_u16::<LittleEndian>()?,
-            cursor.read_u16::<LittleEndian>()?,
-            cursor.read_u16::<LittleEndian>()?,
-            cursor.read_u16::<LittleEndian>()?,
-            cursor.read_u16::<LittleEndian>()?,
-            cursor.read_u16::<LittleEndian>()?,
-            cursor.read_u16::<LittleEndian>()?,
-        ];
+        let header = OdmHeader::read(&mut cursor)?;
 
         cursor.seek(std::io::SeekFrom::Start(HEIGHT_MAP_OFFSET))?;
         let mut height_map: [u8; HEIGHT_MAP_SIZE] = [0; HEIGHT_MAP_SIZE];
@@ -84,17 +191,34 @@ impl Odm {
         let billboard_count = cursor.read_u32::<LittleEndian>()? as usize;
         let billboards: Vec<Billboard> = read_billboards(&mut cursor, billboard_count)?;
 
+        // a map we can't read the spawn points of is still usable
+        let billboards_end = cursor.position();
+        let (cell_data, spawn_points) = read_cell_data(&mut cursor)
+            .and_then(|cell_data| Ok((cell_data, read_spawn_points(&mut cursor)?)))
+            .unwrap_or_else(|_| {
+                cursor.set_position(billboards_end);
+                (Vec::new(), Vec::new())
+            });
+
+        let mut unparsed = Vec::new();
+        cursor.read_to_end(&mut unparsed)?;
+
         Ok(Self {
-            name: "test".into(),
-            odm_version,
-            sky_texture,
-            ground_texture,
-            tile_data,
+            name: String::new(),
+            unknown_header: header.unknown,
+            odm_version: read_string_block(&header.version)?,
+            sky_texture: read_string_block(&header.sky_texture)?,
+            ground_texture: read_string_block(&header.ground_texture)?,
+            name_blocks: [header.version, header.sky_texture, header.ground_texture],
+            tile_data: header.tile_data,
             height_map,
             tile_map,
             attribute_map,
             bsp_models,
             billboards,
+            cell_data,
+            spawn_points,
+            unparsed,
         })
     }
 }
@@ -111,79 +235,6 @@ impl Odm {
     }
 }
 
-pub struct OdmData {
-    pub positions: Vec<[f32; 3]>,
-    pub indices: Vec<u32>,
-    pub uvs: Vec<[f32; 2]>,
-}
-
-impl OdmData {
-    pub fn new(odm: &Odm, tile_table: &TileTable) -> Self {
-        let (width, depth) = odm.size();
-        let width_u32 = width as u32;
-        let (width_half, depth_half) = (width as f32 / 2., depth as f32 / 2.);
-
-        let vertices_count: usize = width * depth;
-        let mut positions: Vec<[f32; 3]> = Vec::with_capacity(vertices_count);
-        let indices_count: usize = (width - 1) * (depth - 1) * 6;
-        let mut indices: Vec<u32> = Vec::with_capacity(indices_count);
-        let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(indices_count); // vertices will be duplicated so we have as much as the indices
-
-        for d in 0..depth {
-            for w in 0..width {
-                let i = d * width + w;
-                positions.push([
-                    (w as f32 - width_half) * ODM_TILE_SCALE,
-                    odm.height_map[i] as f32 * ODM_HEIGHT_SCALE,
-                    (d as f32 - depth_half) * ODM_TILE_SCALE,
-                ]);
-                if w < (depth - 1) && d < (depth - 1) {
-                    Self::push_uvs(&mut uvs, tile_table, odm.tile_map[i]);
-                    Self::push_triangle_indices(&mut indices, i as u32, width_u32);
-                }
-            }
-        }
-
-        Self {
-            positions,
-            indices,
-            uvs,
-        }
-    }
-
-    fn push_uvs(uvs: &mut Vec<[f32; 2]>, tile_table: &TileTable, tile_index: u8) {
-        let (tile_x, tile_y) = tile_table.coordinate(tile_index);
-        let (tile_x, tile_y) = (tile_x as f32, tile_y as f32);
-        let (tile_table_size_x, tile_table_size_y) = tile_table.size();
-        let (tile_table_size_x, tile_table_size_y) =
-            (tile_table_size_x as f32, tile_table_size_y as f32);
-
-        let uv_scale: f32 = 1.0;
-        let w_start = (tile_x / tile_table_size_x) / uv_scale;
-        let w_end = ((tile_x + 1.0) / tile_table_size_x) / uv_scale;
-        let h_start = (tile_y / tile_table_size_y) / uv_scale;
-        let h_end = ((tile_y + 1.0) / tile_table_size_y) / uv_scale;
-
-        uvs.push([w_start, h_start]);
-        uvs.push([w_start, h_end]);
-        uvs.push([w_end, h_start]);
-        uvs.push([w_end, h_start]);
-        uvs.push([w_start, h_end]);
-        uvs.push([w_end, h_end]);
-    }
-
-    fn push_triangle_indices(indices: &mut Vec<u32>, i: u32, width_u32: u32) {
-        // First triangle
-        indices.push(i);
-        indices.push(i + (width_u32));
-        indices.push(i + 1);
-        // Second triangle
-        indices.push(i + 1);
-        indices.push(i + width_u32);
-        indices.push(i + width_u32 + 1);
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -195,4 +246,118 @@ mod tests {
         let map = Odm::new(&lod_manager, "oute3.odm").unwrap();
         assert_eq!(map.bsp_models.len(), 85)
     }
+
+    #[test]
+    fn cell_data_and_spawn_points_work() {
+        let face_ids = [1u16, 2, 3];
+        let mut data = Vec::new();
+        data.extend_from_slice(&(face_ids.len() as u32).to_le_bytes());
+        face_ids
+            .iter()
+            .for_each(|id| data.extend_from_slice(&id.to_le_bytes()));
+        data.resize(data.len() + CELL_MAP_SIZE, 0);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        for value in [100i32, -200, 300] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [64u16, 2, 1, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut cursor = Cursor::new(data.as_slice());
+        let cell_data = read_cell_data(&mut cursor).unwrap();
+        assert_eq!(cell_data, &data[..4 + 6 + CELL_MAP_SIZE]);
+        let spawn_points = read_spawn_points(&mut cursor).unwrap();
+        assert_eq!(
+            spawn_points,
+            vec![SpawnPoint {
+                position: [100, -200, 300],
+                radius: 64,
+                kind: 2,
+                index: 1,
+                attributes: 0
+            }]
+        );
+        assert_eq!(cursor.position() as usize, data.len());
+    }
+
+    /// Name block holding `name` and some leftover bytes after the terminator
+    fn name_block(name: &str, size: usize) -> Vec<u8> {
+        let mut block = name.as_bytes().to_vec();
+        block.push(0);
+        block.resize(size, 0xcd);
+        block
+    }
+
+    #[test]
+    fn round_trip_works() {
+        let mut data = vec![7; 64];
+        for name in ["MM6 Outdoor v1.11", "sky01", "dirttyl"] {
+            data.extend(name_block(name, 32));
+        }
+        data.extend((0..8).flat_map(|i: u16| i.to_le_bytes()));
+        data.extend((0..3 * ODM_AREA).map(|i| i as u8));
+
+        // a model with a triangle
+        data.extend(1u32.to_le_bytes());
+        data.extend(name_block("crate", 32));
+        data.extend(name_block("", 32));
+        for value in [1i32, 3, 0, 1, 0, 0, 0, 1] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend([2, 0, 0, 0, 3, 0, 0, 0]);
+        for value in (0..2 + 3 + 12 + 3 + 1).map(|i: i32| i * 10 - 50) {
+            data.extend(value.to_le_bytes());
+        }
+        for value in [0i32, 0, 0, 512, 0, 0, 0, -512, 256] {
+            data.extend(value.to_le_bytes());
+        }
+        let mut face = vec![0; std::mem::size_of::<crate::bsp_model::BSPModelFace>()];
+        face[32..38].copy_from_slice(&[0, 0, 1, 0, 2, 0]);
+        face[302] = 3;
+        data.extend(face);
+        data.extend([4, 5]);
+        data.extend(name_block("cratetyl", 10));
+        for _ in 0..2 {
+            data.extend([1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0, 0, 1, 0]);
+        }
+
+        // a billboard
+        data.extend(1u32.to_le_bytes());
+        data.extend((0..28).map(|i| i as u8));
+        data.extend(name_block("Tree01", 32));
+
+        data.extend(1u32.to_le_bytes());
+        data.extend(9u16.to_le_bytes());
+        data.resize(data.len() + CELL_MAP_SIZE, 1);
+        data.extend(1u32.to_le_bytes());
+        data.extend((0..20).map(|i| i as u8));
+        data.extend(b"tail");
+
+        let map = Odm::try_from(data.as_slice()).unwrap();
+        assert_eq!(map.sky_texture, "sky01");
+        let model = &map.bsp_models[0];
+        assert_eq!(
+            (model.header.name.as_str(), model.header.grid),
+            ("crate", [-50, -40])
+        );
+        assert_eq!(model.header.position, [-30, -20, -10]);
+        assert_eq!(model.vertices[1], [512., 0., 0.]);
+        assert_eq!(
+            (model.texture_names[0].as_str(), model.bsp_nodes.len()),
+            ("cratetyl", 2)
+        );
+        assert_eq!(map.billboards[0].declist_name, "tree01");
+        assert_eq!(map.spawn_points.len(), 1);
+        assert_eq!(map.unparsed, b"tail");
+        assert_eq!(map.to_bytes().unwrap(), data);
+
+        let mut renamed = map;
+        renamed.sky_texture = "sky02".into();
+        let renamed = Odm::try_from(renamed.to_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(renamed.sky_texture, "sky02");
+        let mut sky02 = [0; 32];
+        sky02[..5].copy_from_slice(b"sky02");
+        assert_eq!(renamed.name_blocks[1], sky02);
+    }
 }
@@ -1,16 +1,28 @@
 use std::collections::HashMap;
 use std::error::Error;
+use std::fmt::Write;
+use std::fs::write;
+use std::path::Path;
+
+use image::Rgb;
 
 use super::Lod;
 
 const PALETTE_HEADER_SIZE: usize = 48;
-const PALETTE_SIZE: usize = 768;
+pub(crate) const PALETTE_SIZE: usize = 768;
 const PALETTE_DATA_SIZE: usize = PALETTE_SIZE + PALETTE_HEADER_SIZE;
 
 #[allow(dead_code)]
 #[derive(Debug, Eq, PartialEq, Hash)]
 pub struct Palette {
-    pub data: [u8; PALETTE_SIZE],
+    pub(crate) data: [u8; PALETTE_SIZE],
+}
+
+/// 256 RGB colors
+impl From<[u8; PALETTE_SIZE]> for Palette {
+    fn from(data: [u8; PALETTE_SIZE]) -> Self {
+        Self { data }
+    }
 }
 
 impl TryFrom<&[u8]> for Palette {
@@ -27,7 +39,167 @@ impl TryFrom<&[u8]> for Palette {
     }
 }
 
-#[allow(dead_code)]
+impl Palette {
+    pub fn data(&self) -> &[u8; PALETTE_SIZE] {
+        &self.data
+    }
+
+    pub fn colors(&self) -> Vec<Rgb<u8>> {
+        self.data
+            .chunks_exact(3)
+            .map(|c| Rgb([c[0], c[1], c[2]]))
+            .collect()
+    }
+
+    /// JASC-PAL text, the format of Paint Shop Pro and most palette editors
+    pub fn to_jasc_pal(&self) -> String {
+        let mut pal = String::from("JASC-PAL\r\n0100\r\n256\r\n");
+        for c in self.data.chunks_exact(3) {
+            let _ = write!(pal, "{} {} {}\r\n", c[0], c[1], c[2]);
+        }
+        pal
+    }
+
+    /// Adobe color table, the raw RGB triplets
+    pub fn to_act(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    /// Saves as JASC-PAL or Adobe color table depending on the `.pal` or `.act` extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("pal") => write(path, self.to_jasc_pal())?,
+            Some("act") => write(path, self.to_act())?,
+            _ => return Err(format!("Unknown palette format for {}", path.display()).into()),
+        }
+        Ok(())
+    }
+
+    pub fn color(&self, index: u8) -> [u8; 3] {
+        let i = index as usize * 3;
+        [self.data[i], self.data[i + 1], self.data[i + 2]]
+    }
+
+    /// Index of the palette color closest to `rgb`, see `search` for many lookups.
+    pub fn nearest(&self, rgb: [u8; 3]) -> u8 {
+        self.search().nearest(rgb)
+    }
+
+    /// Nearest color search over this palette, built once for many lookups
+    pub fn search(&self) -> NearestColor {
+        self.search_from(0)
+    }
+
+    /// Search over the colors a sprite pixel can use, index 0 being transparent
+    pub(crate) fn opaque_search(&self) -> NearestColor {
+        self.search_from(1)
+    }
+
+    fn search_from(&self, first: u8) -> NearestColor {
+        let mut by_green: Vec<([u8; 3], u8)> =
+            (first..=255u8).map(|i| (self.color(i), i)).collect();
+        by_green.sort_by_key(|(color, index)| (color[1], *index));
+        NearestColor { by_green }
+    }
+
+    /// Maps rgb pixels to palette indices, optionally spreading the error with
+    /// Floyd-Steinberg dithering to hide banding.
+    pub fn quantize(&self, rgb: &[u8], width: usize, dither: bool) -> Vec<u8> {
+        let pixels = rgb.len() / 3;
+        let search = self.search();
+        if !dither {
+            // images reuse few colors, so each one is searched once
+            let mut found = HashMap::new();
+            return rgb
+                .chunks_exact(3)
+                .map(|c| {
+                    *found
+                        .entry([c[0], c[1], c[2]])
+                        .or_insert_with(|| search.nearest([c[0], c[1], c[2]]))
+                })
+                .collect();
+        }
+
+        let mut error = vec![[0i32; 3]; pixels];
+        let mut indices = Vec::with_capacity(pixels);
+        for (i, c) in rgb.chunks_exact(3).enumerate() {
+            let wanted: [i32; 3] =
+                std::array::from_fn(|channel| c[channel] as i32 + error[i][channel] / 16);
+            let index = search.nearest(wanted.map(|v| v.clamp(0, 255) as u8));
+            indices.push(index);
+
+            let got = self.color(index);
+            let (x, y) = (i % width, i / width);
+            for channel in 0..3 {
+                let e = wanted[channel] - got[channel] as i32;
+                let mut spread = |dx: isize, dy: usize, weight: i32| {
+                    let nx = x as isize + dx;
+                    if nx >= 0 && (nx as usize) < width {
+                        let j = (y + dy) * width + nx as usize;
+                        if j < pixels {
+                            error[j][channel] += e * weight;
+                        }
+                    }
+                };
+                spread(1, 0, 7);
+                spread(-1, 1, 3);
+                spread(0, 1, 5);
+                spread(1, 1, 1);
+            }
+        }
+        indices
+    }
+}
+
+/// Palette colors sorted by green, the channel weighing most in `color_distance`. A search
+/// starts from the colors with the closest green and stops once the green difference alone is
+/// farther than the best match, which skips most of the palette.
+pub struct NearestColor {
+    by_green: Vec<([u8; 3], u8)>,
+}
+
+impl NearestColor {
+    /// Index of the color closest to `rgb`, the lowest index on ties like a linear scan
+    pub fn nearest(&self, rgb: [u8; 3]) -> u8 {
+        let start = self
+            .by_green
+            .partition_point(|(color, _)| color[1] < rgb[1]);
+        let mut best = (u32::MAX, u8::MAX);
+        let mut consider = |(color, index): &([u8; 3], u8)| {
+            let dg = color[1] as i32 - rgb[1] as i32;
+            if (4 * dg * dg) as u32 > best.0 {
+                return false;
+            }
+            best = best.min((color_distance(rgb, *color), *index));
+            true
+        };
+        let (below, above) = self.by_green.split_at(start);
+        let mut below = below.iter().rev();
+        let mut above = above.iter();
+        let (mut down, mut up) = (true, true);
+        while down || up {
+            if up {
+                up = above.next().is_some_and(&mut consider);
+            }
+            if down {
+                down = below.next().is_some_and(&mut consider);
+            }
+        }
+        best.1
+    }
+}
+
+/// Squared distance weighted by how sensitive the eye is to each channel.
+pub(crate) fn color_distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    let dr = a[0] as i32 - b[0] as i32;
+    let dg = a[1] as i32 - b[1] as i32;
+    let db = a[2] as i32 - b[2] as i32;
+    (2 * dr * dr + 4 * dg * dg + 3 * db * db) as u32
+}
+
+/// Palettes of the sprites, stored in bitmaps.lod as pal000 to pal999
 #[derive(Debug)]
 pub struct Palettes {
     palettes: HashMap<u16, Palette>,
@@ -63,6 +235,26 @@ impl Palettes {
     pub fn get(&self, id: u16) -> Option<&Palette> {
         self.palettes.get(&id)
     }
+
+    /// Lookup by entry name, e.g. "pal001"
+    pub fn get_by_name(&self, name: &str) -> Option<&Palette> {
+        self.get(extract_palette_id(name).ok()?)
+    }
+
+    /// Palettes ordered by id
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Palette)> {
+        let mut ids: Vec<u16> = self.palettes.keys().copied().collect();
+        ids.sort();
+        ids.into_iter().map(|id| (id, &self.palettes[&id]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.palettes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.palettes.is_empty()
+    }
 }
 
 fn extract_palette_id(s: &str) -> Result<u16, Box<dyn Error>> {
@@ -75,3 +267,79 @@ fn extract_palette_id(s: &str) -> Result<u16, Box<dyn Error>> {
         _ => Err("Invalid u16 value".into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use image::Rgb;
+
+    use super::{color_distance, Palette, Palettes, PALETTE_SIZE};
+
+    fn gray_palette() -> Palette {
+        let mut data = [0; PALETTE_SIZE];
+        for (i, c) in data.chunks_exact_mut(3).enumerate() {
+            c.fill(i as u8);
+        }
+        Palette { data }
+    }
+
+    #[test]
+    fn export_works() {
+        let palette = gray_palette();
+        assert_eq!(palette.colors()[7], Rgb([7, 7, 7]));
+        assert_eq!(palette.to_act(), palette.data.to_vec());
+        let pal = palette.to_jasc_pal();
+        assert!(pal.starts_with("JASC-PAL\r\n0100\r\n256\r\n0 0 0\r\n1 1 1\r\n"));
+        assert_eq!(pal.lines().count(), 3 + 256);
+        assert!(palette.save("palette.bmp").is_err());
+    }
+
+    #[test]
+    fn palettes_lookup_works() {
+        let palettes = Palettes {
+            palettes: HashMap::from([(12, gray_palette()), (3, gray_palette())]),
+        };
+        assert_eq!(palettes.len(), 2);
+        assert!(palettes.get_by_name("pal012").is_some());
+        assert!(palettes.get_by_name("pal004").is_none());
+        let ids: Vec<u16> = palettes.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![3, 12]);
+    }
+
+    #[test]
+    fn nearest_works() {
+        let palette = gray_palette();
+        assert_eq!(palette.nearest([10, 10, 10]), 10);
+        assert_eq!(palette.nearest([10, 12, 14]), 12);
+
+        // same result as a linear scan, on a palette with repeated colors
+        let mut data = [0; PALETTE_SIZE];
+        for (i, c) in data.iter_mut().enumerate() {
+            *c = (i * 37 % 251) as u8 & 0xf0;
+        }
+        let palette = Palette { data };
+        let search = palette.search();
+        for rgb in
+            (0..4096u32).map(|i| [i % 16 * 17, i / 16 % 16 * 17, i / 256 * 17].map(|c| c as u8))
+        {
+            let linear = (0..=255u8)
+                .min_by_key(|&i| (color_distance(rgb, palette.color(i)), i))
+                .unwrap();
+            assert_eq!(search.nearest(rgb), linear);
+        }
+    }
+
+    #[test]
+    fn dithering_keeps_the_average() {
+        let mut data = [0; PALETTE_SIZE];
+        data[3..6].fill(255);
+        let palette = Palette { data };
+
+        let rgb = [128; 3 * 16];
+        assert!(palette.quantize(&rgb, 4, false).iter().all(|&i| i == 1));
+        let dithered = palette.quantize(&rgb, 4, true);
+        let white = dithered.iter().filter(|&&i| i == 1).count();
+        assert!((6..=10).contains(&white));
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    error::Error,
+    io::{Cursor, Seek, SeekFrom},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use image::{DynamicImage, RgbImage};
+
+use crate::{image::Image, palette::PALETTE_SIZE};
+
+const HEADER_SIZE: usize = 128;
+const MANUFACTURER: u8 = 0x0a;
+const RLE_ENCODING: u8 = 1;
+/// Marks the 256 color palette ending the file
+const PALETTE_MARKER: u8 = 0x0c;
+
+/// PCX picture, the format of the fullscreen images like the title and the loading screens.
+/// Supports the 8 bit paletted and the 24 bit planar variants.
+#[derive(Debug)]
+pub struct Pcx {
+    pub width: usize,
+    pub height: usize,
+    /// RGB pixels, row by row
+    pub data: Vec<u8>,
+    /// Palette indices and palette of the 8 bit variant
+    indexed: Option<(Vec<u8>, [u8; PALETTE_SIZE])>,
+}
+
+impl TryFrom<&[u8]> for Pcx {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() < HEADER_SIZE || data[0] != MANUFACTURER {
+            return Err("Not a pcx image".into());
+        }
+        let mut cursor = Cursor::new(data);
+        cursor.seek(SeekFrom::Start(2))?;
+        let encoding = cursor.read_u8()?;
+        let bits_per_pixel = cursor.read_u8()?;
+        let x_min = cursor.read_u16::<LittleEndian>()? as usize;
+        let y_min = cursor.read_u16::<LittleEndian>()? as usize;
+        let x_max = cursor.read_u16::<LittleEndian>()? as usize;
+        let y_max = cursor.read_u16::<LittleEndian>()? as usize;
+        cursor.seek(SeekFrom::Start(65))?;
+        let planes = cursor.read_u8()? as usize;
+        let bytes_per_line = cursor.read_u16::<LittleEndian>()? as usize;
+
+        if x_max < x_min || y_max < y_min {
+            return Err("Invalid pcx dimensions".into());
+        }
+        let width = x_max - x_min + 1;
+        let height = y_max - y_min + 1;
+        if bits_per_pixel != 8 || !(planes == 1 || planes == 3) || bytes_per_line < width {
+            return Err(format!(
+                "Unsupported pcx with {} planes of {} bits",
+                planes, bits_per_pixel
+            )
+            .into());
+        }
+
+        let line_size = bytes_per_line * planes;
+        let (pixels, end) = decode_pixels(
+            &data[HEADER_SIZE..],
+            line_size * height,
+            encoding == RLE_ENCODING,
+        )?;
+
+        let mut rgb = Vec::with_capacity(width * height * 3);
+        let mut indexed = None;
+        if planes == 3 {
+            for line in pixels.chunks_exact(line_size) {
+                for x in 0..width {
+                    for plane in 0..3 {
+                        rgb.push(line[plane * bytes_per_line + x]);
+                    }
+                }
+            }
+        } else {
+            // the palette is the last bytes of the file, padding can come before it
+            let marker = data.len().saturating_sub(PALETTE_SIZE + 1);
+            if marker < HEADER_SIZE + end || data[marker] != PALETTE_MARKER {
+                return Err("Missing pcx palette".into());
+            }
+            let palette = &data[marker + 1..];
+            let mut indices = Vec::with_capacity(width * height);
+            for line in pixels.chunks_exact(line_size) {
+                for &index in &line[..width] {
+                    let i = index as usize * 3;
+                    rgb.extend_from_slice(&palette[i..i + 3]);
+                }
+                indices.extend_from_slice(&line[..width]);
+            }
+            indexed = Some((indices, palette.try_into()?));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            data: rgb,
+            indexed,
+        })
+    }
+}
+
+/// Expands `size` bytes of pixels, returning them with the number of bytes consumed.
+fn decode_pixels(data: &[u8], size: usize, rle: bool) -> Result<(Vec<u8>, usize), Box<dyn Error>> {
+    if !rle {
+        let pixels = data.get(..size).ok_or("Truncated pcx pixels")?;
+        return Ok((pixels.to_vec(), size));
+    }
+    let mut pixels = Vec::with_capacity(size);
+    let mut i = 0;
+    while pixels.len() < size {
+        let byte = *data.get(i).ok_or("Truncated pcx pixels")?;
+        i += 1;
+        if byte & 0xc0 == 0xc0 {
+            let value = *data.get(i).ok_or("Truncated pcx pixels")?;
+            i += 1;
+            let count = ((byte & 0x3f) as usize).min(size - pixels.len());
+            pixels.resize(pixels.len() + count, value);
+        } else {
+            pixels.push(byte);
+        }
+    }
+    Ok((pixels, i))
+}
+
+impl Pcx {
+    pub fn to_image_buffer(&self) -> Result<DynamicImage, Box<dyn Error>> {
+        let image = RgbImage::from_raw(self.width as u32, self.height as u32, self.data.clone())
+            .ok_or("Unable to create the pcx image buffer")?;
+        Ok(DynamicImage::ImageRgb8(image))
+    }
+
+    /// The 8 bit variant as palette indices, to cache it like the bitmaps
+    pub(crate) fn to_indexed_image(&self) -> Option<Image> {
+        let (data, palette) = self.indexed.clone()?;
+        Some(Image {
+            height: self.height,
+            width: self.width,
+            data,
+            palette,
+            transparency: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pcx, HEADER_SIZE, PALETTE_MARKER};
+
+    fn header(width: u16, height: u16, planes: u8) -> Vec<u8> {
+        let mut data = vec![0; HEADER_SIZE];
+        data[..4].copy_from_slice(&[0x0a, 5, 1, 8]);
+        data[8..10].copy_from_slice(&(width - 1).to_le_bytes());
+        data[10..12].copy_from_slice(&(height - 1).to_le_bytes());
+        data[65] = planes;
+        // lines are padded to an even size
+        data[66..68].copy_from_slice(&(width + width % 2).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn paletted_pcx_works() {
+        let mut data = header(3, 2, 1);
+        // a run of three 1, padding, then 2 0 0 as literals and padding
+        data.extend([0xc3, 1, 0, 2, 0, 0, 0]);
+        // trailing bytes looking like the marker
+        data.extend([PALETTE_MARKER, 0]);
+        data.push(PALETTE_MARKER);
+        let mut palette = [0; 768];
+        palette[3..9].copy_from_slice(&[10, 20, 30, 40, 50, 60]);
+        data.extend(palette);
+
+        let pcx = Pcx::try_from(data.as_slice()).unwrap();
+        assert_eq!((pcx.width, pcx.height), (3, 2));
+        assert_eq!(&pcx.data[..9], &[10, 20, 30, 10, 20, 30, 10, 20, 30]);
+        assert_eq!(&pcx.data[9..], &[40, 50, 60, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(pcx.to_indexed_image().unwrap().data, vec![1, 1, 1, 2, 0, 0]);
+        assert!(Pcx::try_from(&data[..HEADER_SIZE + 7]).is_err());
+    }
+
+    #[test]
+    fn planar_pcx_works() {
+        let mut data = header(2, 1, 3);
+        data.extend([0xc2, 255, 0, 0, 1, 2]);
+
+        let pcx = Pcx::try_from(data.as_slice()).unwrap();
+        assert_eq!(pcx.data, vec![255, 0, 1, 255, 0, 2]);
+        assert_eq!(pcx.to_image_buffer().unwrap().width(), 2);
+        assert!(pcx.to_indexed_image().is_none());
+    }
+}
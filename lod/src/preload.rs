@@ -0,0 +1,59 @@
+use std::{convert::Infallible, fmt::Display, str::FromStr};
+
+use crate::LodManager;
+
+/// List of lod paths to decode at startup, e.g. "bitmaps/grastyl" or "sprites/rok1".
+/// Written as one path per line, lines starting with `#` are comments.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PreloadManifest {
+    pub paths: Vec<String>,
+}
+
+impl PreloadManifest {
+    /// Everything decoded so far, save it at the end of a profiling run to preload it next time.
+    pub fn from_cache(lod_manager: &LodManager) -> Self {
+        let mut paths = lod_manager.cached_paths();
+        paths.sort();
+        Self { paths }
+    }
+}
+
+impl FromStr for PreloadManifest {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let paths = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_lowercase())
+            .collect();
+        Ok(Self { paths })
+    }
+}
+
+impl Display for PreloadManifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for path in &self.paths {
+            writeln!(f, "{}", path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreloadManifest;
+
+    #[test]
+    fn manifest_round_trip_works() {
+        let manifest: PreloadManifest = "# comment\nbitmaps/GRASTYL\n\n  sprites/rok1 \n"
+            .parse()
+            .unwrap();
+        assert_eq!(manifest.paths, vec!["bitmaps/grastyl", "sprites/rok1"]);
+        assert_eq!(
+            manifest.to_string().parse::<PreloadManifest>(),
+            Ok(manifest)
+        );
+    }
+}
@@ -0,0 +1,30 @@
+//! Types most users need, `use lod::prelude::*` to get them all.
+//! Everything re-exported here is the supported surface of the crate: it only changes with a
+//! version bump, while the items reachable only through the modules may change at any time.
+
+pub use crate::{
+    audio::{SndArchive, Sound},
+    billboard::{Billboard, BillboardManager, BillboardSprite},
+    blv::IndoorMap,
+    bsp_model::{BSPModel, BSPModelFace},
+    checksums::{verify_install, verify_install_with_progress, ChecksumManifest, InstallReport},
+    ddeclist::{DDecList, DDecListItem},
+    dsft::{AnimationFrame, SpriteAction, SpriteAnimation, DSFT},
+    dtft::DTFT,
+    dtile::{Dtile, TerrainGroup, Tile, TileTable},
+    events::{EventCommand, EventScript, Text},
+    get_lod_path,
+    impostor::Impostor,
+    map_deps::MapDependencies,
+    map_stats::{MapStats, MapStatsReport},
+    odm::{Odm, SpawnPoint, ODM_HEIGHT_SCALE, ODM_PLAY_SIZE, ODM_TILE_SCALE},
+    palette::{Palette, Palettes},
+    pcx::Pcx,
+    preload::PreloadManifest,
+    progress::{NoProgress, ProgressSink},
+    save::{SaveGame, SaveHeader},
+    terrain::TerrainMesh,
+    video::{SmkInfo, VidArchive},
+    AssetSource, AtlasRect, CacheStats, DuplicatePolicy, LodDiff, LodManager, LodWriter,
+    SpriteAtlas, TintKind,
+};
@@ -0,0 +1,94 @@
+use std::{
+    error::Error,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// Receives the progress of long operations, it may be called from several threads at once.
+pub trait ProgressSink: Sync {
+    /// `done` out of `total` steps are complete, returning `false` cancels the operation.
+    fn progress(&self, done: usize, total: usize) -> bool;
+}
+
+/// Ignores the progress and never cancels
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn progress(&self, _done: usize, _total: usize) -> bool {
+        true
+    }
+}
+
+impl<F> ProgressSink for F
+where
+    F: Fn(usize, usize) -> bool + Sync,
+{
+    fn progress(&self, done: usize, total: usize) -> bool {
+        self(done, total)
+    }
+}
+
+/// Counts the completed steps of an operation shared between threads.
+/// Once cancelled every following step reports the cancellation without calling the sink.
+pub(crate) struct ProgressCounter<'a> {
+    sink: &'a dyn ProgressSink,
+    total: usize,
+    done: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl<'a> ProgressCounter<'a> {
+    pub fn new(sink: &'a dyn ProgressSink, total: usize) -> Self {
+        Self {
+            sink,
+            total,
+            done: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Records a completed step, `false` when the operation should stop
+    pub fn step(&self) -> bool {
+        if self.is_cancelled() {
+            return false;
+        }
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.sink.progress(done, self.total) {
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+        !self.is_cancelled()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn result(&self) -> Result<(), Box<dyn Error>> {
+        if self.is_cancelled() {
+            Err("Cancelled".into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::ProgressCounter;
+
+    #[test]
+    fn cancellation_works() {
+        let calls = Mutex::new(Vec::new());
+        let sink = |done: usize, total: usize| {
+            calls.lock().unwrap().push((done, total));
+            done < 2
+        };
+        let counter = ProgressCounter::new(&sink, 3);
+        assert!(counter.step());
+        assert!(!counter.step());
+        assert!(!counter.step());
+        assert!(counter.result().is_err());
+        assert_eq!(*calls.lock().unwrap(), vec![(1, 3), (2, 3)]);
+    }
+}
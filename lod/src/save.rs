@@ -0,0 +1,391 @@
+//! Savegames (.mm6, .mm7 and .dod for MM8). They are lod archives holding the save header, the
+//! party, the changes made to the visited maps and a screenshot.
+//! party.bin is the party structure of the game's memory. Its gold, food and the names, classes,
+//! experience, levels and stats of the members are decoded for MM6 and MM7, the inventories
+//! and quest bits stay in the raw bytes the fields are written back over.
+
+use std::{collections::BTreeMap, error::Error, io::Cursor, path::Path};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use image::DynamicImage;
+
+use crate::{lod::Lod, pcx::Pcx, utils::read_string_block, LodWriter, Version};
+
+const HEADER_ENTRY: &str = "header.bin";
+const PARTY_ENTRY: &str = "party.bin";
+const SCREENSHOT_ENTRY: &str = "image.pcx";
+
+const SAVE_NAME_SIZE: usize = 20;
+const LOCATION_NAME_SIZE: usize = 20;
+
+/// Name and location shown in the load menu
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SaveHeader {
+    pub name: String,
+    /// Map file the party is in, e.g. "oute3.odm"
+    pub location: String,
+    /// Bytes after the names, the playing time among them
+    pub unknown: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for SaveHeader {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() < SAVE_NAME_SIZE + LOCATION_NAME_SIZE {
+            return Err("save header is truncated".into());
+        }
+        Ok(Self {
+            name: read_string_block(&data[..SAVE_NAME_SIZE])?,
+            location: read_string_block(
+                &data[SAVE_NAME_SIZE..SAVE_NAME_SIZE + LOCATION_NAME_SIZE],
+            )?,
+            unknown: data[SAVE_NAME_SIZE + LOCATION_NAME_SIZE..].to_vec(),
+        })
+    }
+}
+
+impl SaveHeader {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut data = Vec::with_capacity(SAVE_NAME_SIZE + LOCATION_NAME_SIZE + self.unknown.len());
+        for (text, size) in [
+            (&self.name, SAVE_NAME_SIZE),
+            (&self.location, LOCATION_NAME_SIZE),
+        ] {
+            // keep the terminator
+            if text.len() >= size {
+                return Err(format!("{} is longer than {} bytes", text, size - 1).into());
+            }
+            data.extend_from_slice(text.as_bytes());
+            data.resize(data.len() + size - text.len(), 0);
+        }
+        data.extend_from_slice(&self.unknown);
+        Ok(data)
+    }
+}
+
+const PARTY_MEMBERS: usize = 4;
+const MEMBER_NAME_SIZE: usize = 16;
+/// Might, intellect, personality, endurance, speed, accuracy and luck
+const STATS: usize = 7;
+
+/// Offsets of the decoded fields in party.bin, from the party and player structures of the
+/// game's memory as documented by MMExtension
+struct PartyLayout {
+    food: usize,
+    gold: usize,
+    bank_gold: usize,
+    members: usize,
+    member_size: usize,
+    /// Offsets in a member
+    experience: usize,
+    name: usize,
+    class: usize,
+    /// Base and bonus of each stat, in `STATS` order
+    stats: usize,
+    level: usize,
+}
+
+const MM6_PARTY: PartyLayout = PartyLayout {
+    food: 0xdc,
+    gold: 0xe0,
+    bank_gold: 0xe4,
+    members: 0x2c4,
+    member_size: 0x161c,
+    experience: 0x90,
+    name: 0x98,
+    class: 0xa9,
+    stats: 0xac,
+    level: 0xca,
+};
+
+const MM7_PARTY: PartyLayout = PartyLayout {
+    food: 0x730,
+    gold: 0x734,
+    bank_gold: 0x738,
+    members: 0x9cc,
+    member_size: 0x1b3c,
+    experience: 0xa0,
+    name: 0xa8,
+    class: 0xb9,
+    stats: 0xbc,
+    level: 0xda,
+};
+
+impl PartyLayout {
+    fn of(version: Version) -> Result<&'static Self, Box<dyn Error>> {
+        match version {
+            Version::MM6 => Ok(&MM6_PARTY),
+            Version::MM7 => Ok(&MM7_PARTY),
+            // the MM8 party is a roster the active members are picked from
+            Version::MM8 => Err("MM8 party layout is not supported".into()),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.members + PARTY_MEMBERS * self.member_size
+    }
+}
+
+/// Party member of a save
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PartyMember {
+    pub name: String,
+    /// Class id, the order of the class table of the game
+    pub class: u8,
+    pub experience: i64,
+    pub level: i16,
+    /// Base stats, might, intellect, personality, endurance, speed, accuracy and luck
+    pub stats: [i16; STATS],
+    /// Bonuses added to the base stats, in the same order
+    pub stat_bonuses: [i16; STATS],
+}
+
+/// Decoded party.bin of a save, see the module documentation
+#[derive(Debug, Clone)]
+pub struct Party {
+    pub food: i32,
+    pub gold: i32,
+    pub bank_gold: i32,
+    pub members: [PartyMember; PARTY_MEMBERS],
+    version: Version,
+    /// party.bin as read, the fields above are written over it
+    data: Vec<u8>,
+}
+
+impl TryFrom<(&[u8], Version)> for Party {
+    type Error = Box<dyn Error>;
+
+    fn try_from((data, version): (&[u8], Version)) -> Result<Self, Self::Error> {
+        let layout = PartyLayout::of(version)?;
+        if data.len() < layout.size() {
+            return Err("party is truncated".into());
+        }
+        let i32_at = |offset: usize| Cursor::new(&data[offset..]).read_i32::<LittleEndian>();
+        let mut members: [PartyMember; PARTY_MEMBERS] = Default::default();
+        for (i, member) in members.iter_mut().enumerate() {
+            let record = &data[layout.members + i * layout.member_size..];
+            let name = &record[layout.name..layout.name + MEMBER_NAME_SIZE];
+            member.name = read_string_block(name)?;
+            member.class = record[layout.class];
+            member.experience =
+                Cursor::new(&record[layout.experience..]).read_i64::<LittleEndian>()?;
+            member.level = Cursor::new(&record[layout.level..]).read_i16::<LittleEndian>()?;
+            let mut stats = Cursor::new(&record[layout.stats..]);
+            for stat in 0..STATS {
+                member.stats[stat] = stats.read_i16::<LittleEndian>()?;
+                member.stat_bonuses[stat] = stats.read_i16::<LittleEndian>()?;
+            }
+        }
+        Ok(Self {
+            food: i32_at(layout.food)?,
+            gold: i32_at(layout.gold)?,
+            bank_gold: i32_at(layout.bank_gold)?,
+            members,
+            version,
+            data: data.to_vec(),
+        })
+    }
+}
+
+impl Party {
+    /// party.bin with the decoded fields written back
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let layout = PartyLayout::of(self.version)?;
+        let mut data = self.data.clone();
+        for (offset, value) in [
+            (layout.food, self.food),
+            (layout.gold, self.gold),
+            (layout.bank_gold, self.bank_gold),
+        ] {
+            (&mut data[offset..offset + 4]).write_i32::<LittleEndian>(value)?;
+        }
+        for (i, member) in self.members.iter().enumerate() {
+            let record = &mut data[layout.members + i * layout.member_size..];
+            let name = &mut record[layout.name..layout.name + MEMBER_NAME_SIZE];
+            // the bytes after the terminator are kept while the name is unchanged
+            if read_string_block(name).ok().as_deref() != Some(member.name.as_str()) {
+                if member.name.len() >= MEMBER_NAME_SIZE {
+                    return Err(format!(
+                        "{} is longer than {} bytes",
+                        member.name,
+                        MEMBER_NAME_SIZE - 1
+                    )
+                    .into());
+                }
+                name.fill(0);
+                name[..member.name.len()].copy_from_slice(member.name.as_bytes());
+            }
+            record[layout.class] = member.class;
+            (&mut record[layout.experience..]).write_i64::<LittleEndian>(member.experience)?;
+            (&mut record[layout.level..]).write_i16::<LittleEndian>(member.level)?;
+            let mut stats = &mut record[layout.stats..];
+            for stat in 0..STATS {
+                stats.write_i16::<LittleEndian>(member.stats[stat])?;
+                stats.write_i16::<LittleEndian>(member.stat_bonuses[stat])?;
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Savegame entries by lowercase name, written back in the same archive layout
+#[derive(Debug, Clone)]
+pub struct SaveGame {
+    version: Version,
+    directory: String,
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl SaveGame {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let lod = Lod::open(path)?;
+        let entries = lod
+            .files()
+            .into_iter()
+            .filter(|name| *name != lod.directory())
+            .filter_map(|name| Some((name.to_string(), lod.try_get_bytes(name)?.to_vec())))
+            .collect();
+        Ok(Self {
+            version: lod.version(),
+            directory: lod.directory().to_string(),
+            entries,
+        })
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Entry names, sorted
+    pub fn entries(&self) -> Vec<&str> {
+        self.entries.keys().map(|name| name.as_str()).collect()
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&[u8]> {
+        self.entries
+            .get(&name.to_lowercase())
+            .map(|data| data.as_slice())
+    }
+
+    /// Replaces or adds an entry
+    pub fn set_entry(&mut self, name: &str, data: Vec<u8>) {
+        self.entries.insert(name.to_lowercase(), data);
+    }
+
+    pub fn header(&self) -> Result<SaveHeader, Box<dyn Error>> {
+        SaveHeader::try_from(self.entry(HEADER_ENTRY).ok_or("save has no header")?)
+    }
+
+    pub fn set_header(&mut self, header: &SaveHeader) -> Result<(), Box<dyn Error>> {
+        self.set_entry(HEADER_ENTRY, header.to_bytes()?);
+        Ok(())
+    }
+
+    /// Party of the save, the raw party.bin is `entry("party.bin")`
+    pub fn party(&self) -> Result<Party, Box<dyn Error>> {
+        let data = self.entry(PARTY_ENTRY).ok_or("save has no party")?;
+        Party::try_from((data, self.version))
+    }
+
+    pub fn set_party(&mut self, party: &Party) -> Result<(), Box<dyn Error>> {
+        self.set_entry(PARTY_ENTRY, party.to_bytes()?);
+        Ok(())
+    }
+
+    pub fn screenshot(&self) -> Option<DynamicImage> {
+        Pcx::try_from(self.entry(SCREENSHOT_ENTRY)?)
+            .and_then(|pcx| pcx.to_image_buffer())
+            .ok()
+    }
+
+    /// Saved state of the visited maps, .ddm for outdoor maps and .dlv for indoor ones
+    pub fn map_deltas(&self) -> Vec<&str> {
+        self.entries()
+            .into_iter()
+            .filter(|name| name.ends_with(".ddm") || name.ends_with(".dlv"))
+            .collect()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let version = match self.version {
+            Version::MM6 => "MMVI",
+            Version::MM7 => "MMVII",
+            Version::MM8 => "MMVIII",
+        };
+        let mut writer = LodWriter::new(version, &self.directory)?;
+        for (name, data) in &self.entries {
+            writer.add(name, data.clone())?;
+        }
+        writer.write(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Party, SaveGame, SaveHeader, MM6_PARTY};
+    use crate::{LodWriter, TempDir, Version};
+
+    #[test]
+    fn save_game_works() {
+        let header = SaveHeader {
+            name: "before the dragon".into(),
+            location: "oute3.odm".into(),
+            unknown: vec![1, 2, 3],
+        };
+        let mut writer = LodWriter::new("MMVI", "chapter").unwrap();
+        writer
+            .add("header.bin", header.to_bytes().unwrap())
+            .unwrap();
+        let mut party = vec![0; MM6_PARTY.size()];
+        party[MM6_PARTY.gold] = 200;
+        let first = MM6_PARTY.members + MM6_PARTY.name;
+        party[first..first + 6].copy_from_slice(b"Zoltan");
+        party[first + 7] = 0xcc;
+        writer.add("party.bin", party.clone()).unwrap();
+        writer.add("oute3.ddm", vec![1]).unwrap();
+        writer.add("d01.dlv", vec![2]).unwrap();
+        let dir = TempDir::new("save_game");
+        let path = dir.join("save000.mm6");
+        writer.write(&path).unwrap();
+
+        let mut save = SaveGame::open(&path).unwrap();
+        assert_eq!(save.version(), Version::MM6);
+        assert_eq!(save.header().unwrap(), header);
+        assert_eq!(save.map_deltas(), vec!["d01.dlv", "oute3.ddm"]);
+        assert!(!save.entries().contains(&"chapter"));
+        assert!(save.screenshot().is_none());
+
+        let renamed = SaveHeader {
+            name: "after the dragon".into(),
+            ..header
+        };
+        save.set_header(&renamed).unwrap();
+        let mut decoded = save.party().unwrap();
+        assert_eq!(decoded.gold, 200);
+        assert_eq!(decoded.members[0].name, "Zoltan");
+        assert_eq!(decoded.to_bytes().unwrap(), party);
+        decoded.gold = 1000;
+        decoded.members[1].name = "Serena".into();
+        decoded.members[1].level = 5;
+        decoded.members[1].stats[0] = 30;
+        decoded.members[1].stat_bonuses[6] = -2;
+        save.set_party(&decoded).unwrap();
+        save.save(&path).unwrap();
+        let reopened = SaveGame::open(&path).unwrap();
+        assert_eq!(reopened.header().unwrap(), renamed);
+        let reparsed = reopened.party().unwrap();
+        assert_eq!(reparsed.gold, 1000);
+        assert_eq!(reparsed.members, decoded.members);
+        assert_eq!(reopened.entry("party.bin").unwrap()[first + 7], 0xcc);
+        assert_eq!(reopened.entries(), save.entries());
+
+        let too_long = SaveHeader {
+            name: "a name longer than twenty".into(),
+            ..Default::default()
+        };
+        assert!(too_long.to_bytes().is_err());
+        assert!(Party::try_from((&party[1..], Version::MM6)).is_err());
+        assert!(Party::try_from((&party[..], Version::MM8)).is_err());
+    }
+}
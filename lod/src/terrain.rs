@@ -0,0 +1,139 @@
+//! Indexed triangle mesh of the outdoor terrain, textured with the tile atlas of `get_atlas`.
+
+use crate::{
+    dtile::TileTable,
+    odm::{Odm, ODM_HEIGHT_SCALE, ODM_TILE_SCALE},
+};
+
+/// Terrain of an outdoor map. Each cell has its own four vertices so it can show its own tile
+/// of the atlas, the normals are smoothed across cells.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TerrainMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl TerrainMesh {
+    pub fn new(odm: &Odm, tile_table: &TileTable) -> Self {
+        Self::decimated(odm, tile_table, 1)
+    }
+
+    /// Lower detail mesh keeping one height sample every `step` along each side, the border
+    /// samples are always kept so the mesh covers the same area. A cell shows the tile of its
+    /// first corner.
+    pub fn decimated(odm: &Odm, tile_table: &TileTable, step: usize) -> Self {
+        let (width, _) = odm.size();
+        Self::from_maps(width, &odm.height_map, &odm.tile_map, tile_table, step)
+    }
+
+    fn from_maps(
+        size: usize,
+        height_map: &[u8],
+        tile_map: &[u8],
+        tile_table: &TileTable,
+        step: usize,
+    ) -> Self {
+        let mut samples: Vec<usize> = (0..size).step_by(step.max(1)).collect();
+        if samples.last() != Some(&(size - 1)) {
+            samples.push(size - 1);
+        }
+
+        let half = size as f32 / 2.;
+        let height = |w: usize, d: usize| height_map[d * size + w] as f32 * ODM_HEIGHT_SCALE;
+        let position = |w: usize, d: usize| {
+            [
+                (w as f32 - half) * ODM_TILE_SCALE,
+                height(w, d),
+                (d as f32 - half) * ODM_TILE_SCALE,
+            ]
+        };
+        // central differences of the full resolution heights, one sided on the borders
+        let normal = |w: usize, d: usize| {
+            let (w0, w1) = (w.saturating_sub(1), (w + 1).min(size - 1));
+            let (d0, d1) = (d.saturating_sub(1), (d + 1).min(size - 1));
+            let dx = (height(w1, d) - height(w0, d)) / ((w1 - w0) as f32 * ODM_TILE_SCALE);
+            let dz = (height(w, d1) - height(w, d0)) / ((d1 - d0) as f32 * ODM_TILE_SCALE);
+            let length = (dx * dx + 1. + dz * dz).sqrt();
+            [-dx / length, 1. / length, -dz / length]
+        };
+
+        let cells = (samples.len() - 1) * (samples.len() - 1);
+        let mut mesh = Self {
+            positions: Vec::with_capacity(cells * 4),
+            normals: Vec::with_capacity(cells * 4),
+            uvs: Vec::with_capacity(cells * 4),
+            indices: Vec::with_capacity(cells * 6),
+        };
+        let (columns, rows) = tile_table.size();
+        let (columns, rows) = (columns as f32, rows as f32);
+        for d in samples.windows(2) {
+            for w in samples.windows(2) {
+                let (x, y) = tile_table.coordinate(tile_map[d[0] * size + w[0]]);
+                let (u0, u1) = (x as f32 / columns, (x as f32 + 1.) / columns);
+                let (v0, v1) = (y as f32 / rows, (y as f32 + 1.) / rows);
+
+                let first = mesh.positions.len() as u32;
+                for (corner_w, corner_d, uv) in [
+                    (w[0], d[0], [u0, v0]),
+                    (w[0], d[1], [u0, v1]),
+                    (w[1], d[0], [u1, v0]),
+                    (w[1], d[1], [u1, v1]),
+                ] {
+                    mesh.positions.push(position(corner_w, corner_d));
+                    mesh.normals.push(normal(corner_w, corner_d));
+                    mesh.uvs.push(uv);
+                }
+                mesh.indices
+                    .extend([0, 1, 2, 2, 1, 3].iter().map(|i| first + i));
+            }
+        }
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TerrainMesh;
+    use crate::dtile::TileTable;
+
+    #[test]
+    fn terrain_mesh_works() {
+        let names: [String; 256] = std::array::from_fn(|i| {
+            if i < 12 {
+                format!("tile{:02}", i)
+            } else {
+                "tile00".into()
+            }
+        });
+        let tile_table = TileTable::new(names);
+        let size = 5;
+        let mut tile_map = vec![0u8; size * size];
+        tile_map[0] = 11;
+        let flat = vec![4u8; size * size];
+
+        let mesh = TerrainMesh::from_maps(size, &flat, &tile_map, &tile_table, 1);
+        assert_eq!((mesh.positions.len(), mesh.indices.len()), (64, 96));
+        assert_eq!(&mesh.indices[..6], &[0, 1, 2, 2, 1, 3]);
+        assert_eq!(mesh.positions[0], [-1280., 128., -1280.]);
+        assert_eq!(mesh.positions[3], [-768., 128., -768.]);
+        assert!(mesh.normals.iter().all(|n| *n == [0., 1., 0.]));
+        // tile 11 is the second one of the second row of a 10 wide atlas
+        assert_eq!(mesh.uvs[0], [0.1, 0.5]);
+        assert_eq!(mesh.uvs[3], [0.2, 1.]);
+
+        let slope: Vec<u8> = (0..size * size).map(|i| (i % size) as u8 * 16).collect();
+        let mesh = TerrainMesh::from_maps(size, &slope, &tile_map, &tile_table, 1);
+        let n = mesh.normals[0];
+        assert!(n[0] < 0. && n[1] > 0. && n[2] == 0.);
+        assert!((n.iter().map(|c| c * c).sum::<f32>() - 1.).abs() < 1e-5);
+
+        let mesh = TerrainMesh::from_maps(size, &flat, &tile_map, &tile_table, 2);
+        assert_eq!(mesh.positions.len(), 16);
+        let mesh = TerrainMesh::from_maps(size, &flat, &tile_map, &tile_table, 3);
+        assert_eq!(mesh.positions.len(), 16);
+        assert_eq!(mesh.positions[3], [256., 128., 256.]);
+        assert_eq!(mesh.positions.last(), Some(&[768., 128., 768.]));
+    }
+}
@@ -3,8 +3,6 @@ use std::{
     io::{BufRead, Cursor, Read, Seek},
 };
 
-use byteorder::ReadBytesExt;
-
 pub(super) fn try_read_string<R>(r: &mut R) -> Result<String, Box<dyn Error>>
 where
     R: Read + BufRead,
@@ -32,11 +30,116 @@ pub(super) fn try_read_string_block(
     Ok(s)
 }
 
-// debug
-pub(super) fn hexdump_next_bytes(cursor: &mut Cursor<&[u8]>, n: usize) {
-    let mut t: Vec<u8> = Vec::new();
-    for _i in 0..n {
-        t.push(cursor.read_u8().unwrap())
+/// String of a fixed size block, up to the first nul
+pub(super) fn read_string_block(block: &[u8]) -> Result<String, Box<dyn Error>> {
+    let end = block.iter().position(|&b| b == 0).unwrap_or(block.len());
+    Ok(String::from_utf8(block[..end].to_vec())?)
+}
+
+/// Fixed size block of `name`: `block` when it still holds that name, up to the ascii case, so
+/// the bytes after the terminator are kept, otherwise the name padded with nuls.
+pub(super) fn string_block<const N: usize>(
+    name: &str,
+    block: &[u8; N],
+) -> Result<[u8; N], Box<dyn Error>> {
+    if read_string_block(block).is_ok_and(|stored| stored.eq_ignore_ascii_case(name)) {
+        return Ok(*block);
+    }
+    if name.len() >= N {
+        return Err(format!("{:?} doesn't fit in {}B", name, N).into());
+    }
+    let mut block = [0; N];
+    block[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(block)
+}
+
+/// Matches `name` against a pattern where `*` is any run of characters and `?` any single one,
+/// ignoring the ascii case like the lod lookups.
+pub(super) fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // position after the last `*` and the name position it was tried at
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(c) if *c == b'?' || c.eq_ignore_ascii_case(&name[n]) => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Frames of the group starting at `start` in a sprite or texture frame table, the group goes
+/// on while the frames have the "not group end" flag.
+pub(super) fn frame_group<T>(
+    frames: &[T],
+    start: usize,
+    is_not_group_end: impl Fn(&T) -> bool,
+) -> &[T] {
+    let end = frames[start..]
+        .iter()
+        .position(|f| !is_not_group_end(f))
+        .map(|i| start + i + 1)
+        .unwrap_or(frames.len());
+    &frames[start..end]
+}
+
+/// Index of the frame shown `time` into a looping animation whose frames last `durations`,
+/// the first frame when the animation has no length.
+pub(super) fn frame_at(durations: &[u32], time: u32) -> Option<usize> {
+    let total: u32 = durations.iter().sum();
+    if total == 0 {
+        return (!durations.is_empty()).then_some(0);
+    }
+    let mut time = time % total;
+    durations.iter().position(|&duration| {
+        let shown = time < duration;
+        time = time.saturating_sub(duration);
+        shown
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_at, frame_group, glob_match};
+
+    #[test]
+    fn glob_match_works() {
+        assert!(glob_match("gm*", "GMAsta0"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("d?t", "dot"));
+        assert!(glob_match("*ta*0", "gmasta0"));
+        assert!(glob_match("a*b*c", "axxbyybc"));
+        assert!(!glob_match("gm*", "ogm"));
+        assert!(!glob_match("d?t", "dt"));
+        assert!(!glob_match("a*b", "abc"));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn frame_helpers_work() {
+        let ends = [true, true, false, false];
+        assert_eq!(frame_group(&ends, 0, |&e| e).len(), 3);
+        assert_eq!(frame_group(&ends, 3, |&e| e).len(), 1);
+        assert_eq!(frame_group(&[true], 0, |&e| e).len(), 1);
+
+        assert_eq!(frame_at(&[8, 4, 4], 9), Some(1));
+        assert_eq!(frame_at(&[8, 4, 4], 16 + 12), Some(2));
+        assert_eq!(frame_at(&[0, 0], 5), Some(0));
+        assert_eq!(frame_at(&[], 5), None);
     }
-    hexdump::hexdump(t.as_slice());
 }
@@ -0,0 +1,183 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::utils::try_read_string_block;
+
+const NAME_SIZE: usize = 40;
+const ENTRY_SIZE: usize = NAME_SIZE + 4;
+
+/// Video archive, e.g. Anims1.vid with the intro and the house videos. Each entry is a
+/// Smacker video, the ranges are derived from the offsets as the archive stores no sizes.
+/// Only the index is kept in memory, the videos are read from the file when asked for.
+pub struct VidArchive {
+    path: PathBuf,
+    entries: Vec<(String, Range<u64>)>,
+}
+
+impl VidArchive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = BufReader::new(File::open(&path)?);
+        let size = file.get_ref().metadata()?.len();
+        let count = file.read_u32::<LittleEndian>()? as u64;
+        if 4 + count * ENTRY_SIZE as u64 > size {
+            return Err("Video archive index is truncated".into());
+        }
+        let mut index = vec![0; count as usize * ENTRY_SIZE];
+        file.read_exact(&mut index)?;
+        let mut cursor = Cursor::new(index.as_slice());
+        let mut offsets = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let name = try_read_string_block(&mut cursor, NAME_SIZE)?.to_lowercase();
+            let name = name.strip_suffix(".smk").unwrap_or(&name).to_string();
+            offsets.push((name, cursor.read_u32::<LittleEndian>()? as u64));
+        }
+
+        let mut sorted: Vec<u64> = offsets.iter().map(|(_, offset)| *offset).collect();
+        sorted.sort();
+        let mut entries = Vec::with_capacity(offsets.len());
+        for (name, offset) in offsets {
+            let end = sorted
+                .iter()
+                .find(|&&o| o > offset)
+                .copied()
+                .unwrap_or(size);
+            if end > size {
+                return Err(format!("Video {} is truncated", name).into());
+            }
+            entries.push((name, offset..end));
+        }
+        Ok(Self { path, entries })
+    }
+
+    /// Video names in archive order
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Smacker file of a video, the lookup is case-insensitive and ignores the `.smk` extension
+    pub fn smk(&self, name: &str) -> Option<Vec<u8>> {
+        let name = name.to_lowercase();
+        let name = name.strip_suffix(".smk").unwrap_or(&name);
+        let (_, range) = self.entries.iter().find(|(entry, _)| entry == name)?;
+        match self.read(range.clone()) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                log::warn!(
+                    "Unable to read {} from {}: {}",
+                    name,
+                    self.path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn read(&self, range: Range<u64>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(range.start))?;
+        let mut data = vec![0; (range.end - range.start) as usize];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Header of a Smacker video, enough to know what a video is without decoding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmkInfo {
+    /// 2 or 4
+    pub version: u8,
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    /// Positive in milliseconds per frame, negative in 1/100000 seconds, 0 means 10 fps
+    pub frame_rate: i32,
+}
+
+impl SmkInfo {
+    pub fn frames_per_second(&self) -> f32 {
+        match self.frame_rate {
+            r if r > 0 => 1000. / r as f32,
+            r if r < 0 => 100000. / -r as f32,
+            _ => 10.,
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for SmkInfo {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let mut cursor = Cursor::new(data);
+        let mut signature = [0; 4];
+        cursor.read_exact(&mut signature)?;
+        let version = match &signature {
+            b"SMK2" => 2,
+            b"SMK4" => 4,
+            _ => return Err("Not a smacker video".into()),
+        };
+        Ok(Self {
+            version,
+            width: cursor.read_u32::<LittleEndian>()?,
+            height: cursor.read_u32::<LittleEndian>()?,
+            frames: cursor.read_u32::<LittleEndian>()?,
+            frame_rate: cursor.read_i32::<LittleEndian>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{SmkInfo, VidArchive, ENTRY_SIZE, NAME_SIZE};
+    use crate::TempDir;
+
+    fn smk(width: u32, frames: u32) -> Vec<u8> {
+        let mut data = b"SMK2".to_vec();
+        for v in [width, 480, frames] {
+            data.extend(v.to_le_bytes());
+        }
+        data.extend((-6667i32).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn vid_archive_works() {
+        let videos = [("3DOLogo.smk", smk(640, 10)), ("intro", smk(320, 20))];
+        let mut data = (videos.len() as u32).to_le_bytes().to_vec();
+        let mut offset = 4 + videos.len() * ENTRY_SIZE;
+        for (name, video) in &videos {
+            let mut entry = name.as_bytes().to_vec();
+            entry.resize(NAME_SIZE, 0);
+            entry.extend((offset as u32).to_le_bytes());
+            data.extend(entry);
+            offset += video.len();
+        }
+        for (_, video) in &videos {
+            data.extend(video);
+        }
+        let dir = TempDir::new("vid");
+        fs::write(dir.join("anims1.vid"), &data).unwrap();
+        fs::write(dir.join("broken.vid"), [9, 0, 0, 0]).unwrap();
+        let vid = VidArchive::open(dir.join("anims1.vid"));
+        let broken = VidArchive::open(dir.join("broken.vid"));
+
+        let vid = vid.unwrap();
+        assert_eq!(vid.names(), vec!["3dologo", "intro"]);
+        assert_eq!(vid.smk("3DOLOGO.SMK").unwrap(), videos[0].1);
+        let info = SmkInfo::try_from(vid.smk("intro").unwrap().as_slice()).unwrap();
+        assert_eq!((info.width, info.height, info.frames), (320, 480, 20));
+        assert!((info.frames_per_second() - 15.).abs() < 0.01);
+        assert!(vid.smk("missing").is_none());
+        assert!(broken.is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fmt::Write,
+    fs,
+    panic::PanicHookInfo,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use lod::LodManager;
+
+use crate::{world::WorldSettings, APP_NAME};
+
+/// Events kept for the report, older ones are dropped
+const MAX_EVENTS: usize = 64;
+
+/// What the panic hook knows about the game, it can't reach into the ECS.
+struct CrashContext {
+    map: Option<String>,
+    events: VecDeque<String>,
+    lod_manager: Option<Arc<LodManager>>,
+}
+
+static CRASH_CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    map: None,
+    events: VecDeque::new(),
+    lod_manager: None,
+});
+
+/// Writes a diagnostic report to the working directory when the game panics.
+pub struct CrashReportPlugin;
+
+impl Plugin for CrashReportPlugin {
+    fn build(&self, app: &mut App) {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            match write_report(info) {
+                Ok(path) => eprintln!("Crash report written to {}", path.display()),
+                Err(e) => eprintln!("Unable to write the crash report: {}", e),
+            }
+            previous_hook(info);
+        }));
+
+        app.add_systems(
+            Update,
+            track_world.run_if(resource_changed::<WorldSettings>()),
+        );
+    }
+}
+
+/// Remembers something that happened, to be listed in the crash report
+pub(crate) fn record(event: impl Into<String>) {
+    if let Ok(mut context) = CRASH_CONTEXT.lock() {
+        if context.events.len() == MAX_EVENTS {
+            context.events.pop_front();
+        }
+        context.events.push_back(event.into());
+    }
+}
+
+fn track_world(settings: Res<WorldSettings>) {
+    if let Ok(mut context) = CRASH_CONTEXT.lock() {
+        context.map = Some(settings.current_odm.to_string());
+        if context.lod_manager.is_none() {
+            context.lod_manager = Some(settings.lod_manager.clone());
+        }
+    }
+}
+
+fn write_report(info: &PanicHookInfo) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "{} {} crash report",
+        APP_NAME,
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(report, "{}", info)?;
+
+    // the panic may have happened while the context was locked
+    match CRASH_CONTEXT.try_lock() {
+        Ok(context) => {
+            writeln!(
+                report,
+                "\nmap: {}",
+                context.map.as_deref().unwrap_or("none")
+            )?;
+            if let Some(lod_manager) = &context.lod_manager {
+                writeln!(report, "archives: {}", lod_manager.archives().join(", "))?;
+                writeln!(report, "\nlast decoded assets:")?;
+                for asset in lod_manager.recent_assets() {
+                    writeln!(report, "  {}", asset)?;
+                }
+                writeln!(report, "\nwhere they come from:")?;
+                for source in lod_manager.recent_sources() {
+                    writeln!(report, "  {}", source)?;
+                }
+            }
+            writeln!(report, "\nrecent events:")?;
+            for event in &context.events {
+                writeln!(report, "  {}", event)?;
+            }
+        }
+        Err(_) => writeln!(report, "\ngame context unavailable")?,
+    }
+
+    writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture())?;
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let path = PathBuf::from(format!("{}-crash-{}.txt", APP_NAME, time));
+    fs::write(&path, report)?;
+    Ok(path)
+}
@@ -3,8 +3,9 @@ use bevy::{
     input::common_conditions::input_toggle_active,
     pbr::wireframe::{WireframeConfig, WireframePlugin},
     prelude::{
-        default, in_state, App, Color, Commands, Component, Input, IntoSystemConfigs, KeyCode,
-        OnEnter, Plugin, Query, Res, ResMut, Resource, TextBundle, Transform, Update, Vec3, With,
+        default, in_state, info, App, Color, Commands, Component, Input, IntoSystemConfigs,
+        KeyCode, OnEnter, Plugin, Query, Res, ResMut, Resource, TextBundle, Transform, Update,
+        Vec3, With,
     },
     text::{Text, TextSection, TextStyle},
 };
@@ -12,7 +13,7 @@ use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
 use lod::odm::{ODM_PLAY_SIZE, ODM_TILE_SCALE};
 
-use crate::{player::FlyCam, GameState};
+use crate::{player::FlyCam, world::WorldSettings, GameState};
 
 /// Keeps track of mouse motion events, pitch, and yaw
 #[derive(Resource)]
@@ -33,6 +34,7 @@ impl Default for DevConfig {
 pub struct KeyBindings {
     pub toggle_wireframe: KeyCode,
     pub toggle_play_area: KeyCode,
+    pub log_asset_sources: KeyCode,
 }
 
 impl Default for KeyBindings {
@@ -40,13 +42,14 @@ impl Default for KeyBindings {
         Self {
             toggle_wireframe: KeyCode::BracketRight,
             toggle_play_area: KeyCode::BracketLeft,
+            log_asset_sources: KeyCode::Backslash,
         }
     }
 }
 
 fn dev_setup(
     mut commands: Commands,
-    dev_config: Res<DevConfig>,
+    _dev_config: Res<DevConfig>,
     mut lines: ResMut<DebugLines>,
     //mut wireframe_config: ResMut<WireframeConfig>,
 ) {
@@ -128,11 +131,17 @@ fn dev_input(
     key_bindings: Res<KeyBindings>,
     mut dev_config: ResMut<DevConfig>,
     mut wireframe_config: ResMut<WireframeConfig>,
+    settings: Res<WorldSettings>,
 ) {
     if keys.just_pressed(key_bindings.toggle_wireframe) {
         dev_config.show_play_area = !dev_config.show_play_area;
     } else if keys.just_pressed(key_bindings.toggle_play_area) {
         wireframe_config.global = !wireframe_config.global;
+    } else if keys.just_pressed(key_bindings.log_asset_sources) {
+        // which archive or patch supplied what was just decoded
+        for source in settings.lod_manager.recent_sources() {
+            info!("{}", source);
+        }
     }
 }
 
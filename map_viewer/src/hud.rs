@@ -0,0 +1,127 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{despawn_all, odm::MapLoading, player::FlyCam, GameState};
+
+/// Compass marks, one every 15 degrees starting from north and going clockwise
+const COMPASS_MARKS: [&str; 24] = [
+    "N", ".", ".", "NE", ".", ".", "E", ".", ".", "SE", ".", ".", "S", ".", ".", "SW", ".", ".",
+    "W", ".", ".", "NW", ".", ".",
+];
+/// Marks shown on each side of the current heading
+const COMPASS_HALF_WIDTH: usize = 4;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), hud_setup)
+            .add_systems(
+                Update,
+                (update_compass, update_loading_text).run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), despawn_all::<OnHud>);
+    }
+}
+
+#[derive(Component)]
+struct OnHud;
+
+#[derive(Component)]
+struct Compass;
+
+#[derive(Component)]
+struct LoadingText;
+
+fn hud_setup(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    top: Val::Px(5.0),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            Name::new("hud"),
+            OnHud,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::GOLD,
+                        ..default()
+                    },
+                ),
+                Compass,
+            ));
+        });
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 30.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        }),
+        LoadingText,
+        OnHud,
+    ));
+}
+
+/// Heading in radians from north, clockwise. North is towards -z and east towards +x.
+fn heading(transform: &Transform) -> f32 {
+    let forward = transform.forward();
+    forward.x.atan2(-forward.z).rem_euclid(TAU)
+}
+
+fn compass_strip(heading: f32) -> String {
+    let marks = COMPASS_MARKS.len();
+    let center = (heading / TAU * marks as f32).round() as usize % marks;
+    (0..=2 * COMPASS_HALF_WIDTH)
+        .map(|i| COMPASS_MARKS[(center + marks + i - COMPASS_HALF_WIDTH) % marks])
+        .map(|mark| format!("{mark:^4}"))
+        .collect()
+}
+
+fn update_compass(
+    camera: Query<&Transform, (With<FlyCam>, Changed<Transform>)>,
+    mut query: Query<&mut Text, With<Compass>>,
+) {
+    let Ok(transform) = camera.get_single() else {
+        return;
+    };
+    let strip = compass_strip(heading(transform));
+    for mut text in &mut query {
+        text.sections[0].value = strip.clone();
+    }
+}
+
+fn update_loading_text(
+    loading: Option<Res<MapLoading>>,
+    mut query: Query<&mut Text, With<LoadingText>>,
+) {
+    let value = loading
+        .map(|loading| format!("Loading {} {:.0}%", loading.map, loading.progress() * 100.))
+        .unwrap_or_default();
+    for mut text in &mut query {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
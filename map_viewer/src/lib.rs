@@ -2,16 +2,22 @@ use bevy::prelude::{
     App, Commands, Component, DespawnRecursiveExt, Entity, Plugin, Query, States, With,
 };
 use bevy_config::BevyConfigPlugin;
+use crash_report::CrashReportPlugin;
 use dev::DevPlugin;
+use hud::HudPlugin;
 use menu::MenuPlugin;
+use settings::SettingsPlugin;
 use splash::SplashPlugin;
 use world::WorldPlugin;
 
 pub(crate) mod bevy_config;
+pub(crate) mod crash_report;
 pub(crate) mod dev;
+pub(crate) mod hud;
 pub(crate) mod menu;
 pub(crate) mod odm;
 pub(crate) mod player;
+pub(crate) mod settings;
 pub(crate) mod splash;
 pub(crate) mod utils;
 pub(crate) mod world;
@@ -32,9 +38,12 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_state::<GameState>().add_plugins((
             BevyConfigPlugin,
+            CrashReportPlugin,
+            SettingsPlugin,
             MenuPlugin,
             SplashPlugin,
             WorldPlugin,
+            HudPlugin,
             DevPlugin,
         ));
     }
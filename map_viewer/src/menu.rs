@@ -1,6 +1,16 @@
+use std::marker::PhantomData;
+
 use bevy::{app::AppExit, prelude::*};
 
-use super::{despawn_all, GameState};
+use super::{
+    despawn_all, player,
+    settings::{
+        self, AudioSettings, CombatMode, DrawDistance, FovMode, GameplaySettings, KeyBindingSet,
+        MusicVolume, RenderSettings, Setting, SoundVolume,
+    },
+    world::game_speed,
+    GameState,
+};
 
 const TEXT_COLOR: Color = Color::rgb(0.3, 0.9, 0.3);
 
@@ -9,6 +19,7 @@ pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_state::<MenuState>()
+            .init_resource::<Rebinding>()
             .add_systems(OnEnter(GameState::Menu), menu_setup)
             .add_systems(OnEnter(MenuState::Main), main_menu_setup)
             .add_systems(OnExit(MenuState::Main), despawn_all::<OnMainMenuScreen>)
@@ -17,27 +28,57 @@ impl Plugin for MenuPlugin {
                 OnExit(MenuState::Settings),
                 despawn_all::<OnSettingsMenuScreen>,
             )
-            // .add_systems(
-            //     OnEnter(MenuState::SettingsDisplay),
-            //     display_settings_menu_setup,
-            // )
-            // .add_systems(
-            //     Update,
-            //     (setting_button::<WindowMode>.run_if(in_state(MenuState::SettingsDisplay)),),
-            // )
-            // .add_systems(
-            //     OnExit(MenuState::SettingsDisplay),
-            //     despawn_screen::<OnDisplaySettingsMenuScreen>,
-            // )
-            // .add_systems(OnEnter(MenuState::SettingsSound), sound_settings_menu_setup)
-            // .add_systems(
-            //     Update,
-            //     setting_button::<Volume>.run_if(in_state(MenuState::SettingsSound)),
-            // )
+            .add_systems(
+                OnEnter(MenuState::SettingsDisplay),
+                display_settings_menu_setup,
+            )
+            .add_systems(
+                Update,
+                (setting_button::<FovMode>, setting_button::<DrawDistance>)
+                    .run_if(in_state(MenuState::SettingsDisplay)),
+            )
+            .add_systems(
+                OnExit(MenuState::SettingsDisplay),
+                despawn_all::<OnDisplaySettingsMenuScreen>,
+            )
+            .add_systems(OnEnter(MenuState::SettingsSound), sound_settings_menu_setup)
+            .add_systems(
+                Update,
+                (setting_button::<SoundVolume>, setting_button::<MusicVolume>)
+                    .run_if(in_state(MenuState::SettingsSound)),
+            )
             .add_systems(
                 OnExit(MenuState::SettingsSound),
                 despawn_all::<OnSoundSettingsMenuScreen>,
             )
+            .add_systems(
+                OnEnter(MenuState::SettingsGameplay),
+                gameplay_settings_menu_setup,
+            )
+            .add_systems(
+                Update,
+                setting_button::<CombatMode>.run_if(in_state(MenuState::SettingsGameplay)),
+            )
+            .add_systems(
+                OnExit(MenuState::SettingsGameplay),
+                despawn_all::<OnGameplaySettingsMenuScreen>,
+            )
+            .add_systems(OnEnter(MenuState::SettingsControls), controls_menu_setup)
+            .add_systems(
+                Update,
+                (
+                    start_rebinding,
+                    rebind_key::<settings::KeyBindings>,
+                    rebind_key::<player::KeyBindings>,
+                    rebind_key::<game_speed::KeyBindings>,
+                )
+                    .chain()
+                    .run_if(in_state(MenuState::SettingsControls)),
+            )
+            .add_systems(
+                OnExit(MenuState::SettingsControls),
+                (despawn_all::<OnControlsMenuScreen>, stop_rebinding),
+            )
             .add_systems(
                 Update,
                 (menu_action, button_system).run_if(in_state(GameState::Menu)),
@@ -52,6 +93,8 @@ enum MenuState {
     Settings,
     SettingsDisplay,
     SettingsSound,
+    SettingsGameplay,
+    SettingsControls,
     #[default]
     Disabled,
 }
@@ -68,6 +111,12 @@ struct OnDisplaySettingsMenuScreen;
 #[derive(Component)]
 struct OnSoundSettingsMenuScreen;
 
+#[derive(Component)]
+struct OnGameplaySettingsMenuScreen;
+
+#[derive(Component)]
+struct OnControlsMenuScreen;
+
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
 const HOVERED_PRESSED_BUTTON: Color = Color::rgb(0.25, 0.65, 0.25);
@@ -81,16 +130,21 @@ enum MenuButtonAction {
     Play,
     Settings,
     SettingsDisplay,
-    //SettingsSound,
+    SettingsSound,
+    SettingsGameplay,
+    SettingsControls,
     BackToMainMenu,
     BackToSettings,
     Quit,
 }
 
+/// Buttons whose interaction changed this frame
+type ButtonInteraction = (Changed<Interaction>, With<Button>);
+
 fn button_system(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, Option<&SelectedOption>),
-        (Changed<Interaction>, With<Button>),
+        ButtonInteraction,
     >,
 ) {
     for (interaction, mut color, selected) in &mut interaction_query {
@@ -103,19 +157,20 @@ fn button_system(
     }
 }
 
-fn setting_button<T: Resource + Component + PartialEq + Copy>(
-    interaction_query: Query<(&Interaction, &T, Entity), (Changed<Interaction>, With<Button>)>,
-    mut selected_query: Query<(Entity, &mut BackgroundColor), With<SelectedOption>>,
+fn setting_button<T: Setting>(
+    interaction_query: Query<(&Interaction, &T, Entity), ButtonInteraction>,
+    mut selected_query: Query<(Entity, &T, &mut BackgroundColor), With<SelectedOption>>,
     mut commands: Commands,
-    mut setting: ResMut<T>,
+    mut settings: ResMut<T::Settings>,
 ) {
     for (interaction, button_setting, entity) in &interaction_query {
-        if *interaction == Interaction::Pressed && *setting != *button_setting {
-            let (previous_button, mut previous_color) = selected_query.single_mut();
-            *previous_color = NORMAL_BUTTON.into();
-            commands.entity(previous_button).remove::<SelectedOption>();
+        if *interaction == Interaction::Pressed && T::get(&settings) != *button_setting {
+            for (previous_button, _, mut previous_color) in &mut selected_query {
+                *previous_color = NORMAL_BUTTON.into();
+                commands.entity(previous_button).remove::<SelectedOption>();
+            }
             commands.entity(entity).insert(SelectedOption);
-            *setting = *button_setting;
+            button_setting.set(&mut settings);
         }
     }
 }
@@ -290,7 +345,9 @@ fn settings_menu_setup(mut commands: Commands) {
                 .with_children(|parent| {
                     for (action, text) in [
                         (MenuButtonAction::SettingsDisplay, "Display"),
-                        //(MenuButtonAction::SettingsSound, "Sound"),
+                        (MenuButtonAction::SettingsSound, "Sound"),
+                        (MenuButtonAction::SettingsGameplay, "Gameplay"),
+                        (MenuButtonAction::SettingsControls, "Controls"),
                         (MenuButtonAction::BackToMainMenu, "Back"),
                     ] {
                         parent
@@ -313,194 +370,281 @@ fn settings_menu_setup(mut commands: Commands) {
         });
 }
 
-// fn display_settings_menu_setup(mut commands: Commands, window_mode: Res<WindowMode>) {
-//     let button_style = Style {
-//         width: Val::Px(200.0),
-//         height: Val::Px(65.0),
-//         margin: UiRect::all(Val::Px(20.0)),
-//         justify_content: JustifyContent::Center,
-//         align_items: AlignItems::Center,
-//         ..default()
-//     };
-//     let button_text_style = TextStyle {
-//         font_size: 40.0,
-//         color: TEXT_COLOR,
-//         ..default()
-//     };
-
-//     commands
-//         .spawn((
-//             NodeBundle {
-//                 style: Style {
-//                     width: Val::Percent(100.0),
-//                     align_items: AlignItems::Center,
-//                     justify_content: JustifyContent::Center,
-//                     ..default()
-//                 },
-//                 ..default()
-//             },
-//             OnDisplaySettingsMenuScreen,
-//         ))
-//         .with_children(|parent| {
-//             parent
-//                 .spawn(NodeBundle {
-//                     style: Style {
-//                         flex_direction: FlexDirection::Column,
-//                         align_items: AlignItems::Center,
-//                         ..default()
-//                     },
-//                     background_color: Color::CRIMSON.into(),
-//                     ..default()
-//                 })
-//                 .with_children(|parent| {
-//                     // Create a new `NodeBundle`, this time not setting its `flex_direction`. It will
-//                     // use the default value, `FlexDirection::Row`, from left to right.
-//                     parent
-//                         .spawn(NodeBundle {
-//                             style: Style {
-//                                 align_items: AlignItems::Center,
-//                                 ..default()
-//                             },
-//                             background_color: Color::CRIMSON.into(),
-//                             ..default()
-//                         })
-//                         .with_children(|parent| {
-//                             // Display a label for the current setting
-//                             parent.spawn(TextBundle::from_section(
-//                                 "Display Quality",
-//                                 button_text_style.clone(),
-//                             ));
-//                             // Display a button for each possible value
-//                             for window_mode_setting in
-//                                 [WindowMode::Windowed, WindowMode::FullScreen]
-//                             {
-//                                 let mut entity = parent.spawn(ButtonBundle {
-//                                     style: Style {
-//                                         width: Val::Px(150.0),
-//                                         height: Val::Px(65.0),
-//                                         ..button_style.clone()
-//                                     },
-//                                     background_color: NORMAL_BUTTON.into(),
-//                                     ..default()
-//                                 });
-//                                 entity.insert(window_mode_setting).with_children(|parent| {
-//                                     parent.spawn(TextBundle::from_section(
-//                                         format!("{window_mode_setting:?}"),
-//                                         button_text_style.clone(),
-//                                     ));
-//                                 });
-//                                 if *window_mode == window_mode_setting {
-//                                     entity.insert(SelectedOption);
-//                                 }
-//                             }
-//                         });
-//                     parent
-//                         .spawn((
-//                             ButtonBundle {
-//                                 style: button_style,
-//                                 background_color: NORMAL_BUTTON.into(),
-//                                 ..default()
-//                             },
-//                             MenuButtonAction::BackToSettings,
-//                         ))
-//                         .with_children(|parent| {
-//                             parent.spawn(TextBundle::from_section("Back", button_text_style));
-//                         });
-//                 });
-//         });
-// }
-
-// fn sound_settings_menu_setup(mut commands: Commands, volume: Res<Volume>) {
-//     let button_style = Style {
-//         width: Val::Px(200.0),
-//         height: Val::Px(65.0),
-//         margin: UiRect::all(Val::Px(20.0)),
-//         justify_content: JustifyContent::Center,
-//         align_items: AlignItems::Center,
-//         ..default()
-//     };
-//     let button_text_style = TextStyle {
-//         font_size: 40.0,
-//         color: TEXT_COLOR,
-//         ..default()
-//     };
-
-//     commands
-//         .spawn((
-//             NodeBundle {
-//                 style: Style {
-//                     width: Val::Percent(100.0),
-//                     align_items: AlignItems::Center,
-//                     justify_content: JustifyContent::Center,
-//                     ..default()
-//                 },
-//                 ..default()
-//             },
-//             OnSoundSettingsMenuScreen,
-//         ))
-//         .with_children(|parent| {
-//             parent
-//                 .spawn(NodeBundle {
-//                     style: Style {
-//                         flex_direction: FlexDirection::Column,
-//                         align_items: AlignItems::Center,
-//                         ..default()
-//                     },
-//                     background_color: Color::CRIMSON.into(),
-//                     ..default()
-//                 })
-//                 .with_children(|parent| {
-//                     parent
-//                         .spawn(NodeBundle {
-//                             style: Style {
-//                                 align_items: AlignItems::Center,
-//                                 ..default()
-//                             },
-//                             background_color: Color::CRIMSON.into(),
-//                             ..default()
-//                         })
-//                         .with_children(|parent| {
-//                             parent.spawn(TextBundle::from_section(
-//                                 "Volume",
-//                                 button_text_style.clone(),
-//                             ));
-//                             for volume_setting in [0, 1, 2, 3, 4, 5, 6, 7, 8, 9] {
-//                                 let mut entity = parent.spawn(ButtonBundle {
-//                                     style: Style {
-//                                         width: Val::Px(30.0),
-//                                         height: Val::Px(65.0),
-//                                         ..button_style.clone()
-//                                     },
-//                                     background_color: NORMAL_BUTTON.into(),
-//                                     ..default()
-//                                 });
-//                                 entity.insert(Volume(volume_setting));
-//                                 if *volume == Volume(volume_setting) {
-//                                     entity.insert(SelectedOption);
-//                                 }
-//                             }
-//                         });
-//                     parent
-//                         .spawn((
-//                             ButtonBundle {
-//                                 style: button_style,
-//                                 background_color: NORMAL_BUTTON.into(),
-//                                 ..default()
-//                             },
-//                             MenuButtonAction::BackToSettings,
-//                         ))
-//                         .with_children(|parent| {
-//                             parent.spawn(TextBundle::from_section("Back", button_text_style));
-//                         });
-//                 });
-//         });
-// }
+fn display_settings_menu_setup(commands: Commands, render_settings: Res<RenderSettings>) {
+    settings_screen(
+        commands,
+        OnDisplaySettingsMenuScreen,
+        |parent, text_style| {
+            setting_row::<FovMode>(parent, "Field of View", &render_settings, 250.0, text_style);
+            setting_row::<DrawDistance>(
+                parent,
+                "Draw Distance",
+                &render_settings,
+                250.0,
+                text_style,
+            );
+        },
+    );
+}
+
+fn sound_settings_menu_setup(commands: Commands, audio_settings: Res<AudioSettings>) {
+    settings_screen(commands, OnSoundSettingsMenuScreen, |parent, text_style| {
+        setting_row::<SoundVolume>(parent, "Sound", &audio_settings, 50.0, text_style);
+        setting_row::<MusicVolume>(parent, "Music", &audio_settings, 50.0, text_style);
+    });
+}
+
+fn gameplay_settings_menu_setup(commands: Commands, gameplay_settings: Res<GameplaySettings>) {
+    settings_screen(
+        commands,
+        OnGameplaySettingsMenuScreen,
+        |parent, text_style| {
+            setting_row::<CombatMode>(parent, "Combat", &gameplay_settings, 250.0, text_style);
+        },
+    );
+}
+
+fn controls_menu_setup(
+    commands: Commands,
+    settings_keys: Res<settings::KeyBindings>,
+    player_keys: Res<player::KeyBindings>,
+    game_speed_keys: Res<game_speed::KeyBindings>,
+) {
+    settings_screen(commands, OnControlsMenuScreen, |parent, text_style| {
+        let text_style = TextStyle {
+            font_size: 24.0,
+            ..text_style.clone()
+        };
+        key_rows(parent, &*player_keys, &text_style);
+        key_rows(parent, &*game_speed_keys, &text_style);
+        key_rows(parent, &*settings_keys, &text_style);
+    });
+}
+
+/// A settings screen, the rows added by `rows` followed by a back button
+fn settings_screen(
+    mut commands: Commands,
+    marker: impl Component,
+    rows: impl FnOnce(&mut ChildBuilder, &TextStyle),
+) {
+    let button_style = Style {
+        width: Val::Px(200.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(20.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font_size: 40.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            marker,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::CRIMSON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    rows(parent, &button_text_style);
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style,
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::BackToSettings,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section("Back", button_text_style));
+                        });
+                });
+        });
+}
+
+/// A label followed by one button for each possible value of the setting
+fn setting_row<T: Setting>(
+    parent: &mut ChildBuilder,
+    label: &str,
+    settings: &T::Settings,
+    button_width: f32,
+    button_text_style: &TextStyle,
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::CRIMSON.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(label, button_text_style.clone()));
+            for setting in T::ALL {
+                let mut entity = parent.spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(button_width),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(20.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                });
+                entity.insert(*setting).with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        setting.label(),
+                        button_text_style.clone(),
+                    ));
+                });
+                if T::get(settings) == *setting {
+                    entity.insert(SelectedOption);
+                }
+            }
+        });
+}
+
+/// Key button waiting for the key to bind, set by clicking it
+#[derive(Resource, Default)]
+struct Rebinding(Option<Entity>);
+
+/// Marks the buttons of the controls menu
+#[derive(Component)]
+struct KeyButton;
+
+/// Action of `R` a key button rebinds, by its index in `keys_mut`
+#[derive(Component)]
+struct KeyAction<R: KeyBindingSet> {
+    index: usize,
+    bindings: PhantomData<R>,
+}
+
+/// A label and the bound key of each action of `bindings`
+fn key_rows<R: KeyBindingSet>(parent: &mut ChildBuilder, bindings: &R, text_style: &TextStyle) {
+    for (index, (label, key)) in bindings.keys().into_iter().enumerate() {
+        parent
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(500.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..default()
+                },
+                background_color: Color::CRIMSON.into(),
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(label, text_style.clone()));
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(200.0),
+                                height: Val::Px(32.0),
+                                margin: UiRect::all(Val::Px(4.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        },
+                        KeyButton,
+                        KeyAction::<R> {
+                            index,
+                            bindings: PhantomData,
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            format!("{key:?}"),
+                            text_style.clone(),
+                        ));
+                    });
+            });
+    }
+}
+
+fn start_rebinding(
+    interaction_query: Query<(&Interaction, Entity), (ButtonInteraction, With<KeyButton>)>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for (interaction, entity) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            rebinding.0 = Some(entity);
+        }
+    }
+}
+
+/// Binds the next key pressed to the waiting button of `R`, and shows the keys of `R` again
+/// when the waiting button changed
+fn rebind_key<R: KeyBindingSet>(
+    keys: Res<Input<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<R>,
+    buttons: Query<(Entity, &KeyAction<R>, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if let Some(entity) = rebinding.0 {
+        if let (Ok((_, action, _)), Some(key)) =
+            (buttons.get(entity), keys.get_just_pressed().next())
+        {
+            if let Some((label, bound)) = bindings.keys_mut().into_iter().nth(action.index) {
+                *bound = *key;
+                info!("{} bound to {:?}", label, key);
+            }
+            rebinding.0 = None;
+        }
+    }
+    if !rebinding.is_changed() {
+        return;
+    }
+
+    let keys = bindings.keys();
+    for (entity, action, children) in &buttons {
+        let text = if rebinding.0 == Some(entity) {
+            "Press a key".to_string()
+        } else {
+            format!("{:?}", keys[action.index].1)
+        };
+        for child in children {
+            if let Ok(mut label) = texts.get_mut(*child) {
+                label.sections[0].value = text.clone();
+            }
+        }
+    }
+}
+
+fn stop_rebinding(mut rebinding: ResMut<Rebinding>) {
+    rebinding.0 = None;
+}
 
 fn menu_action(
-    interaction_query: Query<
-        (&Interaction, &MenuButtonAction),
-        (Changed<Interaction>, With<Button>),
-    >,
+    interaction_query: Query<(&Interaction, &MenuButtonAction), ButtonInteraction>,
     mut app_exit_events: EventWriter<AppExit>,
     mut menu_state: ResMut<NextState<MenuState>>,
     mut game_state: ResMut<NextState<GameState>>,
@@ -515,6 +659,9 @@ fn menu_action(
                 }
                 MenuButtonAction::Settings => menu_state.set(MenuState::Settings),
                 MenuButtonAction::SettingsDisplay => menu_state.set(MenuState::SettingsDisplay),
+                MenuButtonAction::SettingsSound => menu_state.set(MenuState::SettingsSound),
+                MenuButtonAction::SettingsGameplay => menu_state.set(MenuState::SettingsGameplay),
+                MenuButtonAction::SettingsControls => menu_state.set(MenuState::SettingsControls),
                 MenuButtonAction::BackToMainMenu => menu_state.set(MenuState::Main),
                 MenuButtonAction::BackToSettings => menu_state.set(MenuState::Settings),
             }
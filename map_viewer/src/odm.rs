@@ -1,50 +1,118 @@
 use bevy::{
+    ecs::system::SystemParam,
     prelude::{shape::Quad, *},
     render::render_resource::{Face, PrimitiveTopology},
+    tasks::AsyncComputeTaskPool,
 };
 use bevy_mod_billboard::{
     prelude::{BillboardMeshHandle, BillboardPlugin, BillboardTexture},
     BillboardLockAxis, BillboardLockAxisBundle, BillboardTextureBundle,
 };
 
-use std::error::Error;
+use std::{
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
 
-use crate::{despawn_all, utils::random_color, world::WorldSettings, GameState};
+use crate::{
+    crash_report, despawn_all,
+    player::{FlyCam, MovementSettings},
+    utils::random_color,
+    world::{
+        collision::decoration_collider, lights::decoration_light, particles::decoration_emitter,
+        transition::MapArrival, WorldSettings, IMAGE_CACHE_TRIM,
+    },
+    GameState,
+};
 use lod::{
+    billboard::BillboardManager,
+    ddeclist::DDecListItem,
     dtile::TileTable,
-    odm::{Odm, OdmData},
+    map_deps::MapDependencies,
+    odm::{Odm, ODM_TILE_SCALE},
+    progress::ProgressSink,
+    terrain::TerrainMesh,
     LodManager,
 };
 
+/// Map parsing, terrain, models and decoration sprites
+const LOAD_STAGES: usize = 4;
+
 // TODO make it a real bundle
 pub(super) struct OdmBundle {
     pub map: Odm,
     pub mesh: Mesh,
     pub texture: Image,
     pub models: Vec<ModelBundle>,
+    /// One sprite for each of `map.billboards`
+    pub decorations: Vec<DecorationSprite>,
+}
+
+pub(super) struct DecorationSprite {
+    /// Bottom center of the sprite
+    pub position: Vec3,
+    pub image: Image,
+    pub width: f32,
+    pub height: f32,
+    pub d_declist_item: DDecListItem,
 }
 
 // TODO make it a real bundle
 pub(super) struct ModelBundle {
     pub mesh: Mesh,
-    pub bounding_box_mesh: Mesh,
     pub material: StandardMaterial,
 }
 
 impl OdmBundle {
-    pub(super) fn new(lod_manager: &LodManager, map_name: &str) -> Result<Self, Box<dyn Error>> {
+    /// Parses the map, then builds terrain, models and decoration sprites in parallel.
+    pub(super) fn new(
+        lod_manager: &LodManager,
+        map_name: &str,
+        progress: &LoadProgress,
+    ) -> Result<Self, Box<dyn Error>> {
         let map = Odm::new(lod_manager, map_name)?;
-        let tile_table = map.tile_table(lod_manager)?;
-        let mesh = Self::generate_terrain_mesh(&map, &tile_table);
-        let image =
-            bevy::render::texture::Image::from_dynamic(tile_table.atlas_image(lod_manager)?, true);
-        let models = process_models(&map);
+        progress.stage_done();
+        progress.cancel.check()?;
+
+        let (terrain, models, decorations) = std::thread::scope(|s| {
+            let terrain = s.spawn(|| -> Result<(Mesh, Image), String> {
+                let tile_table = map.tile_table(lod_manager).map_err(|e| e.to_string())?;
+                let mesh = Self::generate_terrain_mesh(&map, &tile_table);
+                let atlas = tile_table
+                    .atlas_image_with_progress(lod_manager, &progress.cancel)
+                    .map_err(|e| e.to_string())?;
+                progress.stage_done();
+                Ok((mesh, Image::from_dynamic(atlas, true)))
+            });
+            let models = s.spawn(|| {
+                let models = process_models(&map);
+                progress.stage_done();
+                models
+            });
+            let decorations = s.spawn(|| {
+                let decorations = process_decorations(lod_manager, &map, &progress.cancel);
+                progress.stage_done();
+                decorations
+            });
+
+            let stage_panicked = || "map loading stage panicked".to_string();
+            Ok::<_, String>((
+                terrain.join().map_err(|_| stage_panicked())??,
+                models.join().map_err(|_| stage_panicked())?,
+                decorations.join().map_err(|_| stage_panicked())??,
+            ))
+        })?;
+        let (mesh, texture) = terrain;
 
         Ok(OdmBundle {
             map,
             mesh,
-            texture: image,
+            texture,
             models,
+            decorations,
         })
     }
 
@@ -63,25 +131,12 @@ impl OdmBundle {
     }
 
     fn generate_terrain_mesh(odm: &Odm, tile_table: &TileTable) -> Mesh {
-        let odm_data = OdmData::new(odm, tile_table);
+        let terrain = TerrainMesh::new(odm, tile_table);
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(
-            odm_data.indices.clone(),
-        )));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, odm_data.positions);
-        mesh.duplicate_vertices();
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, odm_data.uvs);
-
-        // let normals = generate_terrain_normals(
-        //     mesh.attribute(Mesh::ATTRIBUTE_POSITION)
-        //         .unwrap()
-        //         .as_float3()
-        //         .unwrap(),
-        //     &odm_data.indices,
-        // );
-        //mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-
-        mesh.compute_flat_normals();
+        mesh.set_indices(Some(bevy::render::mesh::Indices::U32(terrain.indices)));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, terrain.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, terrain.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, terrain.uvs);
         mesh.compute_aabb();
         mesh
     }
@@ -90,7 +145,6 @@ impl OdmBundle {
 fn process_models(map: &Odm) -> Vec<ModelBundle> {
     let mut models = Vec::new();
     for b in &map.bsp_models {
-        let bounding_box_mesh: Mesh = generate_bsp_model_bounding_box(b).into();
         let mesh = generate_bsp_model_mesh(b);
         let material = StandardMaterial {
             base_color: random_color(),
@@ -103,46 +157,40 @@ fn process_models(map: &Odm) -> Vec<ModelBundle> {
             cull_mode: None,
             ..default()
         };
-        models.push(ModelBundle {
-            bounding_box_mesh,
-            mesh,
-            material,
-        });
+        models.push(ModelBundle { mesh, material });
     }
     models
 }
 
-fn generate_terrain_normals(vertices: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
-    let mut normals = vec![[0.0, 0.0, 0.0]; vertices.len()];
-
-    for face_indices in indices.chunks(3) {
-        let v0 = vertices[face_indices[0] as usize];
-        let v1 = vertices[face_indices[1] as usize];
-        let v2 = vertices[face_indices[2] as usize];
-
-        let edge1 = [v1[0] - v0[0], v1[1] - v0[1], v1[2] - v0[2]];
-        let edge2 = [v2[0] - v0[0], v2[1] - v0[1], v2[2] - v0[2]];
-
-        let cross_product = [
-            edge1[1] * edge2[2] - edge1[2] * edge2[1],
-            edge1[2] * edge2[0] - edge1[0] * edge2[2],
-            edge1[0] * edge2[1] - edge1[1] * edge2[0],
-        ];
-
-        for index in face_indices {
-            normals[*index as usize][0] += cross_product[0];
-            normals[*index as usize][1] += cross_product[1];
-            normals[*index as usize][2] += cross_product[2];
-        }
-    }
-
-    for normal in normals.iter_mut() {
-        let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
-        normal[0] /= length;
-        normal[1] /= length;
-        normal[2] /= length;
+fn process_decorations(
+    lod_manager: &LodManager,
+    map: &Odm,
+    cancel: &CancelToken,
+) -> Result<Vec<DecorationSprite>, String> {
+    let sprite_manager = BillboardManager::new(lod_manager).map_err(|e| e.to_string())?;
+    let mut decorations = Vec::with_capacity(map.billboards.len());
+    for b in &map.billboards {
+        cancel.check().map_err(|e| e.to_string())?;
+        // a missing decoration leaves a gap instead of failing the whole map
+        let Some(sprite) = sprite_manager.get(lod_manager, &b.declist_name, b.data.declist_id)
+        else {
+            warn!("Unable to load decoration {}, skipping it", b.declist_name);
+            continue;
+        };
+        let (width, height) = sprite.dimensions();
+        decorations.push(DecorationSprite {
+            position: Vec3::new(
+                b.data.position[0] as f32,
+                b.data.position[2] as f32,
+                -b.data.position[1] as f32,
+            ),
+            image: Image::from_dynamic(sprite.image, true),
+            width,
+            height,
+            d_declist_item: sprite.d_declist_item,
+        });
     }
-    normals
+    Ok(decorations)
 }
 
 fn generate_bsp_model_mesh(model: &lod::bsp_model::BSPModel) -> Mesh {
@@ -156,23 +204,7 @@ fn generate_bsp_model_mesh(model: &lod::bsp_model::BSPModel) -> Mesh {
     mesh
 }
 
-fn generate_bsp_model_bounding_box(model: &lod::bsp_model::BSPModel) -> shape::Box {
-    shape::Box::from_corners(
-        [
-            model.header.bounding_box.min_x as f32,
-            model.header.bounding_box.min_z as f32,
-            -model.header.bounding_box.min_y as f32,
-        ]
-        .into(),
-        [
-            model.header.bounding_box.max_x as f32,
-            model.header.bounding_box.max_z as f32,
-            -model.header.bounding_box.max_y as f32,
-        ]
-        .into(),
-    )
-}
-
+#[derive(Clone, Copy)]
 pub(super) struct OdmName {
     pub x: char,
     pub y: char,
@@ -248,44 +280,244 @@ impl OdmName {
 #[derive(Component)]
 struct CurrentMap;
 
-fn odm_setup(mut commands: Commands) {}
+/// Loads the current map again when coming back from the menu, leaving the game despawned it
+fn odm_setup(mut settings: ResMut<WorldSettings>) {
+    settings.odm_changed = true;
+}
+
+fn cancel_map_load(
+    mut commands: Commands,
+    loading: Option<Res<MapLoading>>,
+    precaching: Option<Res<Precaching>>,
+) {
+    if let Some(loading) = loading {
+        loading.progress.cancel.cancel();
+    }
+    if let Some(precaching) = precaching {
+        precaching.0.cancel();
+    }
+    commands.remove_resource::<MapLoading>();
+    commands.remove_resource::<Precaching>();
+}
+
+/// Cooperative cancellation of the background work, checked between decodes
+#[derive(Clone, Default)]
+pub(crate) struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
 
-fn change_odm(
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn check(&self) -> Result<(), Box<dyn Error>> {
+        if self.is_cancelled() {
+            Err("Cancelled".into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl ProgressSink for CancelToken {
+    fn progress(&self, _done: usize, _total: usize) -> bool {
+        !self.is_cancelled()
+    }
+}
+
+/// Shared between the map loading task and the main thread
+pub(crate) struct LoadProgress {
+    stages_done: AtomicUsize,
+    cancel: CancelToken,
+    result: Mutex<Option<Result<OdmBundle, String>>>,
+}
+
+impl LoadProgress {
+    fn stage_done(&self) {
+        self.stages_done.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Present while a map is loading in the background
+#[derive(Resource)]
+pub(crate) struct MapLoading {
+    pub map: OdmName,
+    progress: Arc<LoadProgress>,
+}
+
+/// Present while the maps around the current one are decoded in the background
+#[derive(Resource)]
+struct Precaching(CancelToken);
+
+impl MapLoading {
+    /// Fraction of the loading stages that are done
+    pub fn progress(&self) -> f32 {
+        self.progress.stages_done.load(Ordering::Relaxed) as f32 / LOAD_STAGES as f32
+    }
+}
+
+/// Starts loading the current map in the background, the previous map stays until it's done.
+fn start_map_load(
     mut commands: Commands,
     mut settings: ResMut<WorldSettings>,
-    mut images: ResMut<Assets<Image>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut billboard_textures: ResMut<Assets<BillboardTexture>>,
-    query: Query<Entity, With<CurrentMap>>,
+    loading: Option<Res<MapLoading>>,
+    precaching: Option<Res<Precaching>>,
 ) {
     if !settings.odm_changed {
         return;
     }
+    settings.odm_changed = false;
 
-    for e in &query {
-        commands.entity(e).despawn_recursive();
+    // the decodes queued for the previous map are wasted work now
+    if let Some(loading) = loading {
+        loading.progress.cancel.cancel();
+    }
+    if let Some(precaching) = precaching {
+        precaching.0.cancel();
     }
 
-    let odm = OdmBundle::new(
-        &settings.lod_manager,
-        settings.current_odm.to_string().as_str(),
-    );
+    let progress = Arc::new(LoadProgress {
+        stages_done: AtomicUsize::new(0),
+        cancel: CancelToken::default(),
+        result: Mutex::new(None),
+    });
+    let lod_manager = settings.lod_manager.clone();
+    let map = settings.current_odm;
+    let task_progress = progress.clone();
+    AsyncComputeTaskPool::get()
+        .spawn(async move {
+            let odm = OdmBundle::new(&lod_manager, &map.to_string(), &task_progress)
+                .map_err(|e| e.to_string());
+            *task_progress.result.lock().unwrap() = Some(odm);
+        })
+        .detach();
 
-    if odm.is_err() {
+    crash_report::record(format!("loading {}", map));
+    // a newer load replaces the previous one, whose result is dropped if it still completes
+    commands.insert_resource(MapLoading { map, progress });
+}
+
+/// Decodes the assets of the maps around `map` in the background, so walking into them is quicker.
+fn precache_adjacent_maps(lod_manager: Arc<LodManager>, map: OdmName) -> CancelToken {
+    let cancel = CancelToken::default();
+    let task_cancel = cancel.clone();
+    AsyncComputeTaskPool::get()
+        .spawn(async move {
+            let adjacent = [map.go_north(), map.go_west(), map.go_south(), map.go_east()];
+            for map in adjacent.into_iter().flatten() {
+                let precached = Odm::new(&lod_manager, &map.to_string())
+                    .and_then(|odm| MapDependencies::new(&lod_manager, &odm))
+                    .and_then(|deps| lod_manager.precache_with_progress(&deps, &task_cancel));
+                if task_cancel.is_cancelled() {
+                    return;
+                }
+                if let Err(e) = precached {
+                    warn!("Unable to precache {}: {}", map, e);
+                }
+            }
+        })
+        .detach();
+    cancel
+}
+
+/// Asset stores the spawned map adds to
+#[derive(SystemParam)]
+struct MapAssets<'w> {
+    images: ResMut<'w, Assets<Image>>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<StandardMaterial>>,
+    billboard_textures: ResMut<'w, Assets<BillboardTexture>>,
+}
+
+/// The party camera, moved when a map is spawned or fails to load
+#[derive(SystemParam)]
+struct PartyPosition<'w, 's> {
+    camera: Query<'w, 's, &'static mut Transform, With<FlyCam>>,
+    movement: Res<'w, MovementSettings>,
+}
+
+impl PartyPosition<'_, '_> {
+    fn place(&mut self, translation: Vec3) {
+        for mut transform in &mut self.camera {
+            transform.translation = translation;
+        }
+    }
+
+    /// Moves the party back from the edge it walked off, so edge travel doesn't retry at once
+    fn pull_inside(&mut self) {
+        let inside = self.movement.max_xz - ODM_TILE_SCALE;
+        for mut transform in &mut self.camera {
+            transform.translation.x = transform.translation.x.clamp(-inside, inside);
+            transform.translation.z = transform.translation.z.clamp(-inside, inside);
+        }
+    }
+}
+
+fn finish_map_load(
+    mut commands: Commands,
+    mut settings: ResMut<WorldSettings>,
+    loading: Option<Res<MapLoading>>,
+    mut assets: MapAssets,
+    mut arrivals: EventWriter<MapArrival>,
+    query: Query<Entity, With<CurrentMap>>,
+    mut party: PartyPosition,
+) {
+    let Some(loading) = loading else {
         return;
+    };
+    let Some(odm) = loading.progress.result.lock().unwrap().take() else {
+        return;
+    };
+    commands.remove_resource::<MapLoading>();
+
+    let odm = match odm {
+        Ok(odm) => odm,
+        Err(e) => {
+            crash_report::record(format!("failed to load {}: {}", loading.map, e));
+            error!("Unable to load {}: {}", loading.map, e);
+            // the party stays on the map that is still spawned
+            if let Some(spawned) = settings.spawned_odm {
+                settings.current_odm = spawned;
+            }
+            if settings.arrival.take().is_some() {
+                party.pull_inside();
+            }
+            return;
+        }
+    };
+
+    crash_report::record(format!("loaded {}", loading.map));
+    settings.spawned_odm = Some(loading.map);
+    for e in &query {
+        commands.entity(e).despawn_recursive();
     }
-    let odm = odm.unwrap();
+    // the new map was decoded last, so it stays cached
+    settings.lod_manager.trim_cache_to(IMAGE_CACHE_TRIM);
+    debug!("Image cache: {:?}", settings.lod_manager.cache_stats());
+    commands.insert_resource(Precaching(precache_adjacent_maps(
+        settings.lod_manager.clone(),
+        loading.map,
+    )));
 
-    let image_handle = images.add(odm.texture.clone());
+    let image_handle = assets.images.add(odm.texture.clone());
     let material = odm.terrain_material(image_handle);
 
+    if let Some(arrival) = settings.arrival.take() {
+        party.place(arrival);
+    }
+    arrivals.send(MapArrival {
+        sky_texture: odm.map.sky_texture.to_lowercase(),
+    });
+
     commands
         .spawn((
             Name::new("odm"),
             PbrBundle {
-                mesh: meshes.add(odm.mesh.clone()),
-                material: materials.add(material),
+                mesh: assets.meshes.add(odm.mesh.clone()),
+                material: assets.materials.add(material),
                 ..default()
             },
             CurrentMap,
@@ -295,40 +527,34 @@ fn change_odm(
                 parent.spawn((
                     Name::new("model"),
                     PbrBundle {
-                        mesh: meshes.add(m.mesh.clone()),
-                        material: materials.add(m.material.clone()),
+                        mesh: assets.meshes.add(m.mesh.clone()),
+                        material: assets.materials.add(m.material.clone()),
 
                         ..default()
                     },
                 ));
             }
 
-            let sprite_manager =
-                lod::billboard::BillboardManager::new(&settings.lod_manager).unwrap();
-
-            for b in odm.map.billboards {
-                let billboard_sprite = sprite_manager
-                    .get(&settings.lod_manager, &b.declist_name, b.data.declist_id)
-                    .unwrap();
-                let (width, height) = billboard_sprite.dimensions();
+            for sprite in odm.decorations {
+                let (width, height) = (sprite.width, sprite.height);
+                let image_handle = assets.images.add(sprite.image);
 
-                let image =
-                    bevy::render::texture::Image::from_dynamic(billboard_sprite.image, true);
-                let image_handle = images.add(image);
+                let light = decoration_light(&sprite.d_declist_item);
 
-                parent.spawn((
+                let mut billboard = parent.spawn((
                     Name::new("billboard"),
                     BillboardLockAxisBundle {
                         billboard_bundle: BillboardTextureBundle {
-                            transform: Transform::from_xyz(
-                                b.data.position[0] as f32,
-                                b.data.position[2] as f32 + height / 2.,
-                                -b.data.position[1] as f32,
+                            transform: Transform::from_translation(
+                                sprite.position + Vec3::Y * height / 2.,
                             ),
-                            texture: billboard_textures
+                            texture: assets
+                                .billboard_textures
                                 .add(BillboardTexture::Single(image_handle.clone())),
                             mesh: BillboardMeshHandle(
-                                meshes.add(Quad::new(Vec2::new(width, height)).into()),
+                                assets
+                                    .meshes
+                                    .add(Quad::new(Vec2::new(width, height)).into()),
                             ),
                             ..default()
                         },
@@ -338,30 +564,25 @@ fn change_odm(
                         },
                     },
                 ));
+
+                if let Some(collider) = decoration_collider(&sprite.d_declist_item, height) {
+                    billboard.insert(collider);
+                }
+
+                if let Some(emitter) = decoration_emitter(&sprite.d_declist_item, height) {
+                    billboard.insert(emitter);
+                }
+
+                if let Some((light, flicker)) = light {
+                    billboard.with_children(|parent| {
+                        let mut light = parent.spawn((Name::new("light"), light));
+                        if let Some(flicker) = flicker {
+                            light.insert(flicker);
+                        }
+                    });
+                }
             }
         });
-
-    settings.odm_changed = false;
-}
-
-fn change_map_input(keys: Res<Input<KeyCode>>, mut settings: ResMut<WorldSettings>) {
-    let new_map = if keys.just_pressed(KeyCode::J) {
-        settings.current_odm.go_north()
-    } else if keys.just_pressed(KeyCode::H) {
-        settings.current_odm.go_west()
-    } else if keys.just_pressed(KeyCode::K) {
-        settings.current_odm.go_south()
-    } else if keys.just_pressed(KeyCode::L) {
-        settings.current_odm.go_east()
-    } else {
-        None
-    };
-
-    if let Some(new_map) = new_map {
-        settings.current_odm = new_map;
-        settings.odm_changed = true;
-        info!("Changing map: {}", &settings.current_odm);
-    }
 }
 
 pub struct OdmPlugin;
@@ -371,9 +592,16 @@ impl Plugin for OdmPlugin {
         app.add_plugins(BillboardPlugin)
             .add_systems(
                 Update,
-                (change_map_input, change_odm).run_if(in_state(GameState::Game)),
+                // the MapLoading a new load inserts must replace the previous one before
+                // finish_map_load can take the previous result
+                (start_map_load, apply_deferred, finish_map_load)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
             )
             .add_systems(OnEnter(GameState::Game), odm_setup)
-            .add_systems(OnExit(GameState::Game), despawn_all::<CurrentMap>);
+            .add_systems(
+                OnExit(GameState::Game),
+                (despawn_all::<CurrentMap>, cancel_map_load),
+            );
     }
 }
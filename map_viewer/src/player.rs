@@ -1,9 +1,12 @@
 use bevy::ecs::event::{Events, ManualEventReader};
 use bevy::input::mouse::MouseMotion;
 use bevy::prelude::*;
-use bevy::window::{CursorGrabMode, PrimaryWindow};
+use bevy::window::{CursorGrabMode, PrimaryWindow, WindowResized};
 
-use crate::GameState;
+use crate::{
+    settings::{KeyBindingSet, RenderSettings},
+    GameState,
+};
 
 /// Keeps track of mouse motion events, pitch, and yaw
 #[derive(Resource, Default)]
@@ -34,7 +37,7 @@ impl Default for MovementSettings {
 }
 
 /// Key configuration
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct KeyBindings {
     pub move_forward: KeyCode,
     pub move_backward: KeyCode,
@@ -59,6 +62,24 @@ impl Default for KeyBindings {
     }
 }
 
+impl KeyBindingSet for KeyBindings {
+    fn keys_mut(&mut self) -> Vec<(&'static str, &mut KeyCode)> {
+        vec![
+            ("Forward", &mut self.move_forward),
+            ("Backward", &mut self.move_backward),
+            ("Turn left", &mut self.rotate_left),
+            ("Turn right", &mut self.rotate_right),
+            ("Fly up", &mut self.move_ascend),
+            ("Fly down", &mut self.move_descend),
+            ("Grab cursor", &mut self.toggle_grab_cursor),
+        ]
+    }
+}
+
+/// Camera position kept while the menu is open, so going back to the game doesn't reset it
+#[derive(Resource, Default)]
+struct SavedCamera(Option<Transform>);
+
 /// Used in queries when you want flycams and not other cameras
 /// A marker component used in queries when you want flycams and not other cameras
 #[derive(Component)]
@@ -79,12 +100,25 @@ fn toggle_grab_cursor(window: &mut Window) {
 }
 
 /// Spawns the `Camera3dBundle` to be controlled
-fn setup_camera(mut commands: Commands) {
+fn setup_camera(
+    mut commands: Commands,
+    saved_camera: Res<SavedCamera>,
+    render_settings: Res<RenderSettings>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let aspect_ratio = primary_window
+        .get_single()
+        .map(|window| window.width() / window.height())
+        .unwrap_or(1.0);
+
     commands.spawn((
         Camera3dBundle {
-            transform: Transform::from_xyz(-9700.0, 400.0, 11300.0).looking_at(Vec3::ZERO, Vec3::Y),
+            transform: saved_camera.0.unwrap_or_else(|| {
+                Transform::from_xyz(-9700.0, 400.0, 11300.0).looking_at(Vec3::ZERO, Vec3::Y)
+            }),
             projection: Projection::Perspective(PerspectiveProjection {
-                fov: 65.0_f32.to_radians(),
+                fov: render_settings.vertical_fov(aspect_ratio),
+                far: render_settings.draw_distance.distance(),
                 ..Default::default()
             }),
             ..Default::default()
@@ -92,17 +126,37 @@ fn setup_camera(mut commands: Commands) {
         FlyCam,
         FogSettings {
             color: Color::rgba(0.02, 0.02, 0.02, 0.70),
-            falloff: FogFalloff::Linear {
-                start: 20000.0,
-                end: 64000.0,
-            },
+            falloff: render_settings.fog_falloff(),
             ..default()
         },
     ));
 }
 
+/// Keeps field of view and draw distance in sync with the render settings and the window size
+fn apply_render_settings(
+    render_settings: Res<RenderSettings>,
+    mut resized: EventReader<WindowResized>,
+    mut query: Query<(&mut Projection, &mut FogSettings), With<FlyCam>>,
+) {
+    let resized = resized.iter().last();
+    if !render_settings.is_changed() && resized.is_none() {
+        return;
+    }
+
+    for (mut projection, mut fog) in &mut query {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            let aspect_ratio = resized
+                .map(|e| e.width / e.height)
+                .unwrap_or(perspective.aspect_ratio);
+            perspective.fov = render_settings.vertical_fov(aspect_ratio);
+            perspective.far = render_settings.draw_distance.distance();
+        }
+        fog.falloff = render_settings.fog_falloff();
+    }
+}
+
 /// Handles keyboard input and movement
-fn player_controls(
+pub(crate) fn player_controls(
     keys: Res<Input<KeyCode>>,
     time: Res<Time>,
     primary_window: Query<&Window, With<PrimaryWindow>>,
@@ -158,7 +212,8 @@ fn handle_movement(
         _ => return, // Ignore keys that are not for movement
     };
 
-    transform.translation += movement * time.delta_seconds() * settings.speed;
+    // raw delta so the player can still move around while the game is paused
+    transform.translation += movement * time.raw_delta_seconds() * settings.speed;
 
     limit_movement_to_game_area(settings, transform);
 }
@@ -212,6 +267,23 @@ fn player_look(
     }
 }
 
+/// Saves and despawns the camera and releases the cursor for the menu
+fn leave_game(
+    mut commands: Commands,
+    mut saved_camera: ResMut<SavedCamera>,
+    camera: Query<(Entity, &Transform), With<FlyCam>>,
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if let Ok((entity, transform)) = camera.get_single() {
+        saved_camera.0 = Some(*transform);
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Ok(mut window) = primary_window.get_single_mut() {
+        window.cursor.grab_mode = CursorGrabMode::None;
+        window.cursor.visible = true;
+    }
+}
+
 fn cursor_grab(
     keys: Res<Input<KeyCode>>,
     key_bindings: Res<KeyBindings>,
@@ -233,10 +305,18 @@ impl Plugin for PlayerPlugin {
         app.init_resource::<InputState>()
             .init_resource::<MovementSettings>()
             .init_resource::<KeyBindings>()
+            .init_resource::<SavedCamera>()
             .add_systems(OnEnter(GameState::Game), setup_camera)
+            .add_systems(OnExit(GameState::Game), leave_game)
             .add_systems(
                 Update,
-                (player_controls, player_look, cursor_grab).run_if(in_state(GameState::Game)),
+                (
+                    player_controls,
+                    player_look,
+                    cursor_grab,
+                    apply_render_settings,
+                )
+                    .run_if(in_state(GameState::Game)),
             );
     }
 }
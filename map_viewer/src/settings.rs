@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+use lod::odm::ODM_TILE_SCALE;
+
+use crate::GameState;
+
+mod original;
+
+/// Horizontal field of view of the original 4:3 viewport
+pub const ORIGINAL_HORIZONTAL_FOV: f32 = 60.0;
+const ORIGINAL_ASPECT_RATIO: f32 = 4.0 / 3.0;
+
+/// Fraction of the draw distance where the fog starts
+const FOG_START: f32 = 0.3;
+
+/// Volume steps of the sound menu, 0 is muted
+const VOLUME_LEVELS: u8 = 10;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RenderSettings>()
+            .init_resource::<AudioSettings>()
+            .init_resource::<GameplaySettings>()
+            .init_resource::<KeyBindings>()
+            .add_systems(Startup, original::import_original_settings)
+            .add_systems(Update, settings_input.run_if(in_state(GameState::Game)));
+    }
+}
+
+/// Key configuration
+#[derive(Resource, Clone)]
+pub struct KeyBindings {
+    pub cycle_draw_distance: KeyCode,
+    pub toggle_fov_mode: KeyCode,
+    /// Opens the main menu from the game, New Game goes back to it
+    pub open_menu: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            cycle_draw_distance: KeyCode::F5,
+            toggle_fov_mode: KeyCode::F6,
+            open_menu: KeyCode::F10,
+        }
+    }
+}
+
+impl KeyBindingSet for KeyBindings {
+    fn keys_mut(&mut self) -> Vec<(&'static str, &mut KeyCode)> {
+        vec![
+            ("Draw distance", &mut self.cycle_draw_distance),
+            ("Field of view", &mut self.toggle_fov_mode),
+            ("Menu", &mut self.open_menu),
+        ]
+    }
+}
+
+/// A `KeyBindings` resource whose keys can be changed from the controls menu
+pub trait KeyBindingSet: Resource + Clone {
+    /// Label and key of each action, in menu order
+    fn keys_mut(&mut self) -> Vec<(&'static str, &mut KeyCode)>;
+
+    fn keys(&self) -> Vec<(&'static str, KeyCode)> {
+        self.clone()
+            .keys_mut()
+            .into_iter()
+            .map(|(label, key)| (label, *key))
+            .collect()
+    }
+}
+
+#[derive(Component, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum FovMode {
+    /// Keeps the original horizontal field of view, wider screens see less above and below
+    Original,
+    /// Keeps the original vertical field of view, wider screens see more on the sides
+    #[default]
+    Widescreen,
+}
+
+#[derive(Component, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum DrawDistance {
+    Near,
+    Medium,
+    #[default]
+    Far,
+}
+
+/// Mode a new game starts combat in, the game switches between them
+#[derive(Component, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum CombatMode {
+    #[default]
+    RealTime,
+    TurnBased,
+}
+
+/// A single option of a settings resource that can be picked from a menu
+pub trait Setting: Component + Copy + PartialEq + std::fmt::Debug {
+    type Settings: Resource;
+    const ALL: &'static [Self];
+
+    fn get(settings: &Self::Settings) -> Self;
+    fn set(self, settings: &mut Self::Settings);
+
+    /// Text of the menu button
+    fn label(self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl Setting for FovMode {
+    type Settings = RenderSettings;
+    const ALL: &'static [Self] = &[FovMode::Original, FovMode::Widescreen];
+
+    fn get(settings: &RenderSettings) -> Self {
+        settings.fov_mode
+    }
+
+    fn set(self, settings: &mut RenderSettings) {
+        settings.fov_mode = self;
+    }
+}
+
+impl Setting for DrawDistance {
+    type Settings = RenderSettings;
+    const ALL: &'static [Self] = &[DrawDistance::Near, DrawDistance::Medium, DrawDistance::Far];
+
+    fn get(settings: &RenderSettings) -> Self {
+        settings.draw_distance
+    }
+
+    fn set(self, settings: &mut RenderSettings) {
+        settings.draw_distance = self;
+    }
+}
+
+impl Setting for CombatMode {
+    type Settings = GameplaySettings;
+    const ALL: &'static [Self] = &[CombatMode::RealTime, CombatMode::TurnBased];
+
+    fn get(settings: &GameplaySettings) -> Self {
+        settings.combat_mode
+    }
+
+    fn set(self, settings: &mut GameplaySettings) {
+        settings.combat_mode = self;
+    }
+}
+
+/// A volume setting of [`AudioSettings`], one type per slider so each menu row has its own
+/// buttons
+macro_rules! volume_setting {
+    ($(#[$meta:meta])* $name:ident, $field:ident) => {
+        $(#[$meta])*
+        #[derive(Component, Clone, Copy, Eq, PartialEq, Debug)]
+        pub struct $name(pub u8);
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self(VOLUME_LEVELS - 1)
+            }
+        }
+
+        impl Setting for $name {
+            type Settings = AudioSettings;
+            const ALL: &'static [Self] = &{
+                let mut levels = [Self(0); VOLUME_LEVELS as usize];
+                let mut i = 0;
+                while i < levels.len() {
+                    levels[i] = Self(i as u8);
+                    i += 1;
+                }
+                levels
+            };
+
+            fn get(settings: &AudioSettings) -> Self {
+                settings.$field
+            }
+
+            fn set(self, settings: &mut AudioSettings) {
+                settings.$field = self;
+            }
+
+            fn label(self) -> String {
+                self.0.to_string()
+            }
+        }
+    };
+}
+
+volume_setting!(
+    /// Volume of the sound effects
+    SoundVolume,
+    sound_volume
+);
+volume_setting!(
+    /// Volume of the music
+    MusicVolume,
+    music_volume
+);
+
+impl FovMode {
+    pub fn next(&self) -> Self {
+        match self {
+            FovMode::Original => FovMode::Widescreen,
+            FovMode::Widescreen => FovMode::Original,
+        }
+    }
+}
+
+impl DrawDistance {
+    pub fn next(&self) -> Self {
+        match self {
+            DrawDistance::Near => DrawDistance::Medium,
+            DrawDistance::Medium => DrawDistance::Far,
+            DrawDistance::Far => DrawDistance::Near,
+        }
+    }
+
+    pub fn distance(&self) -> f32 {
+        let tiles = match self {
+            DrawDistance::Near => 32.,
+            DrawDistance::Medium => 64.,
+            DrawDistance::Far => 128.,
+        };
+        tiles * ODM_TILE_SCALE
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct RenderSettings {
+    pub fov_mode: FovMode,
+    pub draw_distance: DrawDistance,
+}
+
+/// Volumes picked in the sound menu, nothing plays audio yet so they are only kept
+#[derive(Resource, Default)]
+pub struct AudioSettings {
+    pub sound_volume: SoundVolume,
+    pub music_volume: MusicVolume,
+}
+
+#[derive(Resource, Default)]
+pub struct GameplaySettings {
+    pub combat_mode: CombatMode,
+}
+
+impl RenderSettings {
+    /// Vertical field of view in radians for the given viewport aspect ratio
+    pub fn vertical_fov(&self, aspect_ratio: f32) -> f32 {
+        let aspect_ratio = match self.fov_mode {
+            FovMode::Original => aspect_ratio,
+            FovMode::Widescreen => ORIGINAL_ASPECT_RATIO,
+        };
+        let half_horizontal_fov = ORIGINAL_HORIZONTAL_FOV.to_radians() / 2.;
+        2. * (half_horizontal_fov.tan() / aspect_ratio).atan()
+    }
+
+    pub fn fog_falloff(&self) -> FogFalloff {
+        let distance = self.draw_distance.distance();
+        FogFalloff::Linear {
+            start: distance * FOG_START,
+            end: distance,
+        }
+    }
+}
+
+fn settings_input(
+    keys: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut render_settings: ResMut<RenderSettings>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if keys.just_pressed(key_bindings.open_menu) {
+        game_state.set(GameState::Menu);
+    } else if keys.just_pressed(key_bindings.cycle_draw_distance) {
+        render_settings.draw_distance = render_settings.draw_distance.next();
+        info!("Draw distance: {:?}", render_settings.draw_distance);
+    } else if keys.just_pressed(key_bindings.toggle_fov_mode) {
+        render_settings.fov_mode = render_settings.fov_mode.next();
+        info!("Field of view mode: {:?}", render_settings.fov_mode);
+    }
+}
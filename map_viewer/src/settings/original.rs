@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use lod::{get_lod_path, install::find_data_dir};
+
+use super::{DrawDistance, RenderSettings, Setting};
+
+/// Configuration files of the original games and of their common patches
+const CONFIG_FILES: [&str; 3] = ["mm6.ini", "mm7.ini", "mm8.ini"];
+/// Registry exports are matched by extension, the games keep their settings in the registry
+const REGISTRY_EXPORT_EXTENSION: &str = "reg";
+
+/// Names of the view distance in the patch ini files and in the registry
+const VIEW_DISTANCE_KEYS: [&str; 2] = ["viewdistanced3d", "view_distance"];
+
+/// Seeds the render settings with the preferences of an existing install, looking next to the
+/// lod files and in the game directory above them.
+pub(super) fn import_original_settings(mut render_settings: ResMut<RenderSettings>) {
+    let data_dir = find_data_dir(get_lod_path());
+    let config: HashMap<String, i64> = [Some(data_dir.as_path()), data_dir.parent()]
+        .into_iter()
+        .flatten()
+        .flat_map(config_files)
+        .filter_map(|path| fs::read(path).ok())
+        .flat_map(|data| parse_config(&String::from_utf8_lossy(&data)))
+        .collect();
+    if config.is_empty() {
+        return;
+    }
+
+    if let Some(distance) = VIEW_DISTANCE_KEYS.iter().find_map(|key| config.get(*key)) {
+        let draw_distance = DrawDistance::ALL
+            .iter()
+            .copied()
+            .min_by_key(|d| (d.distance() - *distance as f32).abs() as i64)
+            .unwrap_or_default();
+        draw_distance.set(&mut render_settings);
+        info!("Imported draw distance: {:?}", draw_distance);
+    }
+}
+
+fn config_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let is_registry_export = path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case(REGISTRY_EXPORT_EXTENSION));
+            CONFIG_FILES.contains(&name.as_str()) || is_registry_export
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Integer values of `key=value` ini lines and `"key"=dword:hex` registry export lines,
+/// keys are lowercased. Anything else, sections included, is skipped.
+fn parse_config(text: &str) -> Vec<(String, i64)> {
+    text.lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            let key = key.trim().trim_matches('"').to_lowercase();
+            let value = value.split(';').next()?.trim();
+            let value = match value.strip_prefix("dword:") {
+                Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+                None => value.parse().ok()?,
+            };
+            Some((key, value))
+        })
+        .collect()
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use bevy::prelude::*;
 
 use lod::{
@@ -12,27 +14,52 @@ use crate::{
     GameState,
 };
 
-use self::{sky::SkyPlugin, sun::SunPlugin};
+use self::{
+    collision::CollisionPlugin, game_speed::GameSpeedPlugin, lights::LightsPlugin,
+    particles::ParticlesPlugin, sky::SkyPlugin, sun::SunPlugin, transition::TransitionPlugin,
+};
 
+pub(crate) mod collision;
+pub(crate) mod game_speed;
+pub(crate) mod lights;
+pub(crate) mod particles;
 pub(crate) mod sky;
 pub(crate) mod sun;
+pub(crate) mod transition;
+
+/// Memory for decoded images, enough for a map and its neighbors
+const IMAGE_CACHE_BUDGET: usize = 512 * 1024 * 1024;
+/// What's left of the image cache after a map is unloaded
+pub(crate) const IMAGE_CACHE_TRIM: usize = IMAGE_CACHE_BUDGET / 2;
 
 #[derive(Component)]
 pub(super) struct InWorld;
 
 #[derive(Resource)]
 pub(super) struct WorldSettings {
-    pub lod_manager: LodManager,
+    /// Shared with the background map loading tasks
+    pub lod_manager: Arc<LodManager>,
     pub current_odm: OdmName,
+    /// Map whose entities are spawned, `current_odm` goes back to it when a load fails
+    pub spawned_odm: Option<OdmName>,
     pub odm_changed: bool,
+    /// Where the party is placed once the map being loaded is spawned
+    pub arrival: Option<Vec3>,
 }
 
 impl Default for WorldSettings {
     fn default() -> Self {
         Self {
-            lod_manager: LodManager::new(lod::get_lod_path()).expect("unable to load lod files"),
+            lod_manager: Arc::new(
+                LodManager::builder()
+                    .cache_size(IMAGE_CACHE_BUDGET)
+                    .build()
+                    .expect("unable to load lod files"),
+            ),
             current_odm: OdmName::default(),
+            spawned_odm: None,
             odm_changed: true,
+            arrival: None,
         }
     }
 }
@@ -47,7 +74,17 @@ impl Plugin for WorldPlugin {
                 max_y: ODM_TILE_SCALE * ODM_HEIGHT_SCALE / 2.0,
                 ..Default::default()
             })
-            .add_plugins((PlayerPlugin, SunPlugin, SkyPlugin, OdmPlugin))
+            .add_plugins((
+                PlayerPlugin,
+                SunPlugin,
+                SkyPlugin,
+                OdmPlugin,
+                TransitionPlugin,
+                LightsPlugin,
+                ParticlesPlugin,
+                CollisionPlugin,
+                GameSpeedPlugin,
+            ))
             .add_systems(OnExit(GameState::Game), despawn_all::<InWorld>);
     }
 }
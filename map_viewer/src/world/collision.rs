@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use lod::ddeclist::DDecListItem;
+
+use crate::{
+    player::{player_controls, FlyCam},
+    GameState,
+};
+
+/// Horizontal radius of the party when colliding with decorations
+const PARTY_RADIUS: f32 = 32.0;
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            decoration_collisions
+                .after(player_controls)
+                .run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+/// Vertical cylinder blocking movement, `bottom` and `top` are relative to the entity position.
+#[derive(Component)]
+pub(crate) struct Collider {
+    radius: f32,
+    bottom: f32,
+    top: f32,
+}
+
+/// Collision cylinder from the declist radius and height, the sprite is centered on its entity.
+pub(crate) fn decoration_collider(item: &DDecListItem, sprite_height: f32) -> Option<Collider> {
+    if item.radius == 0 || item.is_no_block_movement() {
+        return None;
+    }
+    let bottom = -sprite_height / 2.;
+    Some(Collider {
+        radius: item.radius as f32,
+        bottom,
+        top: bottom + item.height as f32,
+    })
+}
+
+/// Pushes the camera out of the colliders it walked into. Only the part of the movement towards
+/// the cylinder is removed, so the player slides around it.
+fn decoration_collisions(
+    colliders: Query<(&GlobalTransform, &Collider)>,
+    mut camera: Query<&mut Transform, With<FlyCam>>,
+) {
+    let Ok(mut transform) = camera.get_single_mut() else {
+        return;
+    };
+
+    for (collider_transform, collider) in &colliders {
+        let center = collider_transform.translation();
+        let position = transform.translation;
+        if position.y < center.y + collider.bottom || position.y > center.y + collider.top {
+            continue;
+        }
+
+        let offset = Vec2::new(position.x - center.x, position.z - center.z);
+        let min_distance = collider.radius + PARTY_RADIUS;
+        let distance = offset.length();
+        if distance >= min_distance {
+            continue;
+        }
+
+        let direction = if distance > f32::EPSILON {
+            offset / distance
+        } else {
+            Vec2::X
+        };
+        let pushed = Vec2::new(center.x, center.z) + direction * min_distance;
+        transform.translation.x = pushed.x;
+        transform.translation.z = pushed.y;
+    }
+}
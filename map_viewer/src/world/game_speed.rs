@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+
+use crate::{settings::KeyBindingSet, GameState};
+
+/// Relative speeds of the simulation, mostly useful to speed up testing
+const GAME_SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED_INDEX: usize = 2;
+
+pub struct GameSpeedPlugin;
+
+impl Plugin for GameSpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeyBindings>()
+            .init_resource::<GameSpeed>()
+            .add_systems(Update, game_speed_input.run_if(in_state(GameState::Game)))
+            .add_systems(OnExit(GameState::Game), reset_game_speed);
+    }
+}
+
+/// Key configuration
+#[derive(Resource, Clone)]
+pub struct KeyBindings {
+    pub toggle_pause: KeyCode,
+    pub speed_up: KeyCode,
+    pub slow_down: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            toggle_pause: KeyCode::P,
+            speed_up: KeyCode::Equals,
+            slow_down: KeyCode::Minus,
+        }
+    }
+}
+
+impl KeyBindingSet for KeyBindings {
+    fn keys_mut(&mut self) -> Vec<(&'static str, &mut KeyCode)> {
+        vec![
+            ("Pause", &mut self.toggle_pause),
+            ("Speed up", &mut self.speed_up),
+            ("Slow down", &mut self.slow_down),
+        ]
+    }
+}
+
+/// Index into `GAME_SPEEDS` of the current simulation speed
+#[derive(Resource)]
+struct GameSpeed(usize);
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        Self(NORMAL_SPEED_INDEX)
+    }
+}
+
+/// Pausing and speed changes go through the bevy `Time`, so everything driven by its delta
+/// follows along. Player movement uses the raw delta and keeps working while paused.
+fn game_speed_input(
+    keys: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut game_speed: ResMut<GameSpeed>,
+    mut time: ResMut<Time>,
+) {
+    if keys.just_pressed(key_bindings.toggle_pause) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+        info!("Paused: {}", time.is_paused());
+        return;
+    }
+
+    let index = if keys.just_pressed(key_bindings.speed_up) {
+        (game_speed.0 + 1).min(GAME_SPEEDS.len() - 1)
+    } else if keys.just_pressed(key_bindings.slow_down) {
+        game_speed.0.saturating_sub(1)
+    } else {
+        return;
+    };
+    game_speed.0 = index;
+    time.set_relative_speed(GAME_SPEEDS[index]);
+    info!("Game speed: {}x", GAME_SPEEDS[index]);
+}
+
+fn reset_game_speed(mut game_speed: ResMut<GameSpeed>, mut time: ResMut<Time>) {
+    *game_speed = GameSpeed::default();
+    time.set_relative_speed(GAME_SPEEDS[NORMAL_SPEED_INDEX]);
+    time.unpause();
+}
@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use lod::ddeclist::DDecListItem;
+
+use crate::{player::FlyCam, GameState};
+
+/// Scales the decoration light radius into bevy luminous power.
+const LIGHT_INTENSITY_SCALE: f32 = 400.0;
+const LIGHT_COLOR: Color = Color::rgb(1.0, 0.75, 0.45);
+/// Radius of a Torch Light cast with normal power, four tiles
+const TORCH_LIGHT_RADIUS: f32 = 2048.0;
+
+pub struct LightsPlugin;
+
+impl Plugin for LightsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PartyLight>().add_systems(
+            Update,
+            (attach_party_light, update_party_light, flicker_lights)
+                .run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+/// Makes a light oscillate around its base intensity.
+#[derive(Component)]
+pub(crate) struct Flicker {
+    intensity: f32,
+    frequency: f32,
+}
+
+/// Light carried by the party, like the one of Torch Light. The radius grows with the power of
+/// the spell, 0 puts the light out.
+#[derive(Resource)]
+pub(crate) struct PartyLight {
+    pub radius: f32,
+}
+
+impl Default for PartyLight {
+    fn default() -> Self {
+        Self {
+            radius: TORCH_LIGHT_RADIUS,
+        }
+    }
+}
+
+impl PartyLight {
+    fn apply(&self, light: &mut PointLight) {
+        light.range = self.radius;
+        light.intensity = self.radius * LIGHT_INTENSITY_SCALE;
+    }
+}
+
+/// Marks the point light following the party camera
+#[derive(Component)]
+struct PartyLightSource;
+
+/// Gives the party camera its light, the camera is spawned again each time the game starts
+fn attach_party_light(
+    mut commands: Commands,
+    party_light: Res<PartyLight>,
+    cameras: Query<Entity, Added<FlyCam>>,
+) {
+    for camera in &cameras {
+        let mut point_light = PointLight {
+            color: LIGHT_COLOR,
+            shadows_enabled: false,
+            ..default()
+        };
+        party_light.apply(&mut point_light);
+        commands.entity(camera).with_children(|parent| {
+            parent.spawn((
+                Name::new("party light"),
+                PointLightBundle {
+                    point_light,
+                    ..default()
+                },
+                PartyLightSource,
+            ));
+        });
+    }
+}
+
+fn update_party_light(
+    party_light: Res<PartyLight>,
+    mut lights: Query<&mut PointLight, With<PartyLightSource>>,
+) {
+    if !party_light.is_changed() {
+        return;
+    }
+    for mut light in &mut lights {
+        party_light.apply(&mut light);
+    }
+}
+
+/// Point light for decorations that emit light, like torches and campfires.
+pub(crate) fn decoration_light(item: &DDecListItem) -> Option<(PointLightBundle, Option<Flicker>)> {
+    if item.light_radius == 0 {
+        return None;
+    }
+
+    let range = item.light_radius as f32;
+    let intensity = range * LIGHT_INTENSITY_SCALE;
+    let light = PointLightBundle {
+        point_light: PointLight {
+            color: LIGHT_COLOR,
+            intensity,
+            range,
+            shadows_enabled: false,
+            ..default()
+        },
+        ..default()
+    };
+
+    let frequency = if item.is_flicker_fast() {
+        Some(12.0)
+    } else if item.is_flicker_medium() {
+        Some(6.0)
+    } else if item.is_flicker_slow() {
+        Some(2.0)
+    } else {
+        None
+    };
+
+    Some((
+        light,
+        frequency.map(|frequency| Flicker {
+            intensity,
+            frequency,
+        }),
+    ))
+}
+
+fn flicker_lights(time: Res<Time>, mut query: Query<(&mut PointLight, &Flicker)>) {
+    let t = time.elapsed_seconds();
+    for (mut light, flicker) in &mut query {
+        let wave = (t * flicker.frequency).sin() * 0.5 + (t * flicker.frequency * 2.3).sin() * 0.25;
+        light.intensity = flicker.intensity * (1.0 + 0.2 * wave);
+    }
+}
@@ -0,0 +1,224 @@
+use bevy::{
+    prelude::{shape::Quad, *},
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use bevy_mod_billboard::{
+    prelude::{BillboardMeshHandle, BillboardTexture},
+    BillboardTextureBundle,
+};
+use lod::ddeclist::DDecListItem;
+
+use crate::{despawn_all, GameState};
+
+use super::InWorld;
+
+const PARTICLE_TEXTURE_SIZE: u32 = 16;
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), particles_setup)
+            .add_systems(
+                Update,
+                (emit_particles, update_particles).run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), despawn_all::<Particle>);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum ParticleKind {
+    Spark,
+    Smoke,
+}
+
+impl ParticleKind {
+    /// Seconds between two emitted particles
+    fn emit_interval(&self) -> f32 {
+        match self {
+            ParticleKind::Spark => 0.08,
+            ParticleKind::Smoke => 0.4,
+        }
+    }
+
+    fn lifetime(&self) -> f32 {
+        match self {
+            ParticleKind::Spark => 0.8,
+            ParticleKind::Smoke => 4.0,
+        }
+    }
+
+    fn size(&self) -> f32 {
+        match self {
+            ParticleKind::Spark => 8.0,
+            ParticleKind::Smoke => 64.0,
+        }
+    }
+
+    fn velocity(&self) -> Vec3 {
+        let spread = Vec3::new(rand::random::<f32>() - 0.5, 0., rand::random::<f32>() - 0.5);
+        match self {
+            ParticleKind::Spark => spread * 120. + Vec3::Y * (150. + rand::random::<f32>() * 100.),
+            ParticleKind::Smoke => spread * 40. + Vec3::Y * 80.,
+        }
+    }
+
+    fn color(&self) -> [u8; 4] {
+        match self {
+            ParticleKind::Spark => [255, 180, 60, 255],
+            ParticleKind::Smoke => [90, 90, 90, 120],
+        }
+    }
+}
+
+/// Periodically spawns particles from the position of its entity.
+#[derive(Component)]
+pub(crate) struct ParticleEmitter {
+    kind: ParticleKind,
+    offset: Vec3,
+    timer: Timer,
+}
+
+impl ParticleEmitter {
+    pub(crate) fn new(kind: ParticleKind, offset: Vec3) -> Self {
+        Self {
+            kind,
+            offset,
+            timer: Timer::from_seconds(kind.emit_interval(), TimerMode::Repeating),
+        }
+    }
+}
+
+/// Emitter for decorations flagged as emitting fire or smoke, placed at the top of the sprite.
+pub(crate) fn decoration_emitter(item: &DDecListItem, height: f32) -> Option<ParticleEmitter> {
+    let offset = Vec3::Y * height / 2.;
+    if item.is_emit_fire() {
+        Some(ParticleEmitter::new(ParticleKind::Spark, offset))
+    } else if item.is_emit_smoke() {
+        Some(ParticleEmitter::new(ParticleKind::Smoke, offset))
+    } else {
+        None
+    }
+}
+
+#[derive(Component)]
+struct Particle {
+    kind: ParticleKind,
+    velocity: Vec3,
+    age: Timer,
+}
+
+#[derive(Resource)]
+struct ParticleAssets {
+    spark: Handle<BillboardTexture>,
+    smoke: Handle<BillboardTexture>,
+    mesh: Handle<Mesh>,
+}
+
+impl ParticleAssets {
+    fn texture(&self, kind: ParticleKind) -> Handle<BillboardTexture> {
+        match kind {
+            ParticleKind::Spark => self.spark.clone(),
+            ParticleKind::Smoke => self.smoke.clone(),
+        }
+    }
+}
+
+fn particles_setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut billboard_textures: ResMut<Assets<BillboardTexture>>,
+) {
+    let mut texture = |kind: ParticleKind| {
+        let image = images.add(soft_dot_image(kind.color()));
+        billboard_textures.add(BillboardTexture::Single(image))
+    };
+
+    commands.insert_resource(ParticleAssets {
+        spark: texture(ParticleKind::Spark),
+        smoke: texture(ParticleKind::Smoke),
+        mesh: meshes.add(Quad::new(Vec2::ONE).into()),
+    });
+}
+
+/// Round particle fading out towards the border
+fn soft_dot_image(color: [u8; 4]) -> Image {
+    let size = PARTICLE_TEXTURE_SIZE;
+    let center = (size as f32 - 1.) / 2.;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let distance = Vec2::new(x as f32 - center, y as f32 - center).length() / center;
+            let falloff = (1. - distance).clamp(0., 1.);
+            data.extend_from_slice(&[
+                color[0],
+                color[1],
+                color[2],
+                (color[3] as f32 * falloff) as u8,
+            ]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+fn emit_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<ParticleAssets>,
+    mut query: Query<(&GlobalTransform, &mut ParticleEmitter)>,
+) {
+    for (transform, mut emitter) in &mut query {
+        if !emitter.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let kind = emitter.kind;
+        commands.spawn((
+            Name::new("particle"),
+            BillboardTextureBundle {
+                transform: Transform::from_translation(transform.translation() + emitter.offset)
+                    .with_scale(Vec3::splat(kind.size())),
+                texture: assets.texture(kind),
+                mesh: BillboardMeshHandle(assets.mesh.clone()),
+                ..default()
+            },
+            Particle {
+                kind,
+                velocity: kind.velocity(),
+                age: Timer::from_seconds(kind.lifetime(), TimerMode::Once),
+            },
+            InWorld,
+        ));
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut Particle)>,
+) {
+    for (entity, mut transform, mut particle) in &mut query {
+        if particle.age.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let life = particle.age.percent_left();
+        let size = match particle.kind {
+            ParticleKind::Spark => particle.kind.size() * life,
+            // smoke grows while it fades away
+            ParticleKind::Smoke => particle.kind.size() * (2. - life),
+        };
+        transform.translation += particle.velocity * time.delta_seconds();
+        transform.scale = Vec3::splat(size);
+    }
+}
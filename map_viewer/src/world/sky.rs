@@ -2,17 +2,21 @@ use bevy::prelude::*;
 
 use crate::{despawn_all, GameState};
 
-use super::{InWorld, WorldSettings};
+use super::{transition::MapArrival, InWorld, WorldSettings};
 
 pub struct SkyPlugin;
 
 impl Plugin for SkyPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Game), sky_setup)
+            .add_systems(Update, update_sky.run_if(in_state(GameState::Game)))
             .add_systems(OnExit(GameState::Game), despawn_all::<InWorld>);
     }
 }
 
+#[derive(Component)]
+struct Sky;
+
 fn sky_setup(
     mut commands: Commands,
     mut images: ResMut<Assets<Image>>,
@@ -26,19 +30,47 @@ fn sky_setup(
     );
     let image_handle = images.add(image);
 
-    commands.spawn(PbrBundle {
-        mesh: meshes.add(Mesh::from(shape::Box::default())),
-        material: materials.add(StandardMaterial {
-            //base_color: Color::hex("4488dd").unwrap(),
-            base_color_texture: Some(image_handle),
-            unlit: true,
-            flip_normal_map_y: true,
-            alpha_mode: AlphaMode::Opaque,
-            fog_enabled: false,
-            cull_mode: None,
+    commands.spawn((
+        Name::new("sky"),
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box::default())),
+            material: materials.add(StandardMaterial {
+                //base_color: Color::hex("4488dd").unwrap(),
+                base_color_texture: Some(image_handle),
+                unlit: true,
+                flip_normal_map_y: true,
+                alpha_mode: AlphaMode::Opaque,
+                fog_enabled: false,
+                cull_mode: None,
+                ..default()
+            }),
+            transform: Transform::from_scale(Vec3::splat(10_000_000.0)),
             ..default()
-        }),
-        transform: Transform::from_scale(Vec3::splat(10_000_000.0)),
-        ..default()
-    });
+        },
+        Sky,
+    ));
+}
+
+/// Switches the sky texture to the one of the map we just arrived in
+fn update_sky(
+    mut arrivals: EventReader<MapArrival>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    settings: Res<WorldSettings>,
+    query: Query<&Handle<StandardMaterial>, With<Sky>>,
+) {
+    let Some(arrival) = arrivals.iter().last() else {
+        return;
+    };
+    let Some(sky) = settings.lod_manager.bitmap(&arrival.sky_texture) else {
+        warn!("Unable to load sky texture {}", arrival.sky_texture);
+        return;
+    };
+    let image_handle = images.add(bevy::render::texture::Image::from_dynamic(sky, true));
+
+    for handle in &query {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color_texture = Some(image_handle.clone());
+        }
+    }
 }
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use lod::odm::ODM_TILE_SCALE;
+
+use crate::{
+    odm::OdmName,
+    player::{FlyCam, MovementSettings},
+    GameState,
+};
+
+use super::WorldSettings;
+
+/// Asks the world to move the party to another map.
+/// When `arrival` is set the party is placed there once the map is spawned, otherwise it keeps
+/// its position.
+#[derive(Event)]
+pub(crate) struct MapTransition {
+    pub map: OdmName,
+    pub arrival: Option<Vec3>,
+}
+
+/// Sent once the destination map has been spawned, carries the environment of the new map.
+#[derive(Event)]
+pub(crate) struct MapArrival {
+    pub sky_texture: String,
+}
+
+pub struct TransitionPlugin;
+
+impl Plugin for TransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MapTransition>()
+            .add_event::<MapArrival>()
+            .add_systems(
+                Update,
+                ((change_map_input, edge_travel), map_transition)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+    }
+}
+
+fn change_map_input(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<WorldSettings>,
+    mut transitions: EventWriter<MapTransition>,
+) {
+    let new_map = if keys.just_pressed(KeyCode::J) {
+        settings.current_odm.go_north()
+    } else if keys.just_pressed(KeyCode::H) {
+        settings.current_odm.go_west()
+    } else if keys.just_pressed(KeyCode::K) {
+        settings.current_odm.go_south()
+    } else if keys.just_pressed(KeyCode::L) {
+        settings.current_odm.go_east()
+    } else {
+        None
+    };
+
+    if let Some(map) = new_map {
+        transitions.send(MapTransition { map, arrival: None });
+    }
+}
+
+/// Walking off the edge of the play area takes the party to the adjacent map,
+/// arriving on the opposite edge one tile inside so it doesn't bounce back.
+/// The game reads the destination, its arrival point and the days the walk takes from the
+/// travel table of its executable, which isn't extracted. Until it is, the party arrives at its
+/// mirrored position and no time passes. A failed load pulls the party back inside the edge.
+fn edge_travel(
+    settings: Res<WorldSettings>,
+    movement_settings: Res<MovementSettings>,
+    query: Query<&Transform, With<FlyCam>>,
+    mut transitions: EventWriter<MapTransition>,
+) {
+    let Ok(transform) = query.get_single() else {
+        return;
+    };
+    // the party is still past the edge until it arrives on the new map
+    if settings.arrival.is_some() {
+        return;
+    }
+    let position = transform.translation;
+    let max_xz = movement_settings.max_xz;
+    let arrival_xz = max_xz - ODM_TILE_SCALE;
+
+    // north is towards -z
+    let (map, arrival) = if position.z <= -max_xz {
+        (
+            settings.current_odm.go_north(),
+            Vec3::new(position.x, position.y, arrival_xz),
+        )
+    } else if position.z >= max_xz {
+        (
+            settings.current_odm.go_south(),
+            Vec3::new(position.x, position.y, -arrival_xz),
+        )
+    } else if position.x <= -max_xz {
+        (
+            settings.current_odm.go_west(),
+            Vec3::new(arrival_xz, position.y, position.z),
+        )
+    } else if position.x >= max_xz {
+        (
+            settings.current_odm.go_east(),
+            Vec3::new(-arrival_xz, position.y, position.z),
+        )
+    } else {
+        return;
+    };
+
+    if let Some(map) = map {
+        transitions.send(MapTransition {
+            map,
+            arrival: Some(arrival),
+        });
+    }
+}
+
+fn map_transition(
+    mut transitions: EventReader<MapTransition>,
+    mut settings: ResMut<WorldSettings>,
+) {
+    // Only the last request matters if several triggers fired in the same frame
+    let Some(transition) = transitions.iter().last() else {
+        return;
+    };
+
+    settings.current_odm = transition.map;
+    settings.odm_changed = true;
+    settings.arrival = transition.arrival;
+    info!("Changing map: {}", &settings.current_odm);
+}